@@ -1,20 +1,106 @@
-use crate::model::model::{Config, StatefulList, StoredConnection};
+use crate::broadcast::Broadcast;
+use crate::export::ExportForm;
+use crate::import::{self, ImportPreview};
+use crate::input::LineEditor;
+use crate::keys::{KeySetup, Step};
+use crate::logging::{self, Action, Target};
+use crate::model::model::{Config, Defaults, StatefulList, StoredConnection};
+use crate::mounts::{Mount, MountsPanel};
+use crate::preview::Preview;
+use crate::probe::Probes;
+use crate::remote::RemoteCommand;
+use crate::session::Session;
+use crate::status::StatusLog;
+use crate::templates::{self, TemplatePicker};
 use crate::terminal::{centered_rect, InputMode};
-use crate::utils::{append_json_to_file, edit_connection_and_save, write_json_to_file};
+use crate::transfer::{Transfer, TransferForm};
+use crate::utils::{
+    append_connections_and_save, append_json_to_file, assign_missing_ids, copy_to_clipboard,
+    edit_connection_and_save, expand_home, format_assignments, is_valid_host, new_id,
+    parse_assignments, remove_connection_and_save, write_json_to_file,
+};
+use crate::wake::{self, Waking};
 use crossterm::event;
-use crossterm::event::{Event, KeyCode, KeyEventKind};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
 use ratatui::backend::Backend;
+use ratatui::layout::Rect;
 use ratatui::widgets::{Block, BorderType, Borders, Clear, Paragraph, Wrap};
 use ratatui::Terminal;
 use std::io;
-use ratatui::layout::Rect;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
 pub(crate) struct App {
     pub(crate) items: StatefulList,
     pub(crate) new_item_popup: bool,
     pub(crate) is_edit_mode: bool,
     pub(crate) new_connection: StoredConnection,
+    /// The ssh options and environment of the popup, edited as `NAME=value` text.
+    pub(crate) ssh_options_input: String,
+    pub(crate) env_input: String,
+    /// The tags of the popup, separated by spaces.
+    pub(crate) tags_input: String,
+    /// Why the popup couldn't be saved.
+    pub(crate) popup_error: Option<String>,
     pub(crate) input_mode: InputMode,
+    /// Cursor of the focused popup field.
+    pub(crate) editor: LineEditor,
+    /// The focused popup field is secret and shown in clear text. Moving the focus masks it again.
+    pub(crate) reveal_secret: bool,
+    /// Index of the connection whose password the info pane shows.
+    pub(crate) revealed_password: Option<usize>,
+    /// When to clear a password copied to the clipboard.
+    pub(crate) clipboard_clear_at: Option<Instant>,
+    /// Values connections inherit, from the config.
+    pub(crate) defaults: Defaults,
+    /// The "run command" prompt while it is open.
+    pub(crate) command_prompt: Option<CommandPrompt>,
+    /// Output of the last command run on a connection, shown until dismissed.
+    pub(crate) remote_command: Option<RemoteCommand>,
+    /// Command running on all marked connections, shown as a result table.
+    pub(crate) broadcast: Option<Broadcast>,
+    /// The upload/download dialog while it is open.
+    pub(crate) transfer_form: Option<TransferForm>,
+    /// File transfer started from the dialog, shown until dismissed.
+    pub(crate) transfer: Option<Transfer>,
+    /// Active sshfs mounts, refreshed after every mount or unmount.
+    pub(crate) mounts: Vec<Mount>,
+    /// The mounts panel while it is open.
+    pub(crate) mounts_panel: Option<MountsPanel>,
+    /// The command preview of the selected connection while it is open.
+    pub(crate) preview: Option<Preview>,
+    /// Messages for the status bar and the message log.
+    pub(crate) status: StatusLog,
+    /// Where session recordings are saved, see [`crate::record`].
+    pub(crate) recordings_dir: PathBuf,
+    /// Recordings of the selected connection: its id, when they were listed and the file names.
+    pub(crate) recordings: Option<(String, Instant, Vec<String>)>,
+    /// The template list while it is open.
+    pub(crate) template_picker: Option<TemplatePicker>,
+    /// Connection being woken up to connect to it.
+    pub(crate) waking: Option<Waking>,
+    /// The path prompt of the import while it is open.
+    pub(crate) import_prompt: Option<String>,
+    /// Connections read by an import, to pick which of them to save.
+    pub(crate) import_preview: Option<ImportPreview>,
+    /// The CSV and Markdown export dialog while it is open.
+    pub(crate) export_form: Option<ExportForm>,
+    /// The key setup screen while it is open.
+    pub(crate) key_setup: Option<KeySetup>,
+    /// Latency, server identification and addresses of the hosts, shown in the info pane.
+    pub(crate) probes: Probes,
+    /// ssh sessions running in embedded terminal tabs.
+    pub(crate) sessions: Vec<Session>,
+    /// Tab shown instead of the connection list.
+    pub(crate) active_session: Option<usize>,
+    /// The session prefix key was pressed and the next key is a session command.
+    pub(crate) prefix_pending: bool,
+}
+
+pub(crate) struct CommandPrompt {
+    pub(crate) input: String,
+    /// Run on all marked connections instead of the selected one.
+    pub(crate) broadcast: bool,
 }
 
 impl App {
@@ -24,44 +110,171 @@ impl App {
         cfg: &Config,
     ) -> io::Result<()> {
         loop {
-            if !self.new_item_popup {
+            if let Some(remote_command) = self.remote_command.as_mut() {
+                remote_command.poll();
+            }
+            if let Some(broadcast) = self.broadcast.as_mut() {
+                broadcast.poll();
+            }
+            if let Some(transfer) = self.transfer.as_mut() {
+                transfer.poll();
+            }
+            if self.mounts_panel.as_mut().is_some_and(|panel| panel.poll()) {
+                self.refresh_mounts();
+            }
+            self.poll_waking(cfg);
+            if let Some(setup) = self.key_setup.as_mut() {
+                setup.poll();
+            }
+            self.probe_selected();
+            self.reap_sessions();
+            self.list_recordings();
+            self.expire_secrets();
+
+            if let Some(active) = self.active_session {
+                terminal
+                    .draw(|f| crate::terminal::render_sessions(f, &mut self.sessions, active))?;
+            } else if !self.new_item_popup {
                 self.draw_main_layout(&mut terminal)?;
             } else {
                 self.draw_popup(&mut terminal)?;
             }
 
-            if let Event::Key(key) = event::read()? {
+            // Poll with a timeout so background output keeps the screen up to date. An embedded
+            // session is redrawn more often so typing in it feels immediate.
+            let timeout = if self.active_session.is_some() {
+                15
+            } else {
+                100
+            };
+            if !event::poll(Duration::from_millis(timeout))? {
+                continue;
+            }
+
+            let event = event::read()?;
+            if let Event::Paste(text) = &event {
+                self.handle_paste(text);
+                continue;
+            }
+            if let Event::Key(key) = event {
                 if key.kind == KeyEventKind::Press {
                     use KeyCode::*;
 
+                    if self.handle_session_key(key) {
+                        continue;
+                    }
+
                     if self.new_item_popup {
                         match key.code {
                             Esc => {
                                 self.new_item_popup = false;
+                                self.popup_error = None;
+                                self.reveal_secret = false;
                             }
-                            Enter => {
-                                if self.is_edit_mode {
-                                    self.edit_connection(&cfg.path_to_data_json);
+                            Enter if self.validate_popup() => {
+                                let saved = if self.is_edit_mode {
+                                    self.edit_connection(&cfg.path_to_data_json)
                                 } else {
-                                    self.save_connection(&cfg.path_to_data_json);
+                                    self.save_connection(&cfg.path_to_data_json)
+                                };
+                                if saved {
+                                    self.new_item_popup = false;
+                                    self.reveal_secret = false;
                                 }
-                                self.new_item_popup = false;
                             }
+                            Enter => {}
                             _ => {
-                                self.handle_new_connection_input(key.code);
+                                self.handle_new_connection_input(key);
                             }
                         }
+                    } else if self.command_prompt.is_some() {
+                        self.handle_command_prompt_input(key.code, cfg);
+                    } else if self.transfer_form.is_some() {
+                        self.handle_transfer_form_input(key.code, cfg);
+                    } else if self.transfer.is_some() {
+                        if matches!(key.code, Esc | Char('q')) {
+                            self.transfer = None;
+                        }
+                    } else if self.mounts_panel.is_some() {
+                        self.handle_mounts_panel_input(key.code);
+                    } else if self.preview.is_some() {
+                        self.handle_preview_input(key.code);
+                    } else if self.status.view.is_some() {
+                        self.handle_message_log_input(key.code);
+                    } else if self.template_picker.is_some() {
+                        self.handle_template_picker_input(key.code, cfg);
+                    } else if self.key_setup.is_some() {
+                        self.handle_key_setup_input(key.code, cfg);
+                    } else if self.import_prompt.is_some() {
+                        self.handle_import_prompt_input(key.code);
+                    } else if self.import_preview.is_some() {
+                        self.handle_import_preview_input(key.code, &cfg.path_to_data_json);
+                    } else if self.export_form.is_some() {
+                        self.handle_export_form_input(key.code);
+                    } else if self.broadcast.is_some() {
+                        self.handle_broadcast_input(key.code);
+                    } else if self.remote_command.is_some() {
+                        self.handle_command_output_input(key.code, cfg);
+                    } else if self.waking.is_some() && key.code == Esc {
+                        self.cancel_waking();
                     } else {
                         match key.code {
-                            Char('q') | Esc => return Ok(()),
+                            Char('q') | Esc => {
+                                self.clear_clipboard();
+                                return Ok(());
+                            }
                             Char('h') | Left => self.items.unselect(),
                             Char('j') | Down => self.items.next(),
                             Char('k') | Up => self.items.previous(),
-                            Char('l') | Right => self.connect_ssh(),
+                            Char('l') | Right => self.connect_ssh(cfg),
                             Char('f') => self.connect_sshfs(),
+                            Char('m') => self.open_mounts_panel(),
+                            Char('p') => self.open_preview(cfg),
+                            Char('v') => {
+                                let selected = self.items.state.selected();
+                                self.revealed_password = if self.revealed_password == selected {
+                                    None
+                                } else {
+                                    selected
+                                };
+                            }
+                            Char('Y') => self.copy_password(cfg.clipboard_clear_secs),
+                            Char('w') => self.wake_selected(),
+                            Char('I') => self.import_prompt = Some(String::new()),
+                            Char('X') => self.open_export_form(),
+                            Char('K') => {
+                                if let Some(i) = self.items.state.selected() {
+                                    self.key_setup = Some(KeySetup::new(&self.items.items[i]));
+                                }
+                            }
+                            Char('i') => {
+                                if let Some(i) = self.items.state.selected() {
+                                    if !self.probes.start(&self.items.items[i]) {
+                                        self.status.warning(
+                                            "Too many probes running, try again in a moment",
+                                        );
+                                    }
+                                }
+                            }
+                            Char('r') => self.open_command_prompt(false),
+                            Char('b') => self.open_command_prompt(true),
+                            Char(' ') => {
+                                self.items.toggle_mark();
+                                self.items.next();
+                            }
+                            Char('a') => self.items.toggle_mark_all(),
+                            Char('c') => self.open_cluster(cfg),
+                            Char('t') if self.items.state.selected().is_some() => {
+                                self.transfer_form = Some(TransferForm::new());
+                            }
                             Char('g') => self.go_top(),
                             Char('G') => self.go_bottom(),
-                            Char('n') => self.new_item_popup = !self.new_item_popup,
+                            Char('n') => self.start_new_connection(),
+                            Char('C') => self.clone_selected(),
+                            Char('L') => self.status.open_view(),
+                            Char('T') => {
+                                self.template_picker = Some(TemplatePicker::new(&cfg.templates));
+                            }
                             Char('e') => {
                                 self.start_editing_connection();
                                 self.new_item_popup = true;
@@ -69,7 +282,7 @@ impl App {
                             Char('d') => {
                                 self.confirm_deletion(&mut terminal, &cfg.path_to_data_json);
                             }
-                            Enter => self.connect_ssh(),
+                            Enter => self.connect_ssh(cfg),
                             _ => {}
                         }
                     }
@@ -78,117 +291,808 @@ impl App {
         }
     }
 
-    pub(crate) fn start_editing_connection(&mut self) {
-        if let Some(selected) = self.items.state.selected() {
-            let current_connection = self.items.items[selected].clone();
-            self.new_connection = StoredConnection::from(current_connection);
-            self.is_edit_mode = true;
+    /// Pasted text goes to the active session or the focused text input.
+    fn handle_paste(&mut self, text: &str) {
+        if let Some(active) = self.active_session {
+            self.sessions[active].paste(text);
+        } else if self.new_item_popup {
+            self.paste_into_popup(text);
+        } else if let Some(prompt) = self.command_prompt.as_mut() {
+            prompt.input.push_str(&text.replace(['\r', '\n'], " "));
+        } else if let Some(prompt) = self.import_prompt.as_mut() {
+            prompt.push_str(text.trim());
+        } else if let Some(path) = self.export_form.as_mut().and_then(ExportForm::input_mut) {
+            path.push_str(text.trim());
+        }
+    }
+
+    /// Handles the session prefix key (Ctrl-a) and forwards everything else to the active
+    /// session. Returns false when the key is meant for the rest of the UI. Outside a tab the
+    /// prefix only applies on the bare list while sessions are open, so popups and text inputs
+    /// keep Ctrl-a.
+    fn handle_session_key(&mut self, key: KeyEvent) -> bool {
+        let ctrl_a = key.code == KeyCode::Char('a')
+            && key.modifiers.contains(KeyModifiers::CONTROL)
+            && (self.active_session.is_some() || (!self.sessions.is_empty() && !self.has_dialog()));
+        if self.prefix_pending {
+            self.prefix_pending = false;
+            let count = self.sessions.len();
+            match key.code {
+                _ if ctrl_a => {
+                    if let Some(active) = self.active_session {
+                        self.sessions[active].write(&[0x01]);
+                    }
+                }
+                KeyCode::Char('l') | KeyCode::Char('0') | KeyCode::Esc => {
+                    self.active_session = None
+                }
+                KeyCode::Char('n') if count > 0 => {
+                    self.active_session = Some(self.active_session.map_or(0, |i| (i + 1) % count))
+                }
+                KeyCode::Char('p') if count > 0 => {
+                    self.active_session = Some(
+                        self.active_session
+                            .map_or(count - 1, |i| (i + count - 1) % count),
+                    )
+                }
+                KeyCode::Char(c @ '1'..='9') => {
+                    let i = c as usize - '1' as usize;
+                    if i < count {
+                        self.active_session = Some(i);
+                    }
+                }
+                KeyCode::Char('x') => {
+                    if let Some(active) = self.active_session {
+                        self.close_session(active);
+                    }
+                }
+                _ => {}
+            }
+            return true;
+        }
+        if ctrl_a {
+            self.prefix_pending = true;
+            return true;
+        }
+        if let Some(active) = self.active_session {
+            self.sessions[active].send_key(key);
+            return true;
         }
+        false
     }
 
-    fn edit_connection(&mut self, path: &String) {
-        let host = self.new_connection.host.clone();
-        if let Err(e) = edit_connection_and_save(&self.new_connection, path, &host) {
-            eprintln!("Failed to replace data in file: {}", e);
+    /// Whether a popup, prompt or panel is open over the connection list.
+    fn has_dialog(&self) -> bool {
+        self.new_item_popup
+            || self.command_prompt.is_some()
+            || self.transfer_form.is_some()
+            || self.transfer.is_some()
+            || self.mounts_panel.is_some()
+            || self.preview.is_some()
+            || self.status.view.is_some()
+            || self.template_picker.is_some()
+            || self.key_setup.is_some()
+            || self.import_prompt.is_some()
+            || self.import_preview.is_some()
+            || self.export_form.is_some()
+            || self.broadcast.is_some()
+            || self.remote_command.is_some()
+    }
+
+    fn open_command_prompt(&mut self, broadcast: bool) {
+        let has_target = if broadcast {
+            !self.items.marked.is_empty()
+        } else {
+            self.items.state.selected().is_some()
+        };
+        if has_target {
+            self.command_prompt = Some(CommandPrompt {
+                input: String::new(),
+                broadcast,
+            });
         }
-        self.new_connection = StoredConnection::new();
-        self.reload_connections_from_file(path);
-        self.is_edit_mode = false;
     }
 
-    fn handle_new_connection_input(&mut self, code: KeyCode) {
+    fn handle_command_prompt_input(&mut self, code: KeyCode, cfg: &Config) {
+        let Some(prompt) = self.command_prompt.as_mut() else {
+            return;
+        };
         match code {
-            KeyCode::Char(c) => match self.input_mode {
-                InputMode::Label => self.new_connection.label.push(c),
-                InputMode::Host => self.new_connection.host.push(c),
-                InputMode::Port => {
-                    if self.new_connection.port.is_none() {
-                        self.new_connection.port = Some(String::new());
+            KeyCode::Char(c) => prompt.input.push(c),
+            KeyCode::Backspace => {
+                prompt.input.pop();
+            }
+            KeyCode::Esc => self.command_prompt = None,
+            KeyCode::Enter => {
+                if let Some(prompt) = self.command_prompt.take() {
+                    if prompt.input.trim().is_empty() {
+                        return;
                     }
-                    if let Some(port) = self.new_connection.port.as_mut() {
-                        port.push(c);
+                    if prompt.broadcast {
+                        self.start_broadcast(&prompt.input, cfg);
+                    } else {
+                        self.run_remote_command(&prompt.input, cfg);
                     }
                 }
-                InputMode::User => {
-                    if self.new_connection.user.is_none() {
-                        self.new_connection.user = Some(String::new());
-                    }
-                    if let Some(user) = self.new_connection.user.as_mut() {
-                        user.push(c);
-                    }
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_transfer_form_input(&mut self, code: KeyCode, cfg: &Config) {
+        let Some(form) = self.transfer_form.as_mut() else {
+            return;
+        };
+        match code {
+            KeyCode::Esc => self.transfer_form = None,
+            KeyCode::Tab | KeyCode::Down => form.field = form.field.next(),
+            KeyCode::BackTab | KeyCode::Up => form.field = form.field.previous(),
+            KeyCode::Enter if form.is_complete() => {
+                if let Some(form) = self.transfer_form.take() {
+                    self.start_transfer(&form, cfg);
                 }
-                InputMode::Password => {
-                    if self.new_connection.password.is_none() {
-                        self.new_connection.password = Some(String::new());
-                    }
-                    if let Some(password) = self.new_connection.password.as_mut() {
-                        password.push(c);
-                    }
+            }
+            KeyCode::Backspace => {
+                if let Some(input) = form.input_mut() {
+                    input.pop();
                 }
+            }
+            KeyCode::Char(c) => match form.input_mut() {
+                Some(input) => input.push(c),
+                None if c == ' ' => form.toggle(),
+                None => {}
             },
-            KeyCode::Backspace => match self.input_mode {
-                InputMode::Label => {
-                    self.new_connection.label.pop();
-                }
-                InputMode::Host => {
-                    self.new_connection.host.pop();
+            KeyCode::Left | KeyCode::Right => form.toggle(),
+            _ => {}
+        }
+    }
+
+    fn handle_mounts_panel_input(&mut self, code: KeyCode) {
+        let Some(panel) = self.mounts_panel.as_mut() else {
+            return;
+        };
+        let count = self.mounts.len();
+        match code {
+            KeyCode::Esc | KeyCode::Char('q') => self.mounts_panel = None,
+            KeyCode::Char('j') | KeyCode::Down if count > 0 => {
+                let i = panel.state.selected().map_or(0, |i| (i + 1) % count);
+                panel.state.select(Some(i));
+            }
+            KeyCode::Char('k') | KeyCode::Up if count > 0 => {
+                let i = panel
+                    .state
+                    .selected()
+                    .map_or(0, |i| (i + count - 1) % count);
+                panel.state.select(Some(i));
+            }
+            KeyCode::Char('u') => self.unmount_selected(false),
+            KeyCode::Char('r') => self.unmount_selected(true),
+            _ => {}
+        }
+    }
+
+    fn handle_preview_input(&mut self, code: KeyCode) {
+        let Some(preview) = self.preview.as_mut() else {
+            return;
+        };
+        match code {
+            KeyCode::Esc | KeyCode::Char('q') => self.preview = None,
+            KeyCode::Char('j') | KeyCode::Down => preview.next(),
+            KeyCode::Char('k') | KeyCode::Up => preview.previous(),
+            KeyCode::Char('y') => {
+                if let Some(line) = preview.selected_line() {
+                    preview.message = Some(match copy_to_clipboard(line) {
+                        Ok(()) => "Copied to the clipboard".to_string(),
+                        Err(e) => format!("Copy failed: {}", e),
+                    });
                 }
-                InputMode::Port => {
-                    if let Some(port) = self.new_connection.port.as_mut() {
-                        port.pop();
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_broadcast_input(&mut self, code: KeyCode) {
+        let Some(broadcast) = self.broadcast.as_mut() else {
+            return;
+        };
+        if let Some(run) = broadcast.open_run() {
+            match code {
+                KeyCode::Esc | KeyCode::Char('q') => broadcast.open = None,
+                KeyCode::Char('j') | KeyCode::Down => run.scroll_down(1),
+                KeyCode::Char('k') | KeyCode::Up => run.scroll_up(1),
+                KeyCode::PageDown => run.scroll_down(10),
+                KeyCode::PageUp => run.scroll_up(10),
+                _ => {}
+            }
+            return;
+        }
+        match code {
+            KeyCode::Esc | KeyCode::Char('q') => self.broadcast = None,
+            KeyCode::Char('j') | KeyCode::Down => broadcast.next(),
+            KeyCode::Char('k') | KeyCode::Up => broadcast.previous(),
+            KeyCode::Enter | KeyCode::Char('l') => broadcast.open_selected(),
+            _ => {}
+        }
+    }
+
+    fn handle_command_output_input(&mut self, code: KeyCode, cfg: &Config) {
+        let Some(output) = self.remote_command.as_mut() else {
+            return;
+        };
+        match code {
+            KeyCode::Esc | KeyCode::Char('q') => self.remote_command = None,
+            KeyCode::Char('j') | KeyCode::Down => output.scroll_down(1),
+            KeyCode::Char('k') | KeyCode::Up => output.scroll_up(1),
+            KeyCode::PageDown => output.scroll_down(10),
+            KeyCode::PageUp => output.scroll_up(10),
+            KeyCode::Char('g') => output.scroll_up(output.scroll),
+            KeyCode::Char('G') => output.follow = true,
+            KeyCode::Char('r') if !output.is_running() => {
+                let command = output.command.clone();
+                self.run_remote_command(&command, cfg);
+            }
+            _ => {}
+        }
+    }
+
+    /// Puts the password of the selected connection on the clipboard, to be cleared again after
+    /// `clear_secs`.
+    fn copy_password(&mut self, clear_secs: u64) {
+        let Some(item) = self.items.state.selected().map(|i| &self.items.items[i]) else {
+            return;
+        };
+        if item.password.is_empty() {
+            self.status
+                .warning(format!("{} has no stored password", item.label));
+            return;
+        }
+        if let Err(e) = copy_to_clipboard(&item.password) {
+            self.status.error(format!("Copy failed: {}", e));
+            return;
+        }
+        self.status
+            .info(format!("Copied the password of {}", item.label));
+        self.clipboard_clear_at =
+            (clear_secs > 0).then(|| Instant::now() + Duration::from_secs(clear_secs));
+    }
+
+    /// Clears a copied password from the clipboard once its time is up, and masks a revealed
+    /// password again once another connection is selected.
+    fn expire_secrets(&mut self) {
+        if self
+            .clipboard_clear_at
+            .is_some_and(|at| at <= Instant::now())
+        {
+            self.clear_clipboard();
+        }
+        if self.revealed_password != self.items.state.selected() {
+            self.revealed_password = None;
+        }
+    }
+
+    fn clear_clipboard(&mut self) {
+        if self.clipboard_clear_at.take().is_some() {
+            if let Err(e) = copy_to_clipboard("") {
+                self.status
+                    .error(format!("Failed to clear the clipboard: {}", e));
+            }
+        }
+    }
+
+    fn start_new_connection(&mut self) {
+        // Don't carry over a cancelled edit.
+        if self.is_edit_mode {
+            self.new_connection = StoredConnection::new();
+            self.ssh_options_input.clear();
+            self.env_input.clear();
+            self.tags_input.clear();
+            self.is_edit_mode = false;
+        }
+        self.popup_error = None;
+        self.focus(InputMode::Label);
+        self.new_item_popup = true;
+    }
+
+    pub(crate) fn start_editing_connection(&mut self) {
+        if let Some(selected) = self.items.state.selected() {
+            let current_connection = self.items.items[selected].clone();
+            self.fill_popup(StoredConnection::from(current_connection), true);
+        }
+    }
+
+    /// Opens the add popup filled in from the selected connection, as a new entry with its own
+    /// id and an unused label.
+    fn clone_selected(&mut self) {
+        let Some(selected) = self.items.state.selected() else {
+            return;
+        };
+        let mut connection = StoredConnection::from(self.items.items[selected].clone());
+        connection.id = new_id();
+        connection.label = self.unused_label(&connection.label);
+        self.fill_popup(connection, false);
+        self.new_item_popup = true;
+    }
+
+    /// `label` with the first of the suffixes `-copy`, `-copy2`, ... no connection has.
+    fn unused_label(&self, label: &str) -> String {
+        (1..)
+            .map(|n| match n {
+                1 => format!("{}-copy", label),
+                n => format!("{}-copy{}", label, n),
+            })
+            .find(|candidate| !self.items.items.iter().any(|item| &item.label == candidate))
+            .unwrap_or_default()
+    }
+
+    fn handle_message_log_input(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Esc | KeyCode::Char('q') => self.status.view = None,
+            KeyCode::Char('j') | KeyCode::Down => self.status.scroll_down(1),
+            KeyCode::Char('k') | KeyCode::Up => self.status.scroll_up(1),
+            KeyCode::PageDown => self.status.scroll_down(10),
+            KeyCode::PageUp => self.status.scroll_up(10),
+            KeyCode::Char('g') => self.status.scroll_up(usize::MAX),
+            KeyCode::Char('G') => self.status.scroll_down(usize::MAX),
+            _ => {}
+        }
+    }
+
+    fn handle_import_prompt_input(&mut self, code: KeyCode) {
+        let Some(prompt) = self.import_prompt.as_mut() else {
+            return;
+        };
+        match code {
+            KeyCode::Char(c) => prompt.push(c),
+            KeyCode::Backspace => {
+                prompt.pop();
+            }
+            KeyCode::Esc => self.import_prompt = None,
+            KeyCode::Enter => {
+                let source = self.import_prompt.take().unwrap_or_default();
+                let source = source.trim();
+                match import::load(&expand_home(source)) {
+                    Ok(imported) if imported.connections.is_empty() => {
+                        self.status.warning(format!(
+                            "Nothing to import from {} ({} entries skipped)",
+                            source,
+                            imported.skipped.len()
+                        ))
                     }
-                }
-                InputMode::User => {
-                    if let Some(user) = self.new_connection.user.as_mut() {
-                        user.pop();
+                    Ok(imported) => {
+                        self.import_preview = Some(ImportPreview::new(
+                            source,
+                            imported,
+                            &self.items.items,
+                            &self.defaults,
+                        ));
                     }
+                    Err(e) => self.status.error(format!("Can't import: {}", e)),
                 }
-                InputMode::Password => {
-                    if let Some(password) = self.new_connection.password.as_mut() {
-                        password.pop();
-                    }
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_import_preview_input(&mut self, code: KeyCode, path: &str) {
+        let Some(preview) = self.import_preview.as_mut() else {
+            return;
+        };
+        match code {
+            KeyCode::Esc | KeyCode::Char('q') => self.import_preview = None,
+            KeyCode::Char('j') | KeyCode::Down => preview.next(),
+            KeyCode::Char('k') | KeyCode::Up => preview.previous(),
+            KeyCode::Char(' ') => preview.toggle(),
+            KeyCode::Char('a') => preview.toggle_all(),
+            KeyCode::Enter => self.save_import(path),
+            _ => {}
+        }
+    }
+
+    /// Adds the connections selected in the import preview to the file.
+    fn save_import(&mut self, path: &str) {
+        let Some(preview) = self.import_preview.as_ref() else {
+            return;
+        };
+        let connections = preview.selected_connections();
+        if connections.is_empty() {
+            self.status
+                .warning("Select connections to import with Space");
+            return;
+        }
+        if let Err(e) = append_connections_and_save(&connections, path) {
+            for connection in &connections {
+                logging::audit(
+                    Action::Add,
+                    &connection.into(),
+                    "",
+                    &format!("failed: {}", e),
+                );
+            }
+            self.status
+                .error(format!("Failed to write {}: {}", path, e));
+            return;
+        }
+        for connection in &connections {
+            logging::audit(Action::Add, &connection.into(), "", "ok");
+        }
+        self.status.info(format!(
+            "Imported {} connections from {}",
+            connections.len(),
+            preview.source
+        ));
+        self.import_preview = None;
+        self.reload_connections_from_file(path);
+    }
+
+    /// Exports the marked connections, or all of them when none are marked.
+    fn open_export_form(&mut self) {
+        let items = if self.items.marked.is_empty() {
+            self.items.items.clone()
+        } else {
+            self.items.marked_items()
+        };
+        let connections = items.into_iter().map(StoredConnection::from).collect();
+        match ExportForm::new(connections) {
+            Ok(form) => self.export_form = Some(form),
+            Err(e) => self.status.error(format!("Can't export: {}", e)),
+        }
+    }
+
+    fn handle_export_form_input(&mut self, code: KeyCode) {
+        let Some(form) = self.export_form.as_mut() else {
+            return;
+        };
+        match code {
+            KeyCode::Esc => self.export_form = None,
+            KeyCode::Tab | KeyCode::Down => form.next(),
+            KeyCode::BackTab | KeyCode::Up => form.previous(),
+            KeyCode::Enter => match form.write() {
+                Ok(()) => {
+                    self.status.info(format!(
+                        "Exported {} connections to {}",
+                        form.connections.len(),
+                        form.path.trim()
+                    ));
+                    self.export_form = None;
+                }
+                Err(e) => self.status.error(format!("Can't export: {}", e)),
+            },
+            KeyCode::Backspace => {
+                if let Some(path) = form.input_mut() {
+                    path.pop();
                 }
+            }
+            KeyCode::Char(c) => match form.input_mut() {
+                Some(path) => path.push(c),
+                None if c == ' ' => form.toggle(),
+                None => {}
             },
-            KeyCode::Tab => {
-                // Switch between input fields
-                self.input_mode = match self.input_mode {
-                    InputMode::Label => InputMode::Host,
-                    InputMode::Host => InputMode::Port,
-                    InputMode::Port => InputMode::User,
-                    InputMode::User => InputMode::Password,
-                    InputMode::Password => InputMode::Label,
+            KeyCode::Left | KeyCode::Right => form.toggle(),
+            _ => {}
+        }
+    }
+
+    fn handle_key_setup_input(&mut self, code: KeyCode, cfg: &Config) {
+        let Some(setup) = self.key_setup.as_mut() else {
+            return;
+        };
+        let editing = setup.step == Step::Generate && !setup.is_running();
+        match code {
+            KeyCode::Esc => self.key_setup = None,
+            KeyCode::Enter | KeyCode::Char('y') if setup.step == Step::Switch => {
+                self.use_key(&cfg.path_to_data_json)
+            }
+            KeyCode::Char('n') if setup.step == Step::Switch => {
+                setup.log.push("Kept the stored password".to_string());
+                setup.step = Step::Done;
+            }
+            KeyCode::Enter if setup.step == Step::Done => self.key_setup = None,
+            KeyCode::Enter => {
+                let backend = setup.item.backend(cfg);
+                setup.run_step(backend);
+            }
+            KeyCode::Char(c) if editing => setup.key_path.push(c),
+            KeyCode::Backspace if editing => {
+                setup.key_path.pop();
+            }
+            _ => {}
+        }
+    }
+
+    /// Stores the key of the key setup screen as the identity file of its connection and clears
+    /// the password.
+    fn use_key(&mut self, path: &str) {
+        let Some(setup) = self.key_setup.as_mut() else {
+            return;
+        };
+        // Saved from the current list, the connection may have been reloaded meanwhile.
+        let item = self
+            .items
+            .items
+            .iter()
+            .find(|item| item.id == setup.item.id)
+            .unwrap_or(&setup.item);
+        let mut connection = StoredConnection::from(item.clone());
+        connection.password = None;
+        connection.identity_file = Some(setup.key_path.clone());
+        let target = Target::from(&connection);
+        if let Err(e) = edit_connection_and_save(&connection, path) {
+            logging::audit(Action::Edit, &target, "", &format!("failed: {}", e));
+            let message = format!("Failed to write {}: {}", path, e);
+            setup.log.push(message.clone());
+            self.status.error(message);
+            return;
+        }
+        logging::audit(Action::Edit, &target, "", "ok");
+        setup.log.push(format!(
+            "{} now logs in with {}",
+            connection.label, setup.key_path
+        ));
+        setup.step = Step::Done;
+        self.status.info(format!(
+            "Cleared the password of {}, it uses {} now",
+            connection.label, setup.key_path
+        ));
+        self.reload_connections_from_file(path);
+    }
+
+    fn handle_template_picker_input(&mut self, code: KeyCode, cfg: &Config) {
+        let Some(picker) = self.template_picker.as_mut() else {
+            return;
+        };
+        match code {
+            KeyCode::Esc | KeyCode::Char('q') => self.template_picker = None,
+            KeyCode::Char('j') | KeyCode::Down => picker.next(),
+            KeyCode::Char('k') | KeyCode::Up => picker.previous(),
+            KeyCode::Enter | KeyCode::Char('l') => {
+                let Some(template) = picker.selected().and_then(|name| cfg.templates.get(name))
+                else {
+                    return;
                 };
+                self.fill_popup(templates::seed(template), false);
+                self.template_picker = None;
+                self.new_item_popup = true;
             }
             _ => {}
         }
     }
 
-    fn save_connection(&mut self, path: &String) {
+    /// Loads `connection` into the popup fields, to edit it in place or to add it as new.
+    fn fill_popup(&mut self, connection: StoredConnection, edit: bool) {
+        self.new_connection = connection;
+        self.ssh_options_input = format_assignments(&self.new_connection.ssh_options);
+        self.env_input = format_assignments(&self.new_connection.env);
+        self.tags_input = self.new_connection.tags.join(" ");
+        self.popup_error = None;
+        self.is_edit_mode = edit;
+        self.focus(InputMode::Label);
+    }
+
+    /// Moves the popup focus to `mode` with the cursor at the end of its text.
+    pub(crate) fn focus(&mut self, mode: InputMode) {
+        self.input_mode = mode;
+        self.reveal_secret = false;
+        let value = self.popup_field(mode).to_string();
+        self.editor.reset(&value);
+    }
+
+    /// The text of a popup field.
+    pub(crate) fn popup_field(&self, mode: InputMode) -> &str {
+        match mode {
+            InputMode::SshOptions => &self.ssh_options_input,
+            InputMode::Env => &self.env_input,
+            InputMode::Tags => &self.tags_input,
+            _ => self.new_connection.field(mode),
+        }
+    }
+
+    /// What an empty popup field falls back to, from the defaults in the config.
+    pub(crate) fn popup_default(&self, mode: InputMode) -> Option<String> {
+        match mode {
+            InputMode::User => self.defaults.user.clone(),
+            InputMode::Port => Some(
+                self.defaults
+                    .port
+                    .clone()
+                    .unwrap_or_else(|| "22".to_string()),
+            ),
+            InputMode::IdentityFile => self.defaults.identity_file.clone(),
+            InputMode::LaunchMode => self
+                .defaults
+                .launch_mode
+                .map(|mode| mode.name().to_string()),
+            InputMode::Record | InputMode::WakeOnConnect => Some("off".to_string()),
+            InputMode::WakeBroadcast => Some(wake::DEFAULT_BROADCAST.to_string()),
+            _ => None,
+        }
+    }
+
+    fn popup_field_mut(&mut self, mode: InputMode) -> Option<&mut String> {
+        match mode {
+            InputMode::SshOptions => Some(&mut self.ssh_options_input),
+            InputMode::Env => Some(&mut self.env_input),
+            InputMode::Tags => Some(&mut self.tags_input),
+            _ => self.new_connection.field_mut(mode),
+        }
+    }
+
+    /// Why the text of a popup field is invalid, if it is.
+    pub(crate) fn field_error(&self, mode: InputMode) -> Option<String> {
+        let value = self.popup_field(mode).trim();
+        match mode {
+            InputMode::Label if value.is_empty() => Some("must not be empty".to_string()),
+            InputMode::Label => {
+                let taken = self
+                    .items
+                    .items
+                    .iter()
+                    .any(|item| item.id != self.new_connection.id && item.label == value);
+                taken.then(|| "another connection has this label".to_string())
+            }
+            InputMode::Host if value.is_empty() => Some("must not be empty".to_string()),
+            InputMode::Host if !is_valid_host(value) => {
+                Some("not a host name or IP address".to_string())
+            }
+            InputMode::Port if !value.is_empty() => match value.parse::<u16>() {
+                Ok(port) if port > 0 => None,
+                _ => Some("must be a number from 1 to 65535".to_string()),
+            },
+            InputMode::User if value.contains(|c: char| c.is_whitespace() || c == '@') => {
+                Some("must not contain spaces or @".to_string())
+            }
+            InputMode::CommandTemplate => shell_words::split(value).err().map(|e| e.to_string()),
+            InputMode::SshOptions | InputMode::Env => parse_assignments(value).err(),
+            InputMode::WakeMac if !value.is_empty() => wake::parse_mac(value).err(),
+            InputMode::WakeBroadcast if !value.is_empty() => wake::check_broadcast(value).err(),
+            InputMode::WakeOnConnect if self.new_connection.wake_on_connect == Some(true) => self
+                .new_connection
+                .wake_mac
+                .as_deref()
+                .is_none_or(|mac| mac.trim().is_empty())
+                .then(|| "needs a MAC address".to_string()),
+            _ => None,
+        }
+    }
+
+    /// Checks every popup field and stores the option and environment fields in the connection.
+    /// On errors the popup stays open on the first invalid field.
+    fn validate_popup(&mut self) -> bool {
+        if let Some((mode, error)) = InputMode::ALL
+            .iter()
+            .find_map(|&mode| self.field_error(mode).map(|error| (mode, error)))
+        {
+            self.focus(mode);
+            self.popup_error = Some(format!("{}: {}", mode.title(), error));
+            return false;
+        }
+        self.new_connection.ssh_options =
+            parse_assignments(&self.ssh_options_input).unwrap_or_default();
+        self.new_connection.env = parse_assignments(&self.env_input).unwrap_or_default();
+        self.new_connection.tags = self
+            .tags_input
+            .split_whitespace()
+            .map(str::to_string)
+            .collect();
+        self.popup_error = None;
+        true
+    }
+
+    /// Writes the edited connection to the file. On failure the popup stays open with the error.
+    fn edit_connection(&mut self, path: &str) -> bool {
+        let target = Target::from(&self.new_connection);
+        if let Err(e) = edit_connection_and_save(&self.new_connection, path) {
+            logging::audit(Action::Edit, &target, "", &format!("failed: {}", e));
+            self.save_failed(path, e);
+            return false;
+        }
+        logging::audit(Action::Edit, &target, "", "ok");
+        self.status
+            .info(format!("Saved {}", self.new_connection.label));
+        self.new_connection = StoredConnection::new();
+        self.reload_connections_from_file(path);
+        self.is_edit_mode = false;
+        true
+    }
+
+    fn save_failed(&mut self, path: &str, e: io::Error) {
+        let message = format!("Failed to write {}: {}", path, e);
+        self.popup_error = Some(message.clone());
+        self.status.error(message);
+    }
+
+    fn handle_new_connection_input(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Tab | KeyCode::Down => self.focus(self.input_mode.next()),
+            KeyCode::BackTab | KeyCode::Up => self.focus(self.input_mode.previous()),
+            KeyCode::Char('r')
+                if key.modifiers.contains(KeyModifiers::CONTROL) && self.input_mode.is_secret() =>
+            {
+                self.reveal_secret = !self.reveal_secret
+            }
+            _ if self.input_mode.is_choice() => match key.code {
+                KeyCode::Char(' ') | KeyCode::Left | KeyCode::Right => {
+                    self.new_connection.cycle_choice(self.input_mode)
+                }
+                KeyCode::Backspace | KeyCode::Delete => {
+                    self.new_connection.clear_choice(self.input_mode)
+                }
+                _ => {}
+            },
+            _ => {
+                let mode = self.input_mode;
+                let mut editor = std::mem::take(&mut self.editor);
+                if let Some(field) = self.popup_field_mut(mode) {
+                    editor.handle_key(field, key);
+                }
+                self.editor = editor;
+            }
+        }
+    }
+
+    /// Inserts pasted text into the focused popup field.
+    fn paste_into_popup(&mut self, text: &str) {
+        let mode = self.input_mode;
+        let mut editor = std::mem::take(&mut self.editor);
+        if let Some(field) = self.popup_field_mut(mode) {
+            editor.paste(field, text);
+        }
+        self.editor = editor;
+    }
+
+    /// Adds the new connection to the file. On failure the popup stays open with the error.
+    fn save_connection(&mut self, path: &str) -> bool {
+        let target = Target::from(&self.new_connection);
         if let Err(e) = append_json_to_file(&self.new_connection, path) {
-            eprintln!("Failed to write to file: {}", e);
-            return;
+            logging::audit(Action::Add, &target, "", &format!("failed: {}", e));
+            self.save_failed(path, e);
+            return false;
         }
-        self.reload_connections_from_file(path)
+        logging::audit(Action::Add, &target, "", "ok");
+        self.status
+            .info(format!("Added {}", self.new_connection.label));
+        self.reload_connections_from_file(path);
+        true
     }
 
-    fn reload_connections_from_file(&mut self, path: &String) {
+    fn reload_connections_from_file(&mut self, path: &str) {
         if let Ok(content) = std::fs::read_to_string(path) {
-            match serde_json::from_str(&content) {
-                Ok(items) => {
-                    self.items = StatefulList::with_items(items);
-                    self.new_connection = StoredConnection::new()
+            match serde_json::from_str::<Vec<StoredConnection>>(&content) {
+                Ok(mut items) => {
+                    // Entries added to the file by hand get an id the next edit can find.
+                    if assign_missing_ids(&mut items) {
+                        if let Err(e) = write_json_to_file(&items, path) {
+                            self.status
+                                .error(format!("Failed to store ids in {}: {}", path, e));
+                        }
+                    }
+                    self.items = StatefulList::with_items(items, &self.defaults);
+                    self.new_connection = StoredConnection::new();
+                    self.ssh_options_input.clear();
+                    self.env_input.clear();
+                    self.tags_input.clear();
                 }
-                Err(e) => eprintln!("Failed to parse JSON: {}", e),
+                Err(e) => self
+                    .status
+                    .error(format!("Failed to parse {}: {}", path, e)),
             }
         } else {
-            eprintln!("Failed to read file: {}", path);
+            self.status.error(format!("Failed to read {}", path));
         }
     }
 
-    fn confirm_deletion<B: Backend>(&mut self, terminal: &mut Terminal<B>, path: &String) {
+    fn confirm_deletion<B: Backend>(&mut self, terminal: &mut Terminal<B>, path: &str) {
         if let Some(selected) = self.items.state.selected() {
             let connection = &self.items.items[selected];
-            let confirm_area = centered_rect(50, 20, Rect::new(0,0, terminal.size().unwrap().width, terminal.size().unwrap().height));
+            let confirm_area = centered_rect(
+                50,
+                20,
+                Rect::new(
+                    0,
+                    0,
+                    terminal.size().unwrap().width,
+                    terminal.size().unwrap().height,
+                ),
+            );
 
             let block = Block::default()
                 .title("Confirm Deletion")
@@ -225,27 +1129,81 @@ impl App {
         }
     }
 
-    fn delete_connection(&mut self, path: &String, index: usize) {
-        self.items.items.remove(index);
-        if let Err(e) = write_json_to_file(&self.items.items, path) {
-            eprintln!("Failed to write to file: {}", e);
+    fn delete_connection(&mut self, path: &str, index: usize) {
+        let label = self.items.items[index].label.clone();
+        let target = Target::from(&self.items.items[index]);
+        match remove_connection_and_save(&self.items.items[index].id, path) {
+            Ok(()) => {
+                logging::audit(Action::Delete, &target, "", "ok");
+                self.status.info(format!("Deleted {}", label));
+            }
+            Err(e) => {
+                logging::audit(Action::Delete, &target, "", &format!("failed: {}", e));
+                self.status
+                    .error(format!("Failed to delete {} from {}: {}", label, path, e));
+            }
         }
         self.reload_connections_from_file(path);
     }
 
-    fn save_all_connections(&self, path: &String) {
-        if let Err(e) = write_json_to_file(&self.items.items, path) {
-            eprintln!("Failed to write to file: {}", e);
-        }
-    }
-
     fn draw_main_layout(&mut self, terminal: &mut Terminal<impl Backend>) -> io::Result<()> {
-        terminal.draw(|f| f.render_widget(self, f.size()))?;
+        terminal.draw(|f| {
+            let area = f.area();
+            f.render_widget(&mut *self, area);
+            if let Some(prompt) = &self.command_prompt {
+                let target = if prompt.broadcast {
+                    format!("{} marked hosts", self.items.marked.len())
+                } else {
+                    self.selected_label().to_string()
+                };
+                crate::terminal::render_command_prompt(f, &target, &prompt.input);
+            }
+            if let Some(remote_command) = self.remote_command.as_mut() {
+                crate::terminal::render_command_output(
+                    f,
+                    remote_command,
+                    "j/k/PgUp/PgDn to scroll, r to rerun, Esc to close",
+                );
+            }
+            if let Some(broadcast) = self.broadcast.as_mut() {
+                crate::terminal::render_broadcast(f, broadcast);
+            }
+            if let Some(form) = &self.transfer_form {
+                crate::terminal::render_transfer_form(f, self.selected_label(), form);
+            }
+            if let Some(transfer) = &self.transfer {
+                crate::terminal::render_transfer(f, transfer);
+            }
+            if let Some(panel) = self.mounts_panel.as_mut() {
+                crate::terminal::render_mounts_panel(f, panel, &self.mounts, &self.items.items);
+            }
+            if let Some(preview) = self.preview.as_mut() {
+                crate::terminal::render_preview(f, preview);
+            }
+            if let Some(picker) = self.template_picker.as_mut() {
+                crate::terminal::render_template_picker(f, picker);
+            }
+            if let Some(setup) = &self.key_setup {
+                crate::terminal::render_key_setup(f, setup);
+            }
+            if let Some(prompt) = &self.import_prompt {
+                crate::terminal::render_import_prompt(f, prompt);
+            }
+            if let Some(preview) = self.import_preview.as_mut() {
+                crate::terminal::render_import_preview(f, preview);
+            }
+            if let Some(form) = self.export_form.as_mut() {
+                crate::terminal::render_export_form(f, form);
+            }
+            if self.status.view.is_some() {
+                crate::terminal::render_message_log(f, &mut self.status);
+            }
+        })?;
         Ok(())
     }
 
     fn draw_popup(&mut self, terminal: &mut Terminal<impl Backend>) -> io::Result<()> {
-        terminal.draw(|f| crate::terminal::add_new_connection_ui(f, &self))?;
+        terminal.draw(|f| crate::terminal::add_new_connection_ui(f, self))?;
         Ok(())
     }
 }
@@ -0,0 +1,52 @@
+//! `SSH_ASKPASS` helper mode of this binary. Sessions handed off to tmux or a terminal emulator
+//! don't inherit our environment, so instead of `SSHPASS` they get the location of the stored
+//! connection and ssh runs this binary again to ask for the password.
+
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io::{self, Write};
+
+use crate::model::model::{ConnectionItem, StoredConnection};
+
+const STORE_VAR: &str = "SSH_MANAGER_ASKPASS_STORE";
+/// The id of the connection, which unlike its label and host stays the same when it is edited
+/// and tells connections with the same label apart.
+const ID_VAR: &str = "SSH_MANAGER_ASKPASS_ID";
+
+/// Environment that makes ssh ask this binary for the password of `item` from the connections
+/// stored in `store`. None of the values are secret, so they may appear on a command line.
+pub(crate) fn env(item: &ConnectionItem, store: &str) -> io::Result<Vec<(String, String)>> {
+    let exe = env::current_exe()?;
+    Ok(vec![
+        ("SSH_ASKPASS".to_string(), exe.to_string_lossy().to_string()),
+        // Use the helper even though the session has a terminal.
+        ("SSH_ASKPASS_REQUIRE".to_string(), "force".to_string()),
+        (STORE_VAR.to_string(), store.to_string()),
+        (ID_VAR.to_string(), item.id.clone()),
+    ])
+}
+
+/// Whether ssh started this process as its askpass helper.
+pub(crate) fn is_requested() -> bool {
+    env::var_os(STORE_VAR).is_some()
+}
+
+/// Prints the stored password for ssh. Other prompts, e.g. for key passphrases, are refused.
+pub(crate) fn run() -> Result<(), Box<dyn Error>> {
+    let prompt = env::args().nth(1).unwrap_or_default();
+    if !prompt.to_lowercase().contains("password") {
+        return Err(format!("no answer for prompt: {}", prompt).into());
+    }
+    let store = env::var(STORE_VAR)?;
+    let id = env::var(ID_VAR)?;
+
+    let connections: Vec<StoredConnection> = serde_json::from_str(&fs::read_to_string(store)?)?;
+    let password = connections
+        .into_iter()
+        .find(|conn| !id.is_empty() && conn.id == id)
+        .and_then(|conn| conn.password)
+        .ok_or_else(|| format!("no stored password for connection {}", id))?;
+    writeln!(io::stdout(), "{}", password)?;
+    Ok(())
+}
@@ -0,0 +1,110 @@
+use std::time::Duration;
+
+use ratatui::widgets::TableState;
+
+use crate::logging::{Action, Pending};
+use crate::model::model::{Config, ConnectionItem, SshBackend};
+use crate::remote::RemoteCommand;
+
+/// One host taking part in a broadcast. `run` stays empty until a concurrency slot frees up.
+pub(crate) struct HostRun {
+    pub(crate) item: ConnectionItem,
+    pub(crate) backend: SshBackend,
+    pub(crate) run: Option<RemoteCommand>,
+}
+
+/// A command running on several hosts in parallel.
+pub(crate) struct Broadcast {
+    pub(crate) command: String,
+    pub(crate) hosts: Vec<HostRun>,
+    pub(crate) state: TableState,
+    /// Host whose output is currently shown instead of the result table.
+    pub(crate) open: Option<usize>,
+    concurrency: usize,
+    timeout: Duration,
+}
+
+impl Broadcast {
+    pub(crate) fn new(command: &str, items: Vec<ConnectionItem>, cfg: &Config) -> Self {
+        let mut broadcast = Self {
+            command: command.to_string(),
+            hosts: items
+                .into_iter()
+                .map(|item| HostRun {
+                    backend: item.backend(cfg),
+                    item,
+                    run: None,
+                })
+                .collect(),
+            state: TableState::default().with_selected(Some(0)),
+            open: None,
+            concurrency: cfg.broadcast_concurrency.max(1),
+            timeout: Duration::from_secs(cfg.broadcast_timeout_secs),
+        };
+        broadcast.poll();
+        broadcast
+    }
+
+    /// Collects output from running hosts and starts pending ones while there are free slots.
+    pub(crate) fn poll(&mut self) {
+        let mut running = 0;
+        for host in self.hosts.iter_mut() {
+            if let Some(run) = host.run.as_mut() {
+                run.poll();
+                if run.is_running() {
+                    running += 1;
+                }
+            }
+        }
+
+        for host in self.hosts.iter_mut().filter(|host| host.run.is_none()) {
+            if running >= self.concurrency {
+                break;
+            }
+            let mut run =
+                RemoteCommand::run_on(&host.item, &self.command, host.backend, Some(self.timeout));
+            run.audit = Some(Pending::start(
+                Action::Run,
+                (&host.item).into(),
+                self.command.clone(),
+            ));
+            host.run = Some(run);
+            running += 1;
+        }
+    }
+
+    pub(crate) fn finished(&self) -> usize {
+        self.hosts
+            .iter()
+            .filter(|host| host.run.as_ref().is_some_and(|run| !run.is_running()))
+            .count()
+    }
+
+    pub(crate) fn next(&mut self) {
+        let i = match self.state.selected() {
+            Some(i) if i + 1 < self.hosts.len() => i + 1,
+            _ => 0,
+        };
+        self.state.select(Some(i));
+    }
+
+    pub(crate) fn previous(&mut self) {
+        let i = match self.state.selected() {
+            Some(0) | None => self.hosts.len().saturating_sub(1),
+            Some(i) => i - 1,
+        };
+        self.state.select(Some(i));
+    }
+
+    pub(crate) fn open_selected(&mut self) {
+        if let Some(i) = self.state.selected() {
+            if self.hosts[i].run.is_some() {
+                self.open = Some(i);
+            }
+        }
+    }
+
+    pub(crate) fn open_run(&mut self) -> Option<&mut RemoteCommand> {
+        self.open.and_then(|i| self.hosts[i].run.as_mut())
+    }
+}
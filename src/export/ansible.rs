@@ -0,0 +1,462 @@
+//! The store as an Ansible inventory, in the shape the importer reads back: groups nest like the
+//! connections' group paths, tags are further groups listed after them, and hosts without a
+//! group are listed in `ungrouped`. Passwords are left out.
+
+use std::collections::HashMap;
+
+use serde_yaml::{Mapping, Value};
+
+use crate::import::ansible::{ALL, HOST, KEY_FILE, PORT, UNGROUPED, USER};
+use crate::model::model::{Defaults, Protocol, StoredConnection};
+
+type Vars = Vec<(&'static str, String)>;
+
+pub(super) struct Inventory {
+    /// `all` variables, from the defaults in the config.
+    vars: Vars,
+    pub(super) hosts: Vec<Host>,
+    ungrouped: Vec<usize>,
+    /// Groups of the connections, parents before their children.
+    groups: Vec<Group>,
+    tags: Vec<Group>,
+    pub(super) skipped: Vec<String>,
+}
+
+pub(super) struct Host {
+    name: String,
+    vars: Vars,
+}
+
+struct Group {
+    /// The group path up to this group, e.g. `prod/web`.
+    path: String,
+    name: String,
+    parent: Option<usize>,
+    hosts: Vec<usize>,
+}
+
+impl Inventory {
+    pub(super) fn new(connections: &[StoredConnection], defaults: &Defaults) -> Self {
+        let mut inventory = Inventory {
+            vars: vars(&defaults.user, &defaults.port, &defaults.identity_file),
+            hosts: Vec::new(),
+            ungrouped: Vec::new(),
+            groups: Vec::new(),
+            tags: Vec::new(),
+            skipped: Vec::new(),
+        };
+        let connections: Vec<&StoredConnection> = connections
+            .iter()
+            .filter(|connection| {
+                let reason = if connection.host.trim().is_empty() {
+                    "no host"
+                } else if connection.protocol == Some(Protocol::Telnet) {
+                    "Telnet isn't an Ansible connection"
+                } else {
+                    return true;
+                };
+                inventory
+                    .skipped
+                    .push(format!("{}: {}", connection.label, reason));
+                false
+            })
+            .collect();
+        let names = group_names(&connections);
+
+        for connection in connections {
+            let host = inventory.hosts.len();
+            let mut name = match connection.label.split_whitespace().collect::<Vec<_>>() {
+                words if words.is_empty() => connection.host.trim().to_string(),
+                words => words.join("_"),
+            };
+            if inventory.hosts.iter().any(|h| h.name == name) {
+                name = (2..)
+                    .map(|n| format!("{}-{}", name, n))
+                    .find(|candidate| !inventory.hosts.iter().any(|h| &h.name == candidate))
+                    .unwrap_or_default();
+            }
+            let mut host_vars = Vec::new();
+            if connection.host.trim() != name {
+                host_vars.push((HOST, connection.host.trim().to_string()));
+            }
+            host_vars.extend(vars(
+                &connection.user,
+                &connection.port,
+                &connection.identity_file,
+            ));
+            inventory.hosts.push(Host {
+                name,
+                vars: host_vars,
+            });
+
+            let segments = segments(connection.group.as_deref().unwrap_or_default());
+            if segments.is_empty() {
+                inventory.ungrouped.push(host);
+            }
+            let mut parent = None;
+            for depth in 1..=segments.len() {
+                let path = segments[..depth].join("/");
+                let i = match inventory.groups.iter().position(|g| g.path == path) {
+                    Some(i) => i,
+                    None => {
+                        inventory.groups.push(Group {
+                            name: names[&path].clone(),
+                            path,
+                            parent,
+                            hosts: Vec::new(),
+                        });
+                        inventory.groups.len() - 1
+                    }
+                };
+                parent = Some(i);
+            }
+            if let Some(i) = parent {
+                inventory.groups[i].hosts.push(host);
+            }
+
+            for tag in &connection.tags {
+                let name = group_name(tag);
+                match inventory.tags.iter_mut().find(|g| g.name == name) {
+                    Some(group) if group.hosts.contains(&host) => {}
+                    Some(group) => group.hosts.push(host),
+                    None => inventory.tags.push(Group {
+                        path: tag.clone(),
+                        name,
+                        parent: None,
+                        hosts: vec![host],
+                    }),
+                }
+            }
+        }
+        inventory
+    }
+
+    pub(super) fn yaml(&self) -> Result<String, String> {
+        let mut children = Mapping::new();
+        if !self.ungrouped.is_empty() {
+            let mut ungrouped = Mapping::new();
+            ungrouped.insert(
+                "hosts".into(),
+                self.yaml_hosts(&self.ungrouped, true).into(),
+            );
+            children.insert(UNGROUPED.into(), ungrouped.into());
+        }
+        for (i, group) in self.groups.iter().enumerate() {
+            if group.parent.is_none() {
+                children.insert(group.name.clone().into(), self.yaml_group(i));
+            }
+        }
+        for tag in &self.tags {
+            let tagged = self.yaml_hosts(&tag.hosts, false);
+            // The tag's hosts join the group of the same name, as they do in INI. That group
+            // moves after the others so the hosts are still listed under their own group first.
+            let mut group = match children.shift_remove(tag.name.as_str()) {
+                Some(Value::Mapping(group)) => group,
+                _ => Mapping::new(),
+            };
+            match group.get_mut("hosts").and_then(Value::as_mapping_mut) {
+                Some(hosts) => {
+                    for (host, vars) in tagged {
+                        if !hosts.contains_key(&host) {
+                            hosts.insert(host, vars);
+                        }
+                    }
+                }
+                None => {
+                    group.insert("hosts".into(), tagged.into());
+                }
+            }
+            children.insert(tag.name.clone().into(), group.into());
+        }
+
+        let mut all = Mapping::new();
+        if !self.vars.is_empty() {
+            all.insert("vars".into(), yaml_vars(&self.vars));
+        }
+        if !children.is_empty() {
+            all.insert("children".into(), children.into());
+        }
+        let mut root = Mapping::new();
+        root.insert(ALL.into(), all.into());
+        serde_yaml::to_string(&root).map_err(|e| e.to_string())
+    }
+
+    fn yaml_group(&self, i: usize) -> Value {
+        let mut group = Mapping::new();
+        if !self.groups[i].hosts.is_empty() {
+            group.insert(
+                "hosts".into(),
+                self.yaml_hosts(&self.groups[i].hosts, true).into(),
+            );
+        }
+        let children: Mapping = self
+            .groups
+            .iter()
+            .enumerate()
+            .filter(|(_, child)| child.parent == Some(i))
+            .map(|(c, child)| (child.name.clone().into(), self.yaml_group(c)))
+            .collect();
+        if !children.is_empty() {
+            group.insert("children".into(), children.into());
+        }
+        group.into()
+    }
+
+    /// The hosts, with their variables where they are listed first.
+    fn yaml_hosts(&self, hosts: &[usize], with_vars: bool) -> Mapping {
+        hosts
+            .iter()
+            .map(|&i| {
+                let host = &self.hosts[i];
+                let vars = if with_vars && !host.vars.is_empty() {
+                    yaml_vars(&host.vars)
+                } else {
+                    Value::Null
+                };
+                (host.name.clone().into(), vars)
+            })
+            .collect()
+    }
+
+    pub(super) fn ini(&self) -> String {
+        let mut sections = Vec::new();
+        if !self.vars.is_empty() {
+            let lines: Vec<String> = self
+                .vars
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect();
+            sections.push(format!("[{}:vars]\n{}\n", ALL, lines.join("\n")));
+        }
+        if !self.ungrouped.is_empty() {
+            sections.push(self.ini_hosts(UNGROUPED, &self.ungrouped, true));
+        }
+        for (i, group) in self.groups.iter().enumerate() {
+            if !group.hosts.is_empty() {
+                sections.push(self.ini_hosts(&group.name, &group.hosts, true));
+            }
+            let children: Vec<&str> = self
+                .groups
+                .iter()
+                .filter(|child| child.parent == Some(i))
+                .map(|child| child.name.as_str())
+                .collect();
+            if !children.is_empty() {
+                sections.push(format!(
+                    "[{}:children]\n{}\n",
+                    group.name,
+                    children.join("\n")
+                ));
+            }
+        }
+        for tag in &self.tags {
+            sections.push(self.ini_hosts(&tag.name, &tag.hosts, false));
+        }
+        sections.join("\n")
+    }
+
+    fn ini_hosts(&self, group: &str, hosts: &[usize], with_vars: bool) -> String {
+        let mut section = format!("[{}]\n", group);
+        for &i in hosts {
+            let host = &self.hosts[i];
+            section.push_str(&host.name);
+            if with_vars {
+                for (key, value) in &host.vars {
+                    section.push_str(&format!(" {}={}", key, shell_words::quote(value)));
+                }
+            }
+            section.push('\n');
+        }
+        section
+    }
+}
+
+fn vars(user: &Option<String>, port: &Option<String>, identity_file: &Option<String>) -> Vars {
+    [(USER, user), (PORT, port), (KEY_FILE, identity_file)]
+        .into_iter()
+        .filter_map(|(key, value)| {
+            let value = value.as_deref()?.trim();
+            (!value.is_empty()).then(|| (key, value.to_string()))
+        })
+        .collect()
+}
+
+fn yaml_vars(vars: &Vars) -> Value {
+    vars.iter()
+        .map(|(key, value)| {
+            let value = match value.parse::<u16>() {
+                Ok(port) if *key == PORT => Value::from(port),
+                _ => Value::from(value.clone()),
+            };
+            (Value::from(*key), value)
+        })
+        .collect::<Mapping>()
+        .into()
+}
+
+fn segments(group: &str) -> Vec<String> {
+    group
+        .split('/')
+        .filter(|segment| !segment.trim().is_empty())
+        .map(group_name)
+        .collect()
+}
+
+/// Ansible group names are shared by the whole inventory. A group is named after its last
+/// segment unless other paths or the tags end in the same name, then after its whole path,
+/// e.g. `prod_web` next to `stage/web`.
+fn group_names(connections: &[&StoredConnection]) -> HashMap<String, String> {
+    let mut paths: HashMap<String, Vec<String>> = HashMap::new();
+    for connection in connections {
+        let segments = segments(connection.group.as_deref().unwrap_or_default());
+        for depth in 1..=segments.len() {
+            let path = segments[..depth].join("/");
+            let leaf = &segments[depth - 1];
+            let same_leaf = paths.entry(leaf.clone()).or_default();
+            if !same_leaf.contains(&path) {
+                same_leaf.push(path);
+            }
+        }
+    }
+    let tags: Vec<String> = connections
+        .iter()
+        .flat_map(|connection| connection.tags.iter().map(|tag| group_name(tag)))
+        .collect();
+
+    let mut names = HashMap::new();
+    for (leaf, same_leaf) in &paths {
+        for path in same_leaf {
+            let nested = path.contains('/');
+            let name = if same_leaf.len() > 1 || (nested && tags.contains(leaf)) {
+                path.replace('/', "_")
+            } else {
+                leaf.clone()
+            };
+            names.insert(path.clone(), name);
+        }
+    }
+    names
+}
+
+/// Letters, digits and `_`, not starting with a digit, as Ansible wants group names.
+fn group_name(name: &str) -> String {
+    let name: String = name
+        .trim()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", name)
+    } else {
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::import;
+
+    fn stored(label: &str, host: &str, group: Option<&str>, tags: &[&str]) -> StoredConnection {
+        StoredConnection {
+            label: label.to_string(),
+            host: host.to_string(),
+            group: group.map(str::to_string),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            ..StoredConnection::new()
+        }
+    }
+
+    /// The inventory read back by the importer, through a file as the import dialog reads it.
+    fn read_back(text: &str, name: &str) -> import::Imported {
+        let path =
+            std::env::temp_dir().join(format!("ssh-manager-{}-{}", std::process::id(), name));
+        fs::write(&path, text).unwrap();
+        let imported = import::load(&path);
+        fs::remove_file(&path).unwrap();
+        imported.unwrap()
+    }
+
+    #[test]
+    fn round_trips() {
+        let mut web = stored(
+            "web 1",
+            "web1.example.com",
+            Some("prod/web"),
+            &["monitored"],
+        );
+        web.port = Some("2222".to_string());
+        web.user = Some("deploy".to_string());
+        web.identity_file = Some("~/.ssh/deploy".to_string());
+        web.password = Some("secret".to_string());
+        let connections = vec![
+            web,
+            stored("stage-web", "stage.example.com", Some("stage/web"), &[]),
+            stored("loose", "loose.example.com", None, &["monitored"]),
+            stored("router", "10.0.0.1", None, &[]),
+        ];
+        let mut connections = connections;
+        connections[3].protocol = Some(Protocol::Telnet);
+        let defaults = Defaults {
+            user: Some("root".to_string()),
+            ..Defaults::default()
+        };
+        let inventory = Inventory::new(&connections, &defaults);
+        assert_eq!(
+            inventory.skipped,
+            ["router: Telnet isn't an Ansible connection"]
+        );
+
+        for (text, name) in [
+            (inventory.yaml().unwrap(), "hosts.yml"),
+            (inventory.ini(), "hosts"),
+        ] {
+            let imported = read_back(&text, name);
+            assert!(
+                imported.skipped.is_empty(),
+                "{}: {:?}",
+                name,
+                imported.skipped
+            );
+            assert_eq!(imported.connections.len(), 3, "{}", name);
+            let find = |label: &str| {
+                imported
+                    .connections
+                    .iter()
+                    .find(|connection| connection.label == label)
+                    .unwrap()
+            };
+            let (web, stage, loose) = (find("web_1"), find("stage-web"), find("loose"));
+            assert_eq!(web.host, "web1.example.com");
+            assert_eq!(web.group.as_deref(), Some("prod/prod_web"));
+            assert_eq!(web.tags, ["monitored"]);
+            assert_eq!(web.port.as_deref(), Some("2222"));
+            assert_eq!(web.user.as_deref(), Some("deploy"));
+            assert_eq!(web.identity_file.as_deref(), Some("~/.ssh/deploy"));
+            assert_eq!(web.password, None);
+
+            assert_eq!(stage.group.as_deref(), Some("stage/stage_web"));
+            assert_eq!(stage.user.as_deref(), Some("root"));
+            assert_eq!(loose.group, None);
+            assert_eq!(loose.tags, ["monitored"]);
+        }
+    }
+
+    #[test]
+    fn merges_tags_into_groups_of_the_same_name() {
+        let connections = vec![
+            stored("db1", "db1", Some("db"), &[]),
+            stored("web1", "web1", Some("web"), &["db"]),
+        ];
+        let inventory = Inventory::new(&connections, &Defaults::default());
+        let imported = read_back(&inventory.yaml().unwrap(), "merge.yml");
+        let [web1, db1] = imported.connections.as_slice() else {
+            panic!("expected two connections");
+        };
+        assert_eq!(db1.group.as_deref(), Some("db"));
+        assert_eq!(web1.group.as_deref(), Some("web"));
+        assert_eq!(web1.tags, ["db"]);
+    }
+}
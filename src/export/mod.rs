@@ -0,0 +1,151 @@
+//! Writers that turn the stored connections into files for other tools.
+
+mod ansible;
+mod table;
+
+use std::fs;
+use std::path::Path;
+
+use ratatui::widgets::ListState;
+
+use crate::model::model::{Defaults, StoredConnection};
+use crate::utils::expand_home;
+
+/// Columns of a CSV or Markdown export unless others are chosen.
+pub(crate) const DEFAULT_COLUMNS: [&str; 6] = ["label", "host", "group", "port", "user", "details"];
+
+/// Writes the connections as an Ansible inventory, in YAML for a `.yml` or `.yaml` file and in
+/// INI otherwise. Returns how many hosts were written and why others were left out.
+pub(crate) fn ansible_inventory(
+    connections: &[StoredConnection],
+    defaults: &Defaults,
+    path: &Path,
+) -> Result<(usize, Vec<String>), String> {
+    let yaml = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| matches!(extension.to_ascii_lowercase().as_str(), "yml" | "yaml"));
+    let inventory = ansible::Inventory::new(connections, defaults);
+    let text = if yaml {
+        inventory.yaml()?
+    } else {
+        inventory.ini()
+    };
+    fs::write(path, text).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok((inventory.hosts.len(), inventory.skipped))
+}
+
+/// Writes the connections as a Markdown table for a `.md` file and as CSV for a `.csv` file,
+/// with the fields of the connection file named in `columns`. `redact` replaces the passwords,
+/// wherever they are written, and the environment values with asterisks.
+pub(crate) fn table(
+    connections: &[StoredConnection],
+    columns: &[String],
+    redact: bool,
+    path: &Path,
+) -> Result<(), String> {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    let format = match extension.as_deref() {
+        Some("csv") => table::Format::Csv,
+        Some("md" | "markdown") => table::Format::Markdown,
+        _ => return Err(format!("{}: expected a .csv or .md file", path.display())),
+    };
+    if columns.is_empty() {
+        return Err("no columns selected".to_string());
+    }
+    let rows = table::rows(connections)?;
+    let fields = table::columns(&rows);
+    if let Some(column) = columns
+        .iter()
+        .find(|column| !fields.contains(column) && !DEFAULT_COLUMNS.contains(&column.as_str()))
+    {
+        return Err(format!(
+            "no connection has a {} field, there are {}",
+            column,
+            fields.join(", ")
+        ));
+    }
+    let text = table::render(&rows, columns, redact, format);
+    fs::write(path, text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// State of the CSV and Markdown export dialog.
+pub(crate) struct ExportForm {
+    pub(crate) connections: Vec<StoredConnection>,
+    pub(crate) path: String,
+    pub(crate) redact: bool,
+    /// Every field of the connections and whether it is exported.
+    pub(crate) columns: Vec<(String, bool)>,
+    /// 0 is the path, 1 the password redaction, the columns follow.
+    pub(crate) focus: usize,
+    /// Selection of the list with the redaction and the columns.
+    pub(crate) state: ListState,
+}
+
+impl ExportForm {
+    pub(crate) fn new(connections: Vec<StoredConnection>) -> Result<Self, String> {
+        let fields = table::columns(&table::rows(&connections)?);
+        let columns = fields
+            .into_iter()
+            .map(|field| {
+                let selected = DEFAULT_COLUMNS.contains(&field.as_str());
+                (field, selected)
+            })
+            .collect();
+        Ok(Self {
+            connections,
+            path: "connections.csv".to_string(),
+            redact: true,
+            columns,
+            focus: 0,
+            state: ListState::default(),
+        })
+    }
+
+    pub(crate) fn next(&mut self) {
+        self.focus = (self.focus + 1) % (self.columns.len() + 2);
+        self.state.select(self.focus.checked_sub(1));
+    }
+
+    pub(crate) fn previous(&mut self) {
+        let count = self.columns.len() + 2;
+        self.focus = (self.focus + count - 1) % count;
+        self.state.select(self.focus.checked_sub(1));
+    }
+
+    /// Flips the redaction or the column in focus.
+    pub(crate) fn toggle(&mut self) {
+        match self.focus {
+            0 => {}
+            1 => self.redact = !self.redact,
+            i => {
+                if let Some((_, selected)) = self.columns.get_mut(i - 2) {
+                    *selected = !*selected;
+                }
+            }
+        }
+    }
+
+    /// The path while it is in focus.
+    pub(crate) fn input_mut(&mut self) -> Option<&mut String> {
+        (self.focus == 0).then_some(&mut self.path)
+    }
+
+    pub(crate) fn write(&self) -> Result<(), String> {
+        let columns: Vec<String> = self
+            .columns
+            .iter()
+            .filter(|(_, selected)| *selected)
+            .map(|(column, _)| column.clone())
+            .collect();
+        table(
+            &self.connections,
+            &columns,
+            self.redact,
+            &expand_home(self.path.trim()),
+        )
+    }
+}
@@ -0,0 +1,279 @@
+//! The connections as a CSV file or a Markdown table: one row per connection, one column per
+//! field of the connection file, with the values as they are serialized there.
+
+use serde_yaml::{Mapping, Value};
+
+use crate::model::model::StoredConnection;
+
+const PASSWORD: &str = "password";
+const ENV: &str = "env";
+const REDACTED: &str = "********";
+
+#[derive(Copy, Clone)]
+pub(super) enum Format {
+    Csv,
+    Markdown,
+}
+
+/// The fields of the connections, as serialized in the connection file. Each connection writes
+/// its fields in the same order but leaves out the empty ones.
+pub(super) fn rows(connections: &[StoredConnection]) -> Result<Vec<Mapping>, String> {
+    connections
+        .iter()
+        .map(|connection| match serde_yaml::to_value(connection) {
+            Ok(Value::Mapping(fields)) => Ok(fields),
+            Ok(_) => Err(format!("{}: not a map of fields", connection.label)),
+            Err(e) => Err(format!("{}: {}", connection.label, e)),
+        })
+        .collect()
+}
+
+/// The field names of the rows in the order of the connection file.
+pub(super) fn columns(rows: &[Mapping]) -> Vec<String> {
+    let mut columns: Vec<String> = Vec::new();
+    for row in rows {
+        let mut position = 0;
+        for name in row.keys().filter_map(Value::as_str) {
+            match columns.iter().position(|column| column == name) {
+                Some(i) => position = i + 1,
+                None => {
+                    columns.insert(position, name.to_string());
+                    position += 1;
+                }
+            }
+        }
+    }
+    columns
+}
+
+pub(super) fn render(rows: &[Mapping], columns: &[String], redact: bool, format: Format) -> String {
+    let cells = rows.iter().map(|row| {
+        let row = if redact { redacted(row) } else { row.clone() };
+        columns
+            .iter()
+            .map(|column| cell(row.get(column.as_str())))
+            .collect::<Vec<String>>()
+    });
+    let header = columns.to_vec();
+    let mut lines = Vec::new();
+    match format {
+        Format::Csv => {
+            for record in std::iter::once(header).chain(cells) {
+                let fields: Vec<String> = record.iter().map(|field| csv_field(field)).collect();
+                lines.push(fields.join(","));
+            }
+        }
+        Format::Markdown => {
+            lines.push(markdown_row(&header));
+            lines.push(markdown_row(&vec!["---".to_string(); columns.len()]));
+            lines.extend(cells.map(|row| markdown_row(&row)));
+        }
+    }
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+/// The row with asterisks for the password, the values of the environment, which often holds
+/// tokens, and the password where it is written out in another field, e.g. the command template.
+/// The `{password}` placeholder itself stays.
+fn redacted(row: &Mapping) -> Mapping {
+    let password = cell(row.get(PASSWORD));
+    row.iter()
+        .map(|(key, value)| {
+            let value = match (key.as_str(), value) {
+                (Some(PASSWORD), _) if !password.is_empty() => REDACTED.into(),
+                (Some(ENV), Value::Mapping(vars)) => vars
+                    .iter()
+                    .map(|(name, value)| match value {
+                        Value::String(s) if s.is_empty() => (name.clone(), value.clone()),
+                        _ => (name.clone(), REDACTED.into()),
+                    })
+                    .collect::<Mapping>()
+                    .into(),
+                _ if password.is_empty() => value.clone(),
+                _ => without(value, &password),
+            };
+            (key.clone(), value)
+        })
+        .collect()
+}
+
+fn without(value: &Value, password: &str) -> Value {
+    match value {
+        Value::String(s) => s.replace(password, REDACTED).into(),
+        Value::Sequence(items) => items.iter().map(|item| without(item, password)).collect(),
+        Value::Mapping(pairs) => pairs
+            .iter()
+            .map(|(key, value)| (key.clone(), without(value, password)))
+            .collect::<Mapping>()
+            .into(),
+        _ => value.clone(),
+    }
+}
+
+/// Lists like the tags are written space separated and maps like the ssh options as
+/// `key=value` pairs, as the edit popup shows them.
+fn cell(value: Option<&Value>) -> String {
+    match value {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Number(n)) => n.to_string(),
+        Some(Value::Bool(b)) => b.to_string(),
+        Some(Value::Sequence(items)) => items
+            .iter()
+            .map(|item| cell(Some(item)))
+            .collect::<Vec<_>>()
+            .join(" "),
+        Some(Value::Mapping(pairs)) => pairs
+            .iter()
+            .map(|(key, value)| format!("{}={}", cell(Some(key)), cell(Some(value))))
+            .collect::<Vec<_>>()
+            .join(" "),
+        Some(Value::Tagged(tagged)) => cell(Some(&tagged.value)),
+        Some(Value::Null) | None => String::new(),
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn markdown_row(cells: &[String]) -> String {
+    let cells: Vec<String> = cells
+        .iter()
+        .map(|cell| {
+            cell.replace('|', "\\|")
+                .replace("\r\n", "<br>")
+                .replace('\n', "<br>")
+        })
+        .collect();
+    format!("| {} |", cells.join(" | "))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    fn columns_of(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    fn web() -> StoredConnection {
+        StoredConnection {
+            label: "web, \"primary\"".to_string(),
+            host: "web.example.com".to_string(),
+            tags: vec!["prod".to_string(), "web".to_string()],
+            password: Some("s3cret".to_string()),
+            details: Some("line one\nline | two".to_string()),
+            command_template: Some(
+                "sshpass -p s3cret ssh {user}@{host} # or -p {password}".to_string(),
+            ),
+            env: BTreeMap::from([
+                ("EMPTY".to_string(), String::new()),
+                ("TOKEN".to_string(), "abc".to_string()),
+            ]),
+            ..StoredConnection::new()
+        }
+    }
+
+    #[test]
+    fn orders_columns_as_the_connection_file() {
+        let mut other = StoredConnection::new();
+        other.group = Some("prod".to_string());
+        other.wake_mac = Some("aa:bb:cc:dd:ee:ff".to_string());
+        let rows = rows(&[web(), other]).unwrap();
+        let columns = columns(&rows);
+        assert_eq!(&columns[..3], ["id", "label", "host"]);
+        for row in &rows {
+            let positions: Vec<usize> = row
+                .keys()
+                .filter_map(Value::as_str)
+                .map(|name| columns.iter().position(|column| column == name).unwrap())
+                .collect();
+            assert!(
+                positions.windows(2).all(|pair| pair[0] < pair[1]),
+                "{:?}",
+                columns
+            );
+        }
+        let mut unique = columns.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), columns.len());
+    }
+
+    #[test]
+    fn quotes_csv_fields() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+        assert_eq!(csv_field("cr\r"), "\"cr\r\"");
+    }
+
+    #[test]
+    fn escapes_markdown_cells() {
+        let cells = columns_of(&["a|b", "one\r\ntwo\nthree", ""]);
+        assert_eq!(markdown_row(&cells), "| a\\|b | one<br>two<br>three |  |");
+    }
+
+    #[test]
+    fn renders_csv() {
+        let rows = rows(&[web()]).unwrap();
+        let text = render(
+            &rows,
+            &columns_of(&["label", "tags", "details", "port"]),
+            true,
+            Format::Csv,
+        );
+        assert_eq!(
+            text,
+            "label,tags,details,port\n\
+             \"web, \"\"primary\"\"\",prod web,\"line one\nline | two\",\n"
+        );
+    }
+
+    #[test]
+    fn renders_markdown() {
+        let rows = rows(&[web()]).unwrap();
+        let text = render(
+            &rows,
+            &columns_of(&["host", "details"]),
+            false,
+            Format::Markdown,
+        );
+        assert_eq!(
+            text,
+            "| host | details |\n\
+             | --- | --- |\n\
+             | web.example.com | line one<br>line \\| two |\n"
+        );
+    }
+
+    #[test]
+    fn redacts_secrets() {
+        let rows = rows(&[web(), StoredConnection::new()]).unwrap();
+        let columns = columns_of(&["password", "command_template", "env"]);
+
+        let text = render(&rows, &columns, true, Format::Csv);
+        assert!(
+            !text.contains("s3cret") && !text.contains("abc"),
+            "{}",
+            text
+        );
+        assert_eq!(
+            text,
+            "password,command_template,env\n\
+             ********,sshpass -p ******** ssh {user}@{host} # or -p {password},EMPTY= TOKEN=********\n\
+             ,,\n"
+        );
+
+        let text = render(&rows, &columns, false, Format::Csv);
+        assert!(text.contains("s3cret,sshpass -p s3cret") && text.contains("TOKEN=abc"));
+    }
+}
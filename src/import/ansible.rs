@@ -0,0 +1,602 @@
+//! Ansible inventories in INI or YAML. A host's first group, with the parents leading to it,
+//! becomes its group, its other groups become tags, and the connection variables its fields.
+//! The export in `crate::export` writes the same variables back.
+
+use std::collections::HashMap;
+
+use serde_yaml::Value;
+
+use super::{connection, non_empty, split_host_port, Imported};
+
+pub(crate) const HOST: &str = "ansible_host";
+pub(crate) const PORT: &str = "ansible_port";
+pub(crate) const USER: &str = "ansible_user";
+pub(crate) const KEY_FILE: &str = "ansible_ssh_private_key_file";
+const PASSWORDS: [&str; 2] = ["ansible_password", "ansible_ssh_pass"];
+const CONNECTION: &str = "ansible_connection";
+/// Names from before Ansible 2.0, still read by it.
+const OLD_NAMES: [(&str, &str); 3] = [
+    ("ansible_ssh_host", HOST),
+    ("ansible_ssh_port", PORT),
+    ("ansible_ssh_user", USER),
+];
+
+pub(crate) const ALL: &str = "all";
+/// Hosts listed here, which the export does for those without a group, keep no group.
+pub(crate) const UNGROUPED: &str = "ungrouped";
+/// Hosts a single pattern may expand to, so a typo like `host[0:99999999]` can't fill memory.
+const MAX_HOSTS: usize = 10_000;
+
+#[derive(Default)]
+struct Inventory {
+    groups: Vec<Group>,
+    hosts: Vec<Host>,
+}
+
+struct Group {
+    name: String,
+    parents: Vec<String>,
+    vars: Vec<(String, String)>,
+}
+
+struct Host {
+    name: String,
+    /// The groups listing the host, in the order they appear in the file.
+    groups: Vec<String>,
+    vars: Vec<(String, String)>,
+}
+
+impl Inventory {
+    fn group(&mut self, name: &str) -> &mut Group {
+        let i = match self.groups.iter().position(|group| group.name == name) {
+            Some(i) => i,
+            None => {
+                self.groups.push(Group {
+                    name: name.to_string(),
+                    parents: Vec::new(),
+                    vars: Vec::new(),
+                });
+                self.groups.len() - 1
+            }
+        };
+        &mut self.groups[i]
+    }
+
+    fn host(&mut self, name: &str, group: &str) -> &mut Host {
+        let i = match self.hosts.iter().position(|host| host.name == name) {
+            Some(i) => i,
+            None => {
+                self.hosts.push(Host {
+                    name: name.to_string(),
+                    groups: Vec::new(),
+                    vars: Vec::new(),
+                });
+                self.hosts.len() - 1
+            }
+        };
+        let host = &mut self.hosts[i];
+        if !host.groups.iter().any(|g| g == group) {
+            host.groups.push(group.to_string());
+        }
+        host
+    }
+
+    fn add_child(&mut self, parent: &str, child: &str) {
+        self.group(parent);
+        let parents = &mut self.group(child).parents;
+        if !parents.iter().any(|p| p == parent) {
+            parents.push(parent.to_string());
+        }
+    }
+
+    fn parents(&self, name: &str) -> &[String] {
+        self.groups
+            .iter()
+            .find(|group| group.name == name)
+            .map_or(&[], |group| group.parents.as_slice())
+    }
+
+    /// The groups from `all` down to `name` along the first parent of each, e.g. `prod/web`.
+    fn path(&self, name: &str) -> String {
+        let mut chain = vec![name];
+        while let Some(parent) = self
+            .parents(chain[chain.len() - 1])
+            .iter()
+            .find(|parent| !is_implicit(parent) && !chain.contains(&parent.as_str()))
+        {
+            chain.push(parent);
+        }
+        chain.reverse();
+        chain.join("/")
+    }
+
+    /// Steps from `all` to the group. Variables of deeper groups win, as in Ansible.
+    fn depth(&self, name: &str, seen: &mut Vec<String>) -> usize {
+        if name == ALL || seen.iter().any(|s| s == name) {
+            return 0;
+        }
+        seen.push(name.to_string());
+        let depth = self
+            .parents(name)
+            .iter()
+            .map(|parent| self.depth(parent, seen))
+            .max()
+            .unwrap_or(0);
+        seen.pop();
+        depth + 1
+    }
+
+    /// The variables of a host: those of `all`, then of its groups and their parents from the
+    /// least to the most specific, then its own.
+    fn vars(&self, host: &Host) -> HashMap<String, String> {
+        let mut groups: Vec<&str> = vec![ALL];
+        let mut pending: Vec<&str> = host.groups.iter().map(String::as_str).collect();
+        while let Some(name) = pending.pop() {
+            if !groups.contains(&name) {
+                groups.push(name);
+                pending.extend(self.parents(name).iter().map(String::as_str));
+            }
+        }
+        let mut ordered: Vec<(usize, &str)> = groups
+            .into_iter()
+            .map(|name| (self.depth(name, &mut Vec::new()), name))
+            .collect();
+        ordered.sort();
+
+        let mut vars = HashMap::new();
+        let group_vars = ordered.iter().filter_map(|(_, name)| {
+            self.groups
+                .iter()
+                .find(|group| group.name == *name)
+                .map(|group| &group.vars)
+        });
+        for (key, value) in group_vars.chain([&host.vars]).flatten() {
+            let key = OLD_NAMES
+                .iter()
+                .find(|(old, _)| old == key)
+                .map_or(key.as_str(), |(_, new)| new);
+            vars.insert(key.to_string(), value.clone());
+        }
+        vars
+    }
+}
+
+fn is_implicit(group: &str) -> bool {
+    group == ALL || group == UNGROUPED
+}
+
+pub(super) fn parse(text: &str, imported: &mut Imported) {
+    let first_line = text
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with(';'))
+        .unwrap_or_default();
+    let inventory = if first_line == "---" || first_line.ends_with(':') {
+        yaml(text, imported)
+    } else {
+        ini(text, imported)
+    };
+
+    for host in &inventory.hosts {
+        let vars = inventory.vars(host);
+        let var = |name: &str| vars.get(name).and_then(|value| non_empty(value));
+        if let Some(kind) = var(CONNECTION) {
+            if !matches!(kind.as_str(), "ssh" | "smart" | "paramiko") {
+                imported.skipped.push(format!(
+                    "Ansible host {}: {} connections aren't supported",
+                    host.name, kind
+                ));
+                continue;
+            }
+        }
+        let address = var(HOST).unwrap_or_else(|| host.name.clone());
+
+        let mut connection = connection(
+            &host.name,
+            &address,
+            var(PORT),
+            &var(USER).unwrap_or_default(),
+        );
+        let mut groups: Vec<&str> = host
+            .groups
+            .iter()
+            .map(String::as_str)
+            .filter(|group| !is_implicit(group))
+            .collect();
+        if !host.groups.iter().any(|group| group == UNGROUPED) && !groups.is_empty() {
+            connection.group = Some(inventory.path(groups.remove(0)));
+        }
+        connection.tags = groups.into_iter().map(str::to_string).collect();
+        connection.identity_file = var(KEY_FILE);
+        connection.password = PASSWORDS.iter().find_map(|name| var(name));
+        connection.details = Some("Imported from Ansible inventory".to_string());
+        imported.connections.push(connection);
+    }
+}
+
+/// `[group]` sections list hosts with their variables, `[group:vars]` ones set variables and
+/// `[group:children]` ones list groups. Hosts before the first section are ungrouped.
+fn ini(text: &str, imported: &mut Imported) -> Inventory {
+    enum Kind {
+        Hosts,
+        Vars,
+        Children,
+    }
+
+    let mut inventory = Inventory::default();
+    let mut group = UNGROUPED.to_string();
+    let mut kind = Kind::Hosts;
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            (group, kind) = match name.rsplit_once(':') {
+                Some((name, "vars")) => (name.to_string(), Kind::Vars),
+                Some((name, "children")) => (name.to_string(), Kind::Children),
+                _ => (name.to_string(), Kind::Hosts),
+            };
+            inventory.group(&group);
+            continue;
+        }
+        match kind {
+            Kind::Hosts => {
+                let words = match shell_words::split(line) {
+                    Ok(words) => words,
+                    Err(e) => {
+                        imported
+                            .skipped
+                            .push(format!("Ansible inventory line {}: {}", n + 1, e));
+                        continue;
+                    }
+                };
+                let Some((pattern, vars)) = words.split_first() else {
+                    continue;
+                };
+                // `name:port` is a short form of `ansible_port`.
+                let (pattern, port) = match split_host_port(pattern) {
+                    (name, Some(port)) if port.parse::<u16>().is_ok() => {
+                        (name, Some((PORT.to_string(), port)))
+                    }
+                    _ => (pattern.clone(), None),
+                };
+                let vars: Vec<(String, String)> = port
+                    .into_iter()
+                    .chain(
+                        vars.iter()
+                            .filter_map(|var| var.split_once('='))
+                            .map(|(key, value)| (key.to_string(), value.to_string())),
+                    )
+                    .collect();
+                match expand(&pattern) {
+                    Ok(names) => {
+                        for name in names {
+                            inventory.host(&name, &group).vars.extend(vars.clone());
+                        }
+                    }
+                    Err(e) => {
+                        imported
+                            .skipped
+                            .push(format!("Ansible inventory line {}: {}", n + 1, e))
+                    }
+                }
+            }
+            Kind::Vars => {
+                if let Some((key, value)) = line.split_once('=') {
+                    let value = value.trim();
+                    let value = ['"', '\'']
+                        .iter()
+                        .find_map(|q| value.strip_prefix(*q)?.strip_suffix(*q))
+                        .unwrap_or(value);
+                    inventory
+                        .group(&group)
+                        .vars
+                        .push((key.trim().to_string(), value.to_string()));
+                }
+            }
+            Kind::Children => inventory.add_child(&group, line),
+        }
+    }
+    inventory
+}
+
+/// Groups map to `hosts`, `vars` and `children`, usually all below `all`.
+fn yaml(text: &str, imported: &mut Imported) -> Inventory {
+    let mut inventory = Inventory::default();
+    match serde_yaml::from_str::<Value>(text) {
+        Ok(Value::Mapping(groups)) => {
+            for (name, group) in &groups {
+                if let Some(name) = name.as_str() {
+                    yaml_group(&mut inventory, name, group, imported);
+                }
+            }
+        }
+        Ok(_) => imported
+            .skipped
+            .push("Ansible inventory: expected groups at the top".to_string()),
+        Err(e) => imported.skipped.push(format!("Ansible inventory: {}", e)),
+    }
+    inventory
+}
+
+fn yaml_group(inventory: &mut Inventory, name: &str, group: &Value, imported: &mut Imported) {
+    inventory.group(name);
+    let section = |key: &str| group.get(key).and_then(Value::as_mapping);
+    if let Some(vars) = section("vars") {
+        inventory.group(name).vars.extend(scalars(vars));
+    }
+    if let Some(hosts) = section("hosts") {
+        for (pattern, vars) in hosts {
+            let Some(pattern) = scalar(pattern) else {
+                continue;
+            };
+            let vars = vars.as_mapping().map(scalars).unwrap_or_default();
+            match expand(&pattern) {
+                Ok(hosts) => {
+                    for host in hosts {
+                        inventory.host(&host, name).vars.extend(vars.clone());
+                    }
+                }
+                Err(e) => imported
+                    .skipped
+                    .push(format!("Ansible group {}: {}", name, e)),
+            }
+        }
+    }
+    if let Some(children) = section("children") {
+        for (child, value) in children {
+            if let Some(child) = child.as_str() {
+                inventory.add_child(name, child);
+                yaml_group(inventory, child, value, imported);
+            }
+        }
+    }
+}
+
+/// The variables with a plain value; lists and dictionaries aren't connection values.
+fn scalars(vars: &serde_yaml::Mapping) -> Vec<(String, String)> {
+    vars.iter()
+        .filter_map(|(key, value)| Some((key.as_str()?.to_string(), scalar(value)?)))
+        .collect()
+}
+
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// The hosts of a pattern like `web[01:03].example.com` or `db-[a:c]`, with an optional
+/// stride as in `[0:10:2]`. Other names, IPv6 addresses among them, are kept as they are. A
+/// pattern of more than `MAX_HOSTS` hosts is an error.
+fn expand(pattern: &str) -> Result<Vec<String>, String> {
+    let range = pattern.find('[').and_then(|start| {
+        let end = start + pattern[start..].find(']')?;
+        Some((start, end))
+    });
+    let Some((start, end)) = range else {
+        return Ok(vec![pattern.to_string()]);
+    };
+    let (prefix, suffix) = (&pattern[..start], &pattern[end + 1..]);
+    let bounds: Vec<&str> = pattern[start + 1..end].split(':').collect();
+    let stride = match bounds.get(2) {
+        Some(stride) => stride.parse::<usize>().unwrap_or(0),
+        None => 1,
+    };
+    if !(2..=3).contains(&bounds.len()) || stride == 0 {
+        return Ok(vec![pattern.to_string()]);
+    }
+    let too_many = || format!("{} expands to more than {} hosts", pattern, MAX_HOSTS);
+    let (first, last) = (bounds[0], bounds[1]);
+
+    let names: Vec<String> = if let (Ok(a), Ok(b)) = (first.parse::<u32>(), last.parse::<u32>()) {
+        // A leading zero pads every number to its width.
+        let width = if first.starts_with('0') {
+            first.len()
+        } else {
+            0
+        };
+        if b >= a && ((b - a) as usize) / stride >= MAX_HOSTS {
+            return Err(too_many());
+        }
+        (a..=b)
+            .step_by(stride)
+            .map(|n| format!("{:0width$}", n, width = width))
+            .collect()
+    } else {
+        match (single_letter(first), single_letter(last)) {
+            (Some(a), Some(b)) => (a..=b).step_by(stride).map(String::from).collect(),
+            _ => return Ok(vec![pattern.to_string()]),
+        }
+    };
+    let mut hosts = Vec::new();
+    for name in names {
+        hosts.extend(expand(&format!("{}{}{}", prefix, name, suffix))?);
+        if hosts.len() > MAX_HOSTS {
+            return Err(too_many());
+        }
+    }
+    Ok(hosts)
+}
+
+fn single_letter(bound: &str) -> Option<char> {
+    let mut chars = bound.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_alphabetic() => Some(c),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hosts(pattern: &str) -> Vec<String> {
+        expand(pattern).unwrap()
+    }
+
+    #[test]
+    fn expands_ranges() {
+        assert_eq!(
+            hosts("web[01:03].example.com"),
+            [
+                "web01.example.com",
+                "web02.example.com",
+                "web03.example.com"
+            ]
+        );
+        assert_eq!(hosts("db-[a:c]"), ["db-a", "db-b", "db-c"]);
+        assert_eq!(hosts("n[0:10:5]"), ["n0", "n5", "n10"]);
+        assert_eq!(hosts("r[1:2]-[a:b]"), ["r1-a", "r1-b", "r2-a", "r2-b"]);
+        assert!(hosts("n[3:1]").is_empty());
+    }
+
+    #[test]
+    fn keeps_other_patterns() {
+        for pattern in [
+            "plain", "[::1]", "fe80::1", "n[1:2:0]", "n[a:10]", "n[1:2", "n[ab:c]",
+        ] {
+            assert_eq!(hosts(pattern), [pattern]);
+        }
+    }
+
+    #[test]
+    fn caps_ranges() {
+        assert!(expand("host[0:99999999]").is_err());
+        assert!(expand("host[0:999][0:999]").is_err());
+        assert_eq!(hosts("host[0:9999]").len(), MAX_HOSTS);
+        assert_eq!(hosts("host[0:99999999:10000]").len(), MAX_HOSTS);
+
+        let mut imported = Imported::default();
+        parse("[web]\nhost[0:99999999]\nother\n", &mut imported);
+        assert_eq!(imported.connections.len(), 1);
+        assert_eq!(imported.skipped.len(), 1);
+        assert!(imported.skipped[0].starts_with("Ansible inventory line 2: host[0:99999999]"));
+
+        let mut imported = Imported::default();
+        parse("all:\n  hosts:\n    host[0:99999999]:\n", &mut imported);
+        assert!(imported.connections.is_empty());
+        assert_eq!(imported.skipped.len(), 1);
+    }
+
+    #[test]
+    fn deeper_groups_win() {
+        // The child group comes first in the file but still wins over its parent, and the
+        // host's own variables win over both.
+        let text = "\
+[web]
+web1
+web2 ansible_user=own
+
+[web:vars]
+ansible_user=web
+ansible_ssh_port=2200
+
+[prod:children]
+web
+
+[prod:vars]
+ansible_user=prod
+ansible_port=2222
+ansible_ssh_private_key_file=~/.ssh/prod
+
+[all:vars]
+ansible_user=all
+ansible_password=secret
+";
+        let mut imported = Imported::default();
+        parse(text, &mut imported);
+
+        let [web1, web2] = imported.connections.as_slice() else {
+            panic!("expected two connections");
+        };
+        assert_eq!(web1.user.as_deref(), Some("web"));
+        assert_eq!(web1.port.as_deref(), Some("2200"));
+        assert_eq!(web1.identity_file.as_deref(), Some("~/.ssh/prod"));
+        assert_eq!(web1.password.as_deref(), Some("secret"));
+        assert_eq!(web1.group.as_deref(), Some("prod/web"));
+        assert_eq!(web2.user.as_deref(), Some("own"));
+    }
+
+    #[test]
+    fn reads_nested_yaml() {
+        let text = "\
+all:
+  vars:
+    ansible_user: deploy
+  hosts:
+    jump:
+      ansible_host: 10.0.0.1
+  children:
+    ungrouped:
+      hosts:
+        loose:
+    prod:
+      vars:
+        ansible_port: 2222
+      children:
+        web:
+          hosts:
+            web[1:2]:
+        db:
+          hosts:
+            db1:
+              ansible_user: postgres
+    monitored:
+      hosts:
+        web1:
+        db1:
+    windows:
+      hosts:
+        win1:
+          ansible_connection: winrm
+";
+        let mut imported = Imported::default();
+        parse(text, &mut imported);
+
+        let find = |label: &str| {
+            imported
+                .connections
+                .iter()
+                .find(|connection| connection.label == label)
+                .unwrap()
+        };
+        assert_eq!(imported.connections.len(), 5);
+        assert_eq!(find("jump").host, "10.0.0.1");
+        assert_eq!(find("jump").group, None);
+        assert_eq!(find("loose").group, None);
+
+        let web1 = find("web1");
+        assert_eq!(web1.group.as_deref(), Some("prod/web"));
+        assert_eq!(web1.tags, ["monitored"]);
+        assert_eq!(web1.port.as_deref(), Some("2222"));
+        assert_eq!(web1.user.as_deref(), Some("deploy"));
+        assert!(find("web2").tags.is_empty());
+
+        let db1 = find("db1");
+        assert_eq!(db1.group.as_deref(), Some("prod/db"));
+        assert_eq!(db1.user.as_deref(), Some("postgres"));
+
+        assert_eq!(imported.skipped.len(), 1);
+        assert!(imported.skipped[0].contains("winrm"));
+    }
+
+    #[test]
+    fn reports_broken_files() {
+        let mut imported = Imported::default();
+        parse("[web]\nweb1 ansible_user='open\n", &mut imported);
+        assert!(imported.connections.is_empty());
+        assert!(imported.skipped[0].starts_with("Ansible inventory line 2"));
+
+        let mut imported = Imported::default();
+        parse("---\n- a list\n", &mut imported);
+        assert_eq!(
+            imported.skipped,
+            ["Ansible inventory: expected groups at the top"]
+        );
+    }
+}
@@ -0,0 +1,96 @@
+//! MobaXterm session exports (`.mxtsessions`). Each folder is a `[Bookmarks]` or
+//! `[Bookmarks_<n>]` section with its path in `SubRep`; sessions are entries like
+//! `name=#109#0%host%22%user%...`, where the number after the icon is the session type.
+
+use super::{connection, group, non_empty, sections, Imported};
+use crate::model::model::Protocol;
+
+const SSH: &str = "0";
+const TELNET: &str = "1";
+
+pub(super) fn parse(text: &str, imported: &mut Imported) {
+    for section in sections(text) {
+        if !section.name.to_ascii_lowercase().starts_with("bookmarks") {
+            continue;
+        }
+        let folder = section.get("SubRep").and_then(group);
+        for (name, value) in &section.entries {
+            if name.eq_ignore_ascii_case("SubRep") || name.eq_ignore_ascii_case("ImgNum") {
+                continue;
+            }
+            let fields: Vec<&str> = value.split('%').collect();
+            let kind = fields[0].rsplit('#').next().unwrap_or_default();
+            let protocol = match kind {
+                SSH => None,
+                TELNET => Some(Protocol::Telnet),
+                _ => {
+                    imported.skipped.push(format!(
+                        "MobaXterm session {}: only SSH and Telnet sessions are supported",
+                        name
+                    ));
+                    continue;
+                }
+            };
+            let field = |i: usize| fields.get(i).copied().unwrap_or_default();
+            let host = field(1);
+            if host.trim().is_empty() {
+                imported
+                    .skipped
+                    .push(format!("MobaXterm session {}: no host", name));
+                continue;
+            }
+
+            let mut connection = connection(name, host, non_empty(field(2)), field(3));
+            connection.group = folder.clone();
+            connection.protocol = protocol;
+            connection.details = Some("Imported from MobaXterm".to_string());
+            imported.connections.push(connection);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sessions() {
+        let text = "\
+[Bookmarks]
+SubRep=
+ImgNum=42
+jump=#109#0%10.0.0.1%22%root%%-1%-1%%%22%%0%0%0%%%-1%0%0%0%%1080%%0%0%1
+
+[Bookmarks_1]
+SubRep=prod\\web
+ImgNum=41
+web1=#109#0%web1.example.com%2222%deploy%%
+router=#98#1%10.0.0.254%23%%
+desktop=#91#4%win.example.com%3389%%
+nohost=#109#0%%22%root
+broken=#109
+";
+        let mut imported = Imported::default();
+        parse(text, &mut imported);
+
+        let [jump, web, router] = imported.connections.as_slice() else {
+            panic!("expected three connections");
+        };
+        assert_eq!(jump.label, "jump");
+        assert_eq!(jump.group, None);
+        assert_eq!(jump.port.as_deref(), Some("22"));
+        assert_eq!(jump.user.as_deref(), Some("root"));
+
+        assert_eq!(web.group.as_deref(), Some("prod/web"));
+        assert_eq!(web.host, "web1.example.com");
+        assert_eq!(web.port.as_deref(), Some("2222"));
+
+        assert_eq!(router.protocol, Some(Protocol::Telnet));
+        assert_eq!(router.user, None);
+
+        assert_eq!(imported.skipped.len(), 3);
+        assert!(imported.skipped[0].contains("desktop"));
+        assert!(imported.skipped[1].contains("nohost: no host"));
+        assert!(imported.skipped[2].contains("broken"));
+    }
+}
@@ -0,0 +1,435 @@
+//! Importers for the session lists of other SSH clients and for Ansible inventories. Each turns
+//! a file into stored connections, which are previewed and checked against the existing ones
+//! before they are saved.
+
+pub(crate) mod ansible;
+mod mobaxterm;
+mod putty;
+mod remmina;
+mod termius;
+
+use std::fs;
+use std::path::Path;
+
+use ratatui::widgets::ListState;
+
+use crate::model::model::{ConnectionItem, Defaults, StoredConnection};
+
+/// What a file yielded: the connections, and why other entries were left out.
+#[derive(Default)]
+pub(crate) struct Imported {
+    pub(crate) connections: Vec<StoredConnection>,
+    pub(crate) skipped: Vec<String>,
+}
+
+#[derive(Copy, Clone)]
+enum Format {
+    Putty,
+    Remmina,
+    Termius,
+    MobaXterm,
+    Ansible,
+}
+
+impl Format {
+    /// The format of a file, from its extension.
+    fn of(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "reg" => Some(Format::Putty),
+            "remmina" => Some(Format::Remmina),
+            "csv" => Some(Format::Termius),
+            "mxtsessions" => Some(Format::MobaXterm),
+            "ini" | "yml" | "yaml" => Some(Format::Ansible),
+            _ => None,
+        }
+    }
+
+    fn parse(self, text: &str, imported: &mut Imported) {
+        match self {
+            Format::Putty => putty::parse(text, imported),
+            Format::Remmina => remmina::parse(text, imported),
+            Format::Termius => termius::parse(text, imported),
+            Format::MobaXterm => mobaxterm::parse(text, imported),
+            Format::Ansible => ansible::parse(text, imported),
+        }
+    }
+}
+
+/// Reads the sessions of a PuTTY `.reg` export, a Remmina `.remmina` file, a Termius `.csv`
+/// export, a MobaXterm `.mxtsessions` file or an Ansible inventory in INI or YAML. A directory
+/// imports every such file in it, e.g. Remmina's data directory with one file per connection.
+/// A file without an extension, like Ansible's `hosts`, is read as an inventory.
+pub(crate) fn load(path: &Path) -> Result<Imported, String> {
+    let mut files = Vec::new();
+    if path.is_dir() {
+        let entries = fs::read_dir(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        for entry in entries.filter_map(Result::ok) {
+            let file = entry.path();
+            if let Some(format) = Format::of(&file) {
+                files.push((file, format));
+            }
+        }
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+        if files.is_empty() {
+            return Err(format!("{} has no files to import", path.display()));
+        }
+    } else {
+        let format = match path.extension() {
+            None => Some(Format::Ansible),
+            Some(_) => Format::of(path),
+        }
+        .ok_or_else(|| {
+            format!(
+                "{}: expected a .reg, .remmina, .csv, .mxtsessions, .ini or .yml file",
+                path.display()
+            )
+        })?;
+        files.push((path.to_path_buf(), format));
+    }
+
+    let mut imported = Imported::default();
+    for (file, format) in files {
+        let bytes = fs::read(&file).map_err(|e| format!("{}: {}", file.display(), e))?;
+        format.parse(&decode(&bytes), &mut imported);
+    }
+    Ok(imported)
+}
+
+/// The text of a file, which is UTF-16 when Windows tools like regedit wrote it.
+fn decode(bytes: &[u8]) -> String {
+    match bytes {
+        [0xff, 0xfe, rest @ ..] => {
+            let units: Vec<u16> = rest
+                .chunks_exact(2)
+                .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        [0xef, 0xbb, 0xbf, rest @ ..] => String::from_utf8_lossy(rest).to_string(),
+        _ => String::from_utf8_lossy(bytes).to_string(),
+    }
+}
+
+/// A connection with the values every importer sets.
+fn connection(label: &str, host: &str, port: Option<String>, user: &str) -> StoredConnection {
+    StoredConnection {
+        label: label.trim().to_string(),
+        host: host.trim().to_string(),
+        // Kept even when it is 22, the defaults may have another port.
+        port: port.filter(|port| !port.is_empty()),
+        user: non_empty(user),
+        ..StoredConnection::new()
+    }
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// A group path with `\` separators, as Windows tools write them, turned into `/` ones.
+fn group(path: &str) -> Option<String> {
+    let group: Vec<&str> = path
+        .split(['/', '\\'])
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect();
+    non_empty(&group.join("/"))
+}
+
+/// `host`, `host:port` or `[address]:port`.
+fn split_host_port(server: &str) -> (String, Option<String>) {
+    let server = server.trim();
+    if let Some(rest) = server.strip_prefix('[') {
+        if let Some((host, port)) = rest.split_once(']') {
+            let port = port.strip_prefix(':').map(str::to_string);
+            return (host.to_string(), port);
+        }
+    }
+    match server.rsplit_once(':') {
+        // More than one colon is an IPv6 address without a port.
+        Some((host, port)) if !host.contains(':') => (host.to_string(), Some(port.to_string())),
+        _ => (server.to_string(), None),
+    }
+}
+
+/// A section of an INI style file with its `key=value` entries in order.
+struct Section {
+    name: String,
+    entries: Vec<(String, String)>,
+}
+
+impl Section {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Splits INI style text into sections. Entries before the first section are dropped.
+fn sections(text: &str) -> Vec<Section> {
+    let mut sections: Vec<Section> = Vec::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            sections.push(Section {
+                name: name.to_string(),
+                entries: Vec::new(),
+            });
+        } else if let (Some(section), Some((key, value))) =
+            (sections.last_mut(), line.split_once('='))
+        {
+            section
+                .entries
+                .push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    sections
+}
+
+/// Splits CSV text into records, with quoted fields that may hold commas, quotes and line
+/// breaks.
+fn csv_records(text: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => record.push(std::mem::take(&mut field)),
+            '\r' if !quoted => {}
+            '\n' if !quoted => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records.retain(|record| record.iter().any(|field| !field.trim().is_empty()));
+    records
+}
+
+/// An imported connection in the preview.
+pub(crate) struct Entry {
+    pub(crate) connection: StoredConnection,
+    /// Label of the connection with the same host, port and user, if there is one.
+    pub(crate) duplicate_of: Option<String>,
+    /// The label the connection had before it was renamed to not clash with another.
+    pub(crate) renamed_from: Option<String>,
+    pub(crate) selected: bool,
+}
+
+/// The connections of an import, to pick which of them to save.
+pub(crate) struct ImportPreview {
+    pub(crate) source: String,
+    pub(crate) entries: Vec<Entry>,
+    pub(crate) skipped: Vec<String>,
+    pub(crate) state: ListState,
+}
+
+impl ImportPreview {
+    /// Compares the imported connections with `existing` and with each other. Duplicates are
+    /// left out unless selected, labels that are taken get a `-2`, `-3`, ... suffix.
+    pub(crate) fn new(
+        source: &str,
+        imported: Imported,
+        existing: &[ConnectionItem],
+        defaults: &Defaults,
+    ) -> Self {
+        let mut seen: Vec<(String, String, String, String)> = existing
+            .iter()
+            .map(|item| {
+                let (host, port, user) = address(item);
+                (item.label.clone(), host, port, user)
+            })
+            .collect();
+        let mut entries = Vec::new();
+        for mut connection in imported.connections {
+            let item = ConnectionItem::from_stored(connection.clone(), defaults);
+            let (host, port, user) = address(&item);
+            let duplicate_of = seen
+                .iter()
+                .find(|(_, h, p, u)| (h, p, u) == (&host, &port, &user))
+                .map(|(label, ..)| label.clone());
+            let mut renamed_from = None;
+            if seen.iter().any(|(label, ..)| label == &connection.label) {
+                let label = (2..)
+                    .map(|n| format!("{}-{}", connection.label, n))
+                    .find(|candidate| !seen.iter().any(|(label, ..)| label == candidate))
+                    .unwrap_or_default();
+                renamed_from = Some(std::mem::replace(&mut connection.label, label));
+            }
+            seen.push((connection.label.clone(), host, port, user));
+            entries.push(Entry {
+                connection,
+                selected: duplicate_of.is_none(),
+                duplicate_of,
+                renamed_from,
+            });
+        }
+        let selected = (!entries.is_empty()).then_some(0);
+        Self {
+            source: source.to_string(),
+            entries,
+            skipped: imported.skipped,
+            state: ListState::default().with_selected(selected),
+        }
+    }
+
+    pub(crate) fn next(&mut self) {
+        let count = self.entries.len();
+        if let Some(i) = self.state.selected() {
+            self.state.select(Some((i + 1) % count));
+        }
+    }
+
+    pub(crate) fn previous(&mut self) {
+        let count = self.entries.len();
+        if let Some(i) = self.state.selected() {
+            self.state.select(Some((i + count - 1) % count));
+        }
+    }
+
+    pub(crate) fn toggle(&mut self) {
+        if let Some(entry) = self.state.selected().and_then(|i| self.entries.get_mut(i)) {
+            entry.selected = !entry.selected;
+        }
+    }
+
+    /// Selects every entry, or none when all are selected.
+    pub(crate) fn toggle_all(&mut self) {
+        let select = !self.entries.iter().all(|entry| entry.selected);
+        for entry in &mut self.entries {
+            entry.selected = select;
+        }
+    }
+
+    pub(crate) fn selected_connections(&self) -> Vec<StoredConnection> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.selected)
+            .map(|entry| entry.connection.clone())
+            .collect()
+    }
+}
+
+/// What makes two connections the same: host, port and user, with the defaults applied.
+fn address(item: &ConnectionItem) -> (String, String, String) {
+    (
+        item.host.to_ascii_lowercase(),
+        item.port.clone(),
+        item.user.clone(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_csv_records() {
+        let text = "a,\"b,c\",\"d\"\"e\"\r\n\"multi\nline\",x\n\n,\nlast,\"open";
+        assert_eq!(
+            csv_records(text),
+            [
+                vec!["a", "b,c", "d\"e"],
+                vec!["multi\nline", "x"],
+                vec!["last", "open"],
+            ]
+        );
+    }
+
+    #[test]
+    fn splits_host_and_port() {
+        let split = |server| split_host_port(server);
+        assert_eq!(split("h"), ("h".to_string(), None));
+        assert_eq!(
+            split(" h:2222 "),
+            ("h".to_string(), Some("2222".to_string()))
+        );
+        assert_eq!(
+            split("[::1]:22"),
+            ("::1".to_string(), Some("22".to_string()))
+        );
+        assert_eq!(split("[::1]"), ("::1".to_string(), None));
+        assert_eq!(split("fe80::1"), ("fe80::1".to_string(), None));
+    }
+
+    #[test]
+    fn decodes_byte_order_marks() {
+        let utf16: Vec<u8> = [0xff, 0xfe]
+            .into_iter()
+            .chain("[a]\r\n".encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+        assert_eq!(decode(&utf16), "[a]\r\n");
+        assert_eq!(decode(&[0xef, 0xbb, 0xbf, b'x']), "x");
+        assert_eq!(decode(b"plain"), "plain");
+    }
+
+    #[test]
+    fn normalizes_groups() {
+        assert_eq!(group("prod\\web"), Some("prod/web".to_string()));
+        assert_eq!(group(" /prod// web/ "), Some("prod/web".to_string()));
+        assert_eq!(group(" \\ "), None);
+    }
+
+    #[test]
+    fn reads_sections() {
+        let sections = sections("ignored=1\n[one]\n; comment\nKey = value\nno value\n[two]\n");
+        assert_eq!(sections.len(), 2);
+        assert_eq!(sections[0].name, "one");
+        assert_eq!(sections[0].get("key"), Some("value"));
+        assert!(sections[1].entries.is_empty());
+    }
+
+    #[test]
+    fn previews_duplicates_and_renames() {
+        let defaults = Defaults::default();
+        let existing = vec![ConnectionItem::from_stored(
+            connection("web", "web.example.com", None, ""),
+            &defaults,
+        )];
+        let imported = Imported {
+            connections: vec![
+                connection("web", "WEB.example.com", Some("22".to_string()), ""),
+                connection("web", "other.example.com", None, ""),
+            ],
+            skipped: vec!["skipped".to_string()],
+        };
+        let mut preview = ImportPreview::new("file", imported, &existing, &defaults);
+
+        let [duplicate, other] = preview.entries.as_slice() else {
+            panic!("expected two entries");
+        };
+        assert_eq!(duplicate.duplicate_of.as_deref(), Some("web"));
+        assert!(!duplicate.selected);
+        assert_eq!(duplicate.connection.label, "web-2");
+        assert_eq!(other.duplicate_of, None);
+        assert!(other.selected);
+        assert_eq!(other.connection.label, "web-3");
+        assert_eq!(other.renamed_from.as_deref(), Some("web"));
+
+        let selected = preview.selected_connections();
+        assert_eq!(selected.len(), 1);
+        assert_eq!(selected[0].host, "other.example.com");
+        preview.toggle_all();
+        assert_eq!(preview.selected_connections().len(), 2);
+    }
+}
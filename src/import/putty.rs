@@ -0,0 +1,183 @@
+//! PuTTY sessions as exported with `regedit /e putty.reg
+//! HKEY_CURRENT_USER\Software\SimonTatham\PuTTY\Sessions`.
+
+use super::{connection, group, sections, Imported};
+use crate::model::model::Protocol;
+
+const SESSIONS_KEY: &str = "\\Software\\SimonTatham\\PuTTY\\Sessions\\";
+
+/// Sessions are named `folder/name` by the session managers that add folders to PuTTY, which
+/// becomes the group.
+pub(super) fn parse(text: &str, imported: &mut Imported) {
+    for section in sections(text) {
+        let Some((_, name)) = section.name.split_once(SESSIONS_KEY) else {
+            continue;
+        };
+        let name = unescape_name(name);
+        let values: Vec<(String, String)> = section
+            .entries
+            .iter()
+            .map(|(key, value)| (unquote(key), value.clone()))
+            .collect();
+        let get = |key: &str| {
+            values
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, value)| value.as_str())
+        };
+
+        let host = get("HostName").map(unquote).unwrap_or_default();
+        if host.is_empty() {
+            // "Default Settings" and other sessions that only hold settings.
+            continue;
+        }
+        let protocol = match get("Protocol").map(unquote).as_deref() {
+            None | Some("ssh") => None,
+            Some("telnet") => Some(Protocol::Telnet),
+            Some(other) => {
+                imported
+                    .skipped
+                    .push(format!("PuTTY session {}: {} isn't supported", name, other));
+                continue;
+            }
+        };
+        // PuTTY takes `user@host` as the host name too.
+        let (user, host) = match host.rsplit_once('@') {
+            Some((user, host)) => (user.to_string(), host.to_string()),
+            None => (get("UserName").map(unquote).unwrap_or_default(), host),
+        };
+        let port = get("PortNumber")
+            .and_then(dword)
+            .map(|port| port.to_string());
+
+        let (folder, label) = match name.rsplit_once(['/', '\\']) {
+            Some((folder, label)) => (group(folder), label.to_string()),
+            None => (None, name.clone()),
+        };
+        let mut connection = connection(&label, &host, port, &user);
+        connection.group = folder;
+        connection.protocol = protocol;
+        connection.details = Some(format!("Imported from PuTTY session {}", name));
+        imported.connections.push(connection);
+    }
+}
+
+/// Session names are stored with spaces and special characters escaped as `%XX`.
+fn unescape_name(name: &str) -> String {
+    let bytes = name.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| name.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// A `"quoted"` registry name or string value with its `\\` and `\"` escapes resolved.
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    let inner = value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value);
+    let mut unquoted = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unquoted.extend(chars.next()),
+            c => unquoted.push(c),
+        }
+    }
+    unquoted
+}
+
+/// A `dword:00000016` value.
+fn dword(value: &str) -> Option<u32> {
+    u32::from_str_radix(value.trim().strip_prefix("dword:")?, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SESSIONS: &str = r#"Windows Registry Editor Version 5.00
+
+[HKEY_CURRENT_USER\Software\SimonTatham\PuTTY\Sessions\Default%20Settings]
+"HostName"=""
+
+[HKEY_CURRENT_USER\Software\SimonTatham\PuTTY\Sessions\prod%2Fweb%201]
+"HostName"="admin@web1.example.com"
+"PortNumber"=dword:00000016
+"Protocol"="ssh"
+
+[HKEY_CURRENT_USER\Software\SimonTatham\PuTTY\Sessions\router]
+"HostName"="10.0.0.1"
+"Protocol"="telnet"
+"PortNumber"=dword:zz
+"UserName"="ops"
+a line without a value
+
+[HKEY_CURRENT_USER\Software\SimonTatham\PuTTY\Sessions\console]
+"HostName"="COM1"
+"Protocol"="serial"
+
+[HKEY_CURRENT_USER\Software\Other]
+"HostName"="elsewhere"
+"#;
+
+    #[test]
+    fn parses_sessions() {
+        let mut imported = Imported::default();
+        parse(SESSIONS, &mut imported);
+
+        let [web, router] = imported.connections.as_slice() else {
+            panic!("expected two connections");
+        };
+        assert_eq!(web.label, "web 1");
+        assert_eq!(web.group.as_deref(), Some("prod"));
+        assert_eq!(web.host, "web1.example.com");
+        assert_eq!(web.user.as_deref(), Some("admin"));
+        assert_eq!(web.port.as_deref(), Some("22"));
+        assert_eq!(web.protocol, None);
+
+        assert_eq!(router.label, "router");
+        assert_eq!(router.protocol, Some(Protocol::Telnet));
+        assert_eq!(router.user.as_deref(), Some("ops"));
+        // A value that isn't a dword leaves the port to the defaults.
+        assert_eq!(router.port, None);
+
+        assert_eq!(imported.skipped.len(), 1);
+        assert!(imported.skipped[0].contains("serial"));
+    }
+
+    #[test]
+    fn unescapes_names_and_values() {
+        assert_eq!(unescape_name("my%20host%2Fa"), "my host/a");
+        // Broken escapes are kept as they are.
+        assert_eq!(unescape_name("100%"), "100%");
+        assert_eq!(unescape_name("%zz"), "%zz");
+        assert_eq!(unquote(r#""C:\\keys\\a \"b\"""#), r#"C:\keys\a "b""#);
+        assert_eq!(unquote("plain"), "plain");
+    }
+
+    #[test]
+    fn reads_dwords() {
+        assert_eq!(dword("dword:00000016"), Some(22));
+        assert_eq!(dword(" dword:0000ffff "), Some(65535));
+        assert_eq!(dword("00000016"), None);
+        assert_eq!(dword("dword:"), None);
+    }
+}
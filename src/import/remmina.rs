@@ -0,0 +1,103 @@
+//! Remmina connection files, one `.remmina` file per connection in
+//! `~/.local/share/remmina`.
+
+use super::{connection, group, non_empty, sections, split_host_port, Imported};
+
+/// SSH and SFTP connections are imported, without their passwords, which Remmina encrypts.
+pub(super) fn parse(text: &str, imported: &mut Imported) {
+    for section in sections(text) {
+        if !section.name.eq_ignore_ascii_case("remmina") {
+            continue;
+        }
+        let name = section.get("name").unwrap_or_default();
+        let protocol = section.get("protocol").unwrap_or_default();
+        if !matches!(protocol.to_ascii_uppercase().as_str(), "SSH" | "SFTP") {
+            imported.skipped.push(format!(
+                "Remmina connection {}: {} isn't supported",
+                name, protocol
+            ));
+            continue;
+        }
+        // Older versions keep the SSH values in their own keys.
+        let server = section
+            .get("server")
+            .and_then(non_empty)
+            .or_else(|| section.get("ssh_server").and_then(non_empty))
+            .unwrap_or_default();
+        let (host, port) = split_host_port(&server);
+        if host.is_empty() {
+            imported
+                .skipped
+                .push(format!("Remmina connection {}: no server", name));
+            continue;
+        }
+        let user = section
+            .get("username")
+            .and_then(non_empty)
+            .or_else(|| section.get("ssh_username").and_then(non_empty))
+            .unwrap_or_default();
+        let label = if name.trim().is_empty() { &host } else { name };
+
+        let mut connection = connection(label, &host, port, &user);
+        connection.group = section.get("group").and_then(group);
+        connection.identity_file = section.get("ssh_privatekey").and_then(non_empty);
+        connection.details = Some("Imported from Remmina".to_string());
+        imported.connections.push(connection);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_connections() {
+        let text = "\
+[remmina]
+name=Web
+protocol=SSH
+server=web.example.com:2222
+username=deploy
+group=prod/web
+ssh_privatekey=/home/u/.ssh/id
+
+[remmina]
+name=Desktop
+protocol=RDP
+server=win.example.com
+
+[remmina]
+name=
+protocol=SFTP
+ssh_server=old.example.com
+ssh_username=u
+
+[remmina]
+name=Empty
+protocol=SSH
+server=
+";
+        let mut imported = Imported::default();
+        parse(text, &mut imported);
+
+        let [web, old] = imported.connections.as_slice() else {
+            panic!("expected two connections");
+        };
+        assert_eq!(web.label, "Web");
+        assert_eq!(web.host, "web.example.com");
+        assert_eq!(web.port.as_deref(), Some("2222"));
+        assert_eq!(web.user.as_deref(), Some("deploy"));
+        assert_eq!(web.group.as_deref(), Some("prod/web"));
+        assert_eq!(web.identity_file.as_deref(), Some("/home/u/.ssh/id"));
+        assert_eq!(web.password.as_deref().unwrap_or_default(), "");
+
+        // Without a name the connection is labelled after its host.
+        assert_eq!(old.label, "old.example.com");
+        assert_eq!(old.port, None);
+        assert_eq!(old.user.as_deref(), Some("u"));
+
+        assert_eq!(imported.skipped.len(), 2);
+        assert!(imported.skipped[0].contains("RDP"));
+        assert!(imported.skipped[1].contains("no server"));
+    }
+}
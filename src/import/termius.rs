@@ -0,0 +1,132 @@
+//! Termius host lists in CSV, with a header naming the columns: `Groups`, `Label`, `Tags`,
+//! `Hostname/IP`, `Protocol`, `Port`, `Username` and `Password`.
+
+use super::{connection, csv_records, group, non_empty, Imported};
+use crate::model::model::Protocol;
+
+pub(super) fn parse(text: &str, imported: &mut Imported) {
+    let mut records = csv_records(text).into_iter();
+    let Some(header) = records.next() else {
+        return;
+    };
+    // Termius' own column name first, then names other tools use.
+    let column = |names: &[&str]| {
+        header
+            .iter()
+            .position(|title| names.contains(&title.trim().to_ascii_lowercase().as_str()))
+    };
+    let Some(host_column) = column(&["hostname/ip", "hostname", "host", "address", "ip"]) else {
+        imported
+            .skipped
+            .push("Termius CSV: no Hostname/IP column".to_string());
+        return;
+    };
+    let groups = column(&["groups", "group", "folder"]);
+    let label = column(&["label", "name", "alias"]);
+    let tags = column(&["tags"]);
+    let protocol = column(&["protocol"]);
+    let port = column(&["port"]);
+    let user = column(&["username", "user"]);
+    let password = column(&["password"]);
+
+    for (line, record) in records.enumerate() {
+        let field = |column: Option<usize>| {
+            column
+                .and_then(|i| record.get(i))
+                .map_or("", |value| value.trim())
+        };
+        let host = field(Some(host_column));
+        if host.is_empty() {
+            imported
+                .skipped
+                .push(format!("Termius CSV row {}: no host", line + 2));
+            continue;
+        }
+        let protocol = match field(protocol).to_ascii_lowercase().as_str() {
+            "" | "ssh" => None,
+            "mosh" => Some(Protocol::Mosh),
+            "telnet" => Some(Protocol::Telnet),
+            other => {
+                imported
+                    .skipped
+                    .push(format!("Termius host {}: {} isn't supported", host, other));
+                continue;
+            }
+        };
+        let label = match field(label) {
+            "" => host,
+            label => label,
+        };
+
+        let mut connection = connection(label, host, non_empty(field(port)), field(user));
+        connection.group = group(field(groups));
+        connection.protocol = protocol;
+        connection.password = Some(field(password).to_string());
+        connection.tags = field(tags)
+            .split([',', ' '])
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_string)
+            .collect();
+        connection.details = Some("Imported from Termius".to_string());
+        imported.connections.push(connection);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hosts() {
+        let text = "\
+Groups,Label,Tags,Hostname/IP,Protocol,Port,Username,Password
+prod/web,\"Web, primary\",\"a,b\",web.example.com,ssh,2222,deploy,\"p\"\"w\"
+,,,,ssh,,,
+,console,,s.example.com,serial,,,
+,,,db.example.com,MOSH,,,
+";
+        let mut imported = Imported::default();
+        parse(text, &mut imported);
+
+        let [web, db] = imported.connections.as_slice() else {
+            panic!("expected two connections");
+        };
+        assert_eq!(web.label, "Web, primary");
+        assert_eq!(web.group.as_deref(), Some("prod/web"));
+        assert_eq!(web.tags, ["a", "b"]);
+        assert_eq!(web.port.as_deref(), Some("2222"));
+        assert_eq!(web.password.as_deref(), Some("p\"w"));
+
+        assert_eq!(db.label, "db.example.com");
+        assert_eq!(db.protocol, Some(Protocol::Mosh));
+        assert_eq!(db.port, None);
+
+        assert_eq!(imported.skipped.len(), 2);
+        assert!(imported.skipped[0].contains("row 3"));
+        assert!(imported.skipped[1].contains("serial"));
+    }
+
+    #[test]
+    fn reads_other_column_names() {
+        let mut imported = Imported::default();
+        parse(
+            "Name,Host,User\nweb,web.example.com,deploy\n",
+            &mut imported,
+        );
+        assert_eq!(imported.connections.len(), 1);
+        assert_eq!(imported.connections[0].label, "web");
+        assert_eq!(imported.connections[0].user.as_deref(), Some("deploy"));
+    }
+
+    #[test]
+    fn needs_a_host_column() {
+        let mut imported = Imported::default();
+        parse("Label,Port\nweb,22\n", &mut imported);
+        assert!(imported.connections.is_empty());
+        assert_eq!(imported.skipped, ["Termius CSV: no Hostname/IP column"]);
+
+        let mut imported = Imported::default();
+        parse("", &mut imported);
+        assert!(imported.connections.is_empty() && imported.skipped.is_empty());
+    }
+}
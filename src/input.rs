@@ -0,0 +1,225 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Cursor editing of a single line of text. The text itself stays with its owner, so one editor
+/// serves whichever field of a form is focused.
+#[derive(Default)]
+pub(crate) struct LineEditor {
+    /// Cursor position in characters.
+    cursor: usize,
+}
+
+impl LineEditor {
+    /// Puts the cursor at the end of `value`, e.g. when a field gets the focus.
+    pub(crate) fn reset(&mut self, value: &str) {
+        self.cursor = value.chars().count();
+    }
+
+    pub(crate) fn cursor(&self, value: &str) -> usize {
+        self.cursor.min(value.chars().count())
+    }
+
+    /// Applies an editing key to `value`. Returns false for keys that aren't line editing, so
+    /// the caller can use them, e.g. Tab or Enter.
+    pub(crate) fn handle_key(&mut self, value: &mut String, key: KeyEvent) -> bool {
+        let len = value.chars().count();
+        self.cursor = self.cursor.min(len);
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Left if ctrl => self.cursor = previous_word(value, self.cursor),
+            KeyCode::Right if ctrl => self.cursor = next_word(value, self.cursor),
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(len),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = len,
+            KeyCode::Char('a') if ctrl => self.cursor = 0,
+            KeyCode::Char('e') if ctrl => self.cursor = len,
+            KeyCode::Char('u') if ctrl => {
+                self.remove(value, 0, self.cursor);
+                self.cursor = 0;
+            }
+            KeyCode::Char('k') if ctrl => self.remove(value, self.cursor, len),
+            KeyCode::Char('w') if ctrl => {
+                let start = previous_word(value, self.cursor);
+                self.remove(value, start, self.cursor);
+                self.cursor = start;
+            }
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.remove(value, self.cursor - 1, self.cursor);
+                self.cursor -= 1;
+            }
+            KeyCode::Delete if self.cursor < len => self.remove(value, self.cursor, self.cursor + 1),
+            KeyCode::Backspace | KeyCode::Delete => {}
+            KeyCode::Char(c) if !ctrl => self.insert(value, &c.to_string()),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Inserts pasted text at the cursor. Line breaks become spaces since fields are one line.
+    pub(crate) fn paste(&mut self, value: &mut String, text: &str) {
+        let text: String = text
+            .trim_end_matches(['\r', '\n'])
+            .chars()
+            .map(|c| if c == '\r' || c == '\n' { ' ' } else { c })
+            .collect();
+        self.insert(value, &text);
+    }
+
+    fn insert(&mut self, value: &mut String, text: &str) {
+        let at = byte_index(value, self.cursor);
+        value.insert_str(at, text);
+        self.cursor += text.chars().count();
+    }
+
+    fn remove(&self, value: &mut String, start: usize, end: usize) {
+        let range = byte_index(value, start)..byte_index(value, end);
+        value.replace_range(range, "");
+    }
+}
+
+fn byte_index(value: &str, chars: usize) -> usize {
+    value
+        .char_indices()
+        .nth(chars)
+        .map_or(value.len(), |(i, _)| i)
+}
+
+/// Start of the word before `cursor`.
+fn previous_word(value: &str, cursor: usize) -> usize {
+    let chars: Vec<char> = value.chars().collect();
+    let mut i = cursor;
+    while i > 0 && chars[i - 1].is_whitespace() {
+        i -= 1;
+    }
+    while i > 0 && !chars[i - 1].is_whitespace() {
+        i -= 1;
+    }
+    i
+}
+
+/// End of the word after `cursor`.
+fn next_word(value: &str, cursor: usize) -> usize {
+    let chars: Vec<char> = value.chars().collect();
+    let mut i = cursor;
+    while i < chars.len() && chars[i].is_whitespace() {
+        i += 1;
+    }
+    while i < chars.len() && !chars[i].is_whitespace() {
+        i += 1;
+    }
+    i
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(editor: &mut LineEditor, value: &mut String, code: KeyCode) -> bool {
+        editor.handle_key(value, KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    fn ctrl(editor: &mut LineEditor, value: &mut String, code: KeyCode) -> bool {
+        editor.handle_key(value, KeyEvent::new(code, KeyModifiers::CONTROL))
+    }
+
+    fn type_text(editor: &mut LineEditor, value: &mut String, text: &str) {
+        for c in text.chars() {
+            assert!(key(editor, value, KeyCode::Char(c)));
+        }
+    }
+
+    #[test]
+    fn edits_multi_byte_text() {
+        let (mut editor, mut value) = (LineEditor::default(), String::new());
+        type_text(&mut editor, &mut value, "hé");
+        assert_eq!(editor.cursor(&value), 2);
+
+        key(&mut editor, &mut value, KeyCode::Left);
+        type_text(&mut editor, &mut value, "日本");
+        assert_eq!(value, "h日本é");
+        assert_eq!(editor.cursor(&value), 3);
+
+        key(&mut editor, &mut value, KeyCode::Backspace);
+        assert_eq!(value, "h日é");
+        key(&mut editor, &mut value, KeyCode::Delete);
+        assert_eq!(value, "h日");
+        // Nothing to delete at the end, but the key is still taken.
+        assert!(key(&mut editor, &mut value, KeyCode::Delete));
+        assert_eq!(value, "h日");
+
+        key(&mut editor, &mut value, KeyCode::Home);
+        key(&mut editor, &mut value, KeyCode::Backspace);
+        key(&mut editor, &mut value, KeyCode::Left);
+        assert_eq!(editor.cursor(&value), 0);
+        key(&mut editor, &mut value, KeyCode::End);
+        key(&mut editor, &mut value, KeyCode::Right);
+        assert_eq!(editor.cursor(&value), 2);
+    }
+
+    #[test]
+    fn moves_and_deletes_by_word() {
+        let mut value = "ssh  -p 22 höst".to_string();
+        let mut editor = LineEditor::default();
+        editor.reset(&value);
+        assert_eq!(editor.cursor(&value), 15);
+
+        ctrl(&mut editor, &mut value, KeyCode::Left);
+        assert_eq!(editor.cursor(&value), 11);
+        ctrl(&mut editor, &mut value, KeyCode::Left);
+        ctrl(&mut editor, &mut value, KeyCode::Left);
+        assert_eq!(editor.cursor(&value), 5);
+        ctrl(&mut editor, &mut value, KeyCode::Right);
+        assert_eq!(editor.cursor(&value), 7);
+
+        ctrl(&mut editor, &mut value, KeyCode::Char('w'));
+        assert_eq!(value, "ssh   22 höst");
+        assert_eq!(editor.cursor(&value), 5);
+        ctrl(&mut editor, &mut value, KeyCode::Char('k'));
+        assert_eq!(value, "ssh  ");
+        ctrl(&mut editor, &mut value, KeyCode::Char('u'));
+        assert_eq!(value, "");
+    }
+
+    #[test]
+    fn jumps_to_the_ends() {
+        let mut value = "émoji 😀".to_string();
+        let mut editor = LineEditor::default();
+        editor.reset(&value);
+        ctrl(&mut editor, &mut value, KeyCode::Char('a'));
+        assert_eq!(editor.cursor(&value), 0);
+        ctrl(&mut editor, &mut value, KeyCode::Char('e'));
+        assert_eq!(editor.cursor(&value), 7);
+        key(&mut editor, &mut value, KeyCode::Backspace);
+        assert_eq!(value, "émoji ");
+    }
+
+    #[test]
+    fn leaves_other_keys_to_the_caller() {
+        let (mut editor, mut value) = (LineEditor::default(), "x".to_string());
+        assert!(!key(&mut editor, &mut value, KeyCode::Tab));
+        assert!(!key(&mut editor, &mut value, KeyCode::Enter));
+        assert!(!ctrl(&mut editor, &mut value, KeyCode::Char('x')));
+        assert_eq!(value, "x");
+    }
+
+    #[test]
+    fn pastes_one_line() {
+        let mut value = "ab".to_string();
+        let mut editor = LineEditor::default();
+        editor.reset(&value);
+        key(&mut editor, &mut value, KeyCode::Left);
+        editor.paste(&mut value, "1\r\n2\né\n");
+        assert_eq!(value, "a1  2 éb");
+        assert_eq!(editor.cursor(&value), 7);
+    }
+
+    #[test]
+    fn clamps_the_cursor_to_a_shorter_value() {
+        let mut editor = LineEditor::default();
+        editor.reset("longer");
+        let mut value = "ab".to_string();
+        assert_eq!(editor.cursor(&value), 2);
+        type_text(&mut editor, &mut value, "ç");
+        assert_eq!(value, "abç");
+    }
+}
@@ -0,0 +1,240 @@
+//! The key setup screen: moves a connection from its stored password to a new ed25519 key by
+//! generating the key, installing it on the host like `ssh-copy-id`, checking that it logs in,
+//! and then letting the user drop the password.
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+
+use crate::logging::{Action, Pending};
+use crate::model::model::{ConnectionItem, SshBackend};
+use crate::remote::{OutputLine, RemoteCommand, RunStatus};
+use crate::utils::expand_home;
+
+/// How long installing or verifying the key may take.
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Copy, Clone, PartialEq, PartialOrd)]
+pub(crate) enum Step {
+    Generate,
+    Install,
+    Verify,
+    Switch,
+    Done,
+}
+
+impl Step {
+    /// The steps shown on the screen, in order.
+    pub(crate) const ALL: [Step; 4] = [Step::Generate, Step::Install, Step::Verify, Step::Switch];
+
+    pub(crate) fn title(self) -> &'static str {
+        match self {
+            Step::Generate => "Generate an ed25519 key pair",
+            Step::Install => "Install the public key with the stored password",
+            Step::Verify => "Log in with the key only",
+            Step::Switch => "Clear the stored password and use the key",
+            Step::Done => "Done",
+        }
+    }
+}
+
+/// State of the key setup screen for one connection.
+pub(crate) struct KeySetup {
+    pub(crate) item: ConnectionItem,
+    /// Private key file, edited while generating is the next step.
+    pub(crate) key_path: String,
+    pub(crate) step: Step,
+    /// ssh-keygen or ssh process of the running step.
+    pub(crate) job: Option<RemoteCommand>,
+    /// What the steps did, newest last.
+    pub(crate) log: Vec<String>,
+    /// Audit record of the running install.
+    pub(crate) audit: Option<Pending>,
+}
+
+impl KeySetup {
+    pub(crate) fn new(item: &ConnectionItem) -> Self {
+        Self {
+            item: item.clone(),
+            key_path: default_key_path(item),
+            step: Step::Generate,
+            job: None,
+            log: Vec::new(),
+            audit: None,
+        }
+    }
+
+    pub(crate) fn is_running(&self) -> bool {
+        self.job.is_some()
+    }
+
+    /// Starts the current step in the background. Switching is done by the app, which owns the
+    /// connection file.
+    pub(crate) fn run_step(&mut self, backend: SshBackend) {
+        if self.is_running() {
+            return;
+        }
+        match self.step {
+            Step::Generate => self.generate(),
+            Step::Install => self.install(backend),
+            Step::Verify => self.verify(),
+            Step::Switch | Step::Done => {}
+        }
+    }
+
+    fn generate(&mut self) {
+        let path = expand_home(&self.key_path);
+        let public = public_key_path(&self.key_path);
+        if public.exists() {
+            self.log
+                .push(format!("Using the existing key {}", self.key_path));
+            self.step = Step::Install;
+            return;
+        }
+        if path.exists() {
+            self.log.push(format!(
+                "{} exists without {}, pick another file",
+                self.key_path,
+                public.display()
+            ));
+            return;
+        }
+        if let Some(dir) = path.parent() {
+            if let Err(e) = fs::create_dir_all(dir) {
+                self.log
+                    .push(format!("Can't create {}: {}", dir.display(), e));
+                return;
+            }
+        }
+        let mut process = Command::new("ssh-keygen");
+        process
+            .args(["-q", "-t", "ed25519", "-N", "", "-f"])
+            .arg(&path);
+        self.log.push(format!("Generating {}...", self.key_path));
+        self.job = Some(RemoteCommand::spawn(
+            &self.item.label,
+            "ssh-keygen",
+            process,
+            Some(TIMEOUT),
+        ));
+    }
+
+    fn install(&mut self, backend: SshBackend) {
+        let public = public_key_path(&self.key_path);
+        let key = match fs::read_to_string(&public) {
+            Ok(key) => key.trim().to_string(),
+            Err(e) => {
+                self.log
+                    .push(format!("Can't read {}: {}", public.display(), e));
+                return;
+            }
+        };
+        let script = install_script(&key);
+        self.log
+            .push(format!("Installing the key on {}...", self.item.host));
+        self.audit = Some(Pending::start(
+            Action::Run,
+            (&self.item).into(),
+            script.clone(),
+        ));
+        self.job = Some(RemoteCommand::run_on(
+            &self.item,
+            &script,
+            backend,
+            Some(TIMEOUT),
+        ));
+    }
+
+    /// Logs in with nothing but the new key. The ssh binary is used whatever the backend, as
+    /// only it can be kept from falling back to other keys.
+    fn verify(&mut self) {
+        let mut item = self.item.clone();
+        item.password.clear();
+        item.identity_file = self.key_path.clone();
+        item.ssh_options.extend([
+            ("IdentitiesOnly".to_string(), "yes".to_string()),
+            ("PasswordAuthentication".to_string(), "no".to_string()),
+        ]);
+        self.log
+            .push(format!("Logging in to {} with the key...", self.item.host));
+        self.job = Some(RemoteCommand::run_on(
+            &item,
+            "true",
+            SshBackend::OpenSsh,
+            Some(TIMEOUT),
+        ));
+    }
+
+    /// Moves on once the running step has succeeded, or logs why it failed.
+    pub(crate) fn poll(&mut self) {
+        let Some(job) = self.job.as_mut() else {
+            return;
+        };
+        job.poll();
+        if job.is_running() {
+            return;
+        }
+        let job = self.job.take().unwrap();
+        let succeeded = matches!(job.status, RunStatus::Exited(0));
+        if let Some(audit) = self.audit.take() {
+            audit.finish(&job.status_text());
+        }
+        if !succeeded {
+            let error = job.lines.iter().rev().find_map(|line| match line {
+                OutputLine::Stderr(text) => Some(text.clone()),
+                OutputLine::Stdout(_) => None,
+            });
+            self.log.push(format!(
+                "{} failed: {}",
+                self.step.title(),
+                error.unwrap_or_else(|| job.status_text())
+            ));
+            return;
+        }
+        let (message, next) = match self.step {
+            Step::Generate => (format!("Generated {}", self.key_path), Step::Install),
+            Step::Install => ("Installed the public key".to_string(), Step::Verify),
+            _ => ("Logged in with the key".to_string(), Step::Switch),
+        };
+        self.log.push(message);
+        self.step = next;
+    }
+}
+
+/// `~/.ssh/id_ed25519_<label>`, with characters other than letters, digits, `-`, `_` and `.`
+/// of the label replaced.
+fn default_key_path(item: &ConnectionItem) -> String {
+    let name: String = item
+        .label
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("~/.ssh/id_ed25519_{}", name)
+}
+
+/// The `.pub` file next to the private key, where ssh-keygen puts it.
+fn public_key_path(key_path: &str) -> PathBuf {
+    let mut path = expand_home(key_path).into_os_string();
+    path.push(".pub");
+    path.into()
+}
+
+/// Appends `key` to `~/.ssh/authorized_keys` unless it is there, creating the directory and the
+/// file with the permissions sshd requires, like `ssh-copy-id`.
+fn install_script(key: &str) -> String {
+    let key = shell_words::quote(key);
+    format!(
+        "umask 077; mkdir -p ~/.ssh && touch ~/.ssh/authorized_keys && \
+         {{ grep -qxF {key} ~/.ssh/authorized_keys || {{ \
+         [ -z \"$(tail -c 1 ~/.ssh/authorized_keys)\" ] || echo >> ~/.ssh/authorized_keys; \
+         printf '%s\\n' {key} >> ~/.ssh/authorized_keys; }}; }}",
+        key = key
+    )
+}
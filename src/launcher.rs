@@ -0,0 +1,66 @@
+use std::io;
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use crate::model::model::{Config, ConnectionItem};
+use crate::protocol;
+
+/// Used when no `terminal_launcher` is configured.
+const DEFAULT_TEMPLATE: &str = "x-terminal-emulator -e {cmd}";
+
+/// Opens an ssh session to `item` in a new terminal emulator window built from `template`,
+/// e.g. `alacritty -e {cmd}` or `kitty --title {label} {cmd}`, the `terminal_launcher` of `cfg`.
+pub(crate) fn open(item: &ConnectionItem, cfg: &Config) -> io::Result<()> {
+    let argv = command_line(item, cfg)?;
+    let (program, args) = argv.split_first().expect("checked by command_line");
+
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        // Keep the window alive when the manager exits or gets Ctrl-C.
+        .process_group(0)
+        .spawn()?;
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// The command [`open`] runs to start the terminal emulator.
+pub(crate) fn command_line(item: &ConnectionItem, cfg: &Config) -> io::Result<Vec<String>> {
+    let ssh = protocol::handoff_command_line(item, cfg)?;
+    let argv = expand(&cfg.terminal_launcher, &item.label, &ssh)?;
+    if argv.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "terminal_launcher is empty",
+        ));
+    }
+    Ok(argv)
+}
+
+/// Splits the template like a shell would and fills in the placeholders. A `{cmd}` word becomes
+/// the ssh arguments; inside a longer word it is replaced by the shell-quoted command line.
+fn expand(template: &str, label: &str, ssh: &[String]) -> io::Result<Vec<String>> {
+    let template = if template.trim().is_empty() {
+        DEFAULT_TEMPLATE
+    } else {
+        template
+    };
+    let words =
+        shell_words::split(template).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    let mut argv = Vec::new();
+    for word in words {
+        if word == "{cmd}" {
+            argv.extend(ssh.iter().cloned());
+        } else {
+            argv.push(
+                word.replace("{cmd}", &shell_words::join(ssh))
+                    .replace("{label}", label),
+            );
+        }
+    }
+    Ok(argv)
+}
@@ -0,0 +1,232 @@
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::model::model::{Config, ConnectionItem, StoredConnection};
+
+/// Path of the debug log, taking precedence over `debug_log` in the config.
+pub(crate) const DEBUG_LOG_ENV: &str = "SSH_MANAGER_DEBUG_LOG";
+
+static AUDIT_LOG: OnceLock<Mutex<File>> = OnceLock::new();
+static DEBUG_LOG: OnceLock<Mutex<File>> = OnceLock::new();
+
+/// Opens the audit and debug logs set in the config or the environment. Without them, the
+/// logging functions do nothing.
+pub(crate) fn init(cfg: &Config) -> io::Result<()> {
+    if !cfg.audit_log.is_empty() {
+        let _ = AUDIT_LOG.set(Mutex::new(open(&cfg.audit_log)?));
+    }
+    let debug_log = env::var(DEBUG_LOG_ENV).unwrap_or_else(|_| cfg.debug_log.clone());
+    if !debug_log.is_empty() {
+        let _ = DEBUG_LOG.set(Mutex::new(open(&debug_log)?));
+    }
+    Ok(())
+}
+
+fn open(path: &str) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// What an audit record is about.
+#[derive(Copy, Clone, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Action {
+    Connect,
+    Run,
+    Mount,
+    Unmount,
+    Transfer,
+    Add,
+    Edit,
+    Delete,
+    Wake,
+}
+
+/// The connection an audit record is about.
+#[derive(Clone, Debug, Default, Serialize)]
+pub(crate) struct Target {
+    pub(crate) label: String,
+    pub(crate) host: String,
+    pub(crate) port: String,
+    pub(crate) user: String,
+}
+
+impl From<&ConnectionItem> for Target {
+    fn from(item: &ConnectionItem) -> Self {
+        Self {
+            label: item.label.clone(),
+            host: item.host.clone(),
+            port: item.port.clone(),
+            user: item.user.clone(),
+        }
+    }
+}
+
+impl From<&StoredConnection> for Target {
+    fn from(connection: &StoredConnection) -> Self {
+        Self {
+            label: connection.label.clone(),
+            host: connection.host.clone(),
+            port: connection.port.clone().unwrap_or_default(),
+            user: connection.user.clone().unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+    time: String,
+    /// The local user running the manager.
+    user: String,
+    action: Action,
+    target: &'a Target,
+    /// The command line run, with the password masked. Empty for changes to the connection file.
+    command: &'a str,
+    outcome: &'a str,
+}
+
+/// Appends a JSON line to the audit log. `command` must already have its secrets masked.
+pub(crate) fn audit(action: Action, target: &Target, command: &str, outcome: &str) {
+    let Some(file) = AUDIT_LOG.get() else {
+        return;
+    };
+    let record = Record {
+        time: timestamp(SystemTime::now()),
+        user: local_user(),
+        action,
+        target,
+        command,
+        outcome,
+    };
+    let Ok(line) = serde_json::to_string(&record) else {
+        return;
+    };
+    if let Ok(mut file) = file.lock() {
+        // One write per record, so lines of concurrent managers don't interleave.
+        if let Err(e) = file.write_all(format!("{}\n", line).as_bytes()) {
+            debug(&format!("Failed to write the audit log: {}", e));
+        }
+    }
+}
+
+/// An action that runs in the background, audited when it starts and again with its outcome.
+/// Dropped before it finished, e.g. when its dialog is closed, it is audited as cancelled.
+pub(crate) struct Pending {
+    action: Action,
+    target: Target,
+    command: String,
+    finished: bool,
+}
+
+impl Pending {
+    pub(crate) fn start(action: Action, target: Target, command: String) -> Self {
+        audit(action, &target, &command, "started");
+        Self {
+            action,
+            target,
+            command,
+            finished: false,
+        }
+    }
+
+    pub(crate) fn finish(mut self, outcome: &str) {
+        self.record(outcome);
+    }
+
+    fn record(&mut self, outcome: &str) {
+        if !self.finished {
+            self.finished = true;
+            audit(self.action, &self.target, &self.command, outcome);
+        }
+    }
+}
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        self.record("cancelled");
+    }
+}
+
+/// Appends a timestamped line to the debug log.
+pub(crate) fn debug(message: &str) {
+    if let Some(Ok(mut file)) = DEBUG_LOG.get().map(Mutex::lock) {
+        let _ = writeln!(file, "{} {}", timestamp(SystemTime::now()), message);
+    }
+}
+
+fn local_user() -> String {
+    env::var("USER")
+        .or_else(|_| env::var("LOGNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// `time` as RFC 3339 in UTC, e.g. `2024-05-01T12:30:00Z`.
+pub(crate) fn timestamp(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, secs) = (secs / 86400, secs % 86400);
+    // Civil date from days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use super::*;
+
+    /// The audit log of the test process, which every test shares since it is set only once.
+    fn audit_log() -> PathBuf {
+        let path = env::temp_dir().join(format!("ssh-manager-audit-{}.log", std::process::id()));
+        AUDIT_LOG.get_or_init(|| Mutex::new(open(path.to_str().unwrap()).unwrap()));
+        path
+    }
+
+    /// The outcomes audited for `command`, in order.
+    fn outcomes(command: &str) -> Vec<String> {
+        fs::read_to_string(audit_log())
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .filter(|record| record["command"] == command)
+            .map(|record| record["outcome"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn audits_the_outcome() {
+        audit_log();
+        let pending = Pending::start(Action::Transfer, Target::default(), "finished".to_string());
+        pending.finish("ok");
+        assert_eq!(outcomes("finished"), ["started", "ok"]);
+    }
+
+    #[test]
+    fn audits_a_dropped_action_as_cancelled() {
+        audit_log();
+        let pending = Pending::start(Action::Run, Target::default(), "dropped".to_string());
+        drop(pending);
+        assert_eq!(outcomes("dropped"), ["started", "cancelled"]);
+    }
+}
@@ -1,20 +1,55 @@
 use std::env;
+use std::path::Path;
 use std::{error::Error, fs};
 
 use crate::app::App;
-use crate::model::model::{Config, StoredConnection};
+use crate::model::model::{Config, StatefulList, StoredConnection};
 use crate::terminal::{init_error_hooks, init_terminal, restore_terminal};
 
 mod app;
+mod askpass;
+mod broadcast;
+mod export;
+mod import;
+mod input;
+mod keys;
+mod launcher;
+mod logging;
 mod model;
+mod mounts;
+#[cfg(feature = "native-ssh")]
+mod native;
+mod preview;
+mod probe;
+mod protocol;
+mod pty;
+mod record;
+mod remote;
+mod session;
+mod ssh;
+mod status;
+mod templates;
 mod terminal;
+mod tmux;
+mod transfer;
 mod ui;
 mod utils;
+mod wake;
 
 fn main() -> Result<(), Box<dyn Error>> {
+    if askpass::is_requested() {
+        return askpass::run();
+    }
+    if record::is_requested() {
+        return record::run();
+    }
+
     let args: Vec<String> = env::args().collect();
+    let dry_run = args.iter().any(|arg| arg == "--dry-run");
     let config_path = args
-        .get(1)
+        .iter()
+        .skip(1)
+        .find(|arg| !arg.starts_with("--"))
         .map(|s| s.as_str())
         .unwrap_or("/home/zybc/Code/ssh-manager-rust/config.toml");
 
@@ -23,15 +58,66 @@ fn main() -> Result<(), Box<dyn Error>> {
     let file = fs::read_to_string(&cfg.path_to_data_json)?;
 
     let mut connections: Vec<StoredConnection> = serde_json::from_str(&file).unwrap();
+    // entries from before ids existed keep the ones they get now
+    if utils::assign_missing_ids(&mut connections) && !dry_run {
+        utils::write_json_to_file(&connections, &cfg.path_to_data_json)?;
+    }
     // sort by label
     connections.sort_by_key(|conn| conn.label.clone());
 
+    // write the connections as an Ansible inventory instead of starting the TUI
+    if let Some(path) = args
+        .iter()
+        .find_map(|arg| arg.strip_prefix("--export-ansible="))
+    {
+        let (count, skipped) =
+            export::ansible_inventory(&connections, &cfg.defaults, Path::new(path))?;
+        for reason in skipped {
+            eprintln!("skipped {}", reason);
+        }
+        println!("wrote {} hosts to {}", count, path);
+        return Ok(());
+    }
+
+    // write the connections as CSV or a Markdown table instead of starting the TUI
+    if let Some(path) = args.iter().find_map(|arg| arg.strip_prefix("--export=")) {
+        let columns: Vec<String> = match args.iter().find_map(|arg| arg.strip_prefix("--columns="))
+        {
+            Some(columns) => columns
+                .split(',')
+                .map(|column| column.trim().to_string())
+                .filter(|column| !column.is_empty())
+                .collect(),
+            None => export::DEFAULT_COLUMNS.map(str::to_string).to_vec(),
+        };
+        let redact = !args.iter().any(|arg| arg == "--show-passwords");
+        export::table(&connections, &columns, redact, Path::new(path))?;
+        println!("wrote {} connections to {}", connections.len(), path);
+        return Ok(());
+    }
+
+    // print what connecting would run instead of starting the TUI
+    if dry_run {
+        preview::print_all(
+            &StatefulList::with_items(connections, &cfg.defaults).items,
+            &cfg,
+        )?;
+        return Ok(());
+    }
+
+    logging::init(&cfg)?;
+    logging::debug(&format!(
+        "started with {} connections from {}",
+        connections.len(),
+        cfg.path_to_data_json
+    ));
+
     // setup terminal
     init_error_hooks()?;
     let terminal = init_terminal()?;
 
     // create app and run it
-    App::new(connections).run(terminal, &cfg)?;
+    App::new(connections, &cfg).run(terminal, &cfg)?;
 
     // restore default terminal
     restore_terminal()?;
@@ -1,45 +1,338 @@
 use crate::ui::style::{ALT_ROW_COLOR, NORMAL_ROW_COLOR, NOT_AVAILABLE_TEXT_COLOR, TEXT_COLOR};
+use crate::utils::new_id;
+use crate::wake;
 use ratatui::prelude::Line;
 use ratatui::style::Stylize;
 use ratatui::widgets::{ListItem, ListState};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt;
 use std::fmt::{Display, Formatter};
 
-#[derive(Default, Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub(crate) struct Config {
     pub(crate) path_to_data_json: String,
+    /// How many hosts a broadcast command runs on at the same time.
+    pub(crate) broadcast_concurrency: usize,
+    /// Seconds after which a broadcast command is killed on a host.
+    pub(crate) broadcast_timeout_secs: u64,
+    pub(crate) launch_mode: LaunchMode,
+    /// Command template for the `external` launch mode, e.g. `alacritty -e {cmd}`.
+    pub(crate) terminal_launcher: String,
+    pub(crate) backend: SshBackend,
+    pub(crate) defaults: Defaults,
+    /// Seconds after which a password copied to the clipboard is cleared again, 0 to keep it.
+    pub(crate) clipboard_clear_secs: u64,
+    /// File the audit trail is appended to as JSON lines, off when empty.
+    pub(crate) audit_log: String,
+    /// File for diagnostics, off when empty. `SSH_MANAGER_DEBUG_LOG` takes precedence.
+    pub(crate) debug_log: String,
+    /// Where sessions of connections with `record` set are saved, `recordings` next to the
+    /// connection file when empty.
+    pub(crate) recordings_dir: String,
+    /// Named connections new entries can start from, e.g. `[templates.web]`.
+    pub(crate) templates: BTreeMap<String, StoredConnection>,
+    /// Seconds connecting to a host with `wake_on_connect` waits for it to come up.
+    pub(crate) wake_timeout_secs: u64,
 }
 
-// impl Default for Config {
-//     fn default() -> Self {
-//         Self { path_to_data_json: "data/".to_string() }
-//     }
-// }
+/// Shown instead of a password unless it is revealed.
+pub(crate) const PASSWORD_MASK: &str = "********";
+
+/// Values every connection inherits unless it sets its own.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct Defaults {
+    /// Remote user; without one ssh uses the local user name.
+    pub(crate) user: Option<String>,
+    /// Port, 22 when not set.
+    pub(crate) port: Option<String>,
+    /// Private key passed to ssh as `IdentityFile`.
+    pub(crate) identity_file: Option<String>,
+    /// Launch mode of connections that don't set one, instead of the top level `launch_mode`.
+    pub(crate) launch_mode: Option<LaunchMode>,
+    /// ssh `-o` options, e.g. `Compression = "yes"`.
+    pub(crate) ssh_options: BTreeMap<String, String>,
+    /// Environment of the local ssh process, e.g. `TERM` or `LC_ALL`.
+    pub(crate) env: BTreeMap<String, String>,
+}
+
+/// Which SSH implementation talks to the hosts.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) enum SshBackend {
+    /// The `ssh`, `sshpass`, `scp` and `sshfs` binaries.
+    #[default]
+    #[serde(rename = "openssh")]
+    OpenSsh,
+    /// The in-process client, available when built with the `native-ssh` feature. Interactive
+    /// sessions always open in an embedded tab.
+    #[serde(rename = "native")]
+    Native,
+}
+
+impl SshBackend {
+    pub(crate) const ALL: [SshBackend; 2] = [SshBackend::OpenSsh, SshBackend::Native];
+
+    /// The name used in the config file.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            SshBackend::OpenSsh => "openssh",
+            SshBackend::Native => "native",
+        }
+    }
+}
+
+/// How connecting to a host runs ssh.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum LaunchMode {
+    /// Leave the TUI and run ssh in the current terminal.
+    #[default]
+    Inline,
+    /// Run ssh in a terminal tab inside the TUI.
+    Embedded,
+    /// Open ssh in a new tmux window named after the label.
+    TmuxWindow,
+    /// Split the current tmux window and run ssh in the new pane.
+    TmuxPane,
+    /// Start a tmux session named after the label and switch to it.
+    TmuxSession,
+    /// Open ssh in a new window of the configured terminal emulator.
+    External,
+}
+
+impl LaunchMode {
+    pub(crate) const ALL: [LaunchMode; 6] = [
+        LaunchMode::Inline,
+        LaunchMode::Embedded,
+        LaunchMode::TmuxWindow,
+        LaunchMode::TmuxPane,
+        LaunchMode::TmuxSession,
+        LaunchMode::External,
+    ];
+
+    /// The name used in the config file.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            LaunchMode::Inline => "inline",
+            LaunchMode::Embedded => "embedded",
+            LaunchMode::TmuxWindow => "tmux_window",
+            LaunchMode::TmuxPane => "tmux_pane",
+            LaunchMode::TmuxSession => "tmux_session",
+            LaunchMode::External => "external",
+        }
+    }
+}
+
+/// Program used to connect to a host interactively.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Protocol {
+    #[default]
+    Ssh,
+    Mosh,
+    Telnet,
+}
+
+impl Protocol {
+    pub(crate) const ALL: [Protocol; 3] = [Protocol::Ssh, Protocol::Mosh, Protocol::Telnet];
+
+    /// The name used in the connection file.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Protocol::Ssh => "ssh",
+            Protocol::Mosh => "mosh",
+            Protocol::Telnet => "telnet",
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            path_to_data_json: String::new(),
+            broadcast_concurrency: 8,
+            broadcast_timeout_secs: 30,
+            launch_mode: LaunchMode::default(),
+            terminal_launcher: String::new(),
+            backend: SshBackend::default(),
+            defaults: Defaults::default(),
+            clipboard_clear_secs: 30,
+            templates: BTreeMap::new(),
+            recordings_dir: String::new(),
+            audit_log: String::new(),
+            debug_log: String::new(),
+            wake_timeout_secs: 120,
+        }
+    }
+}
 
 #[derive(Clone, Serialize, Deserialize)]
 pub(crate) struct ConnectionItem {
+    pub(crate) id: String,
     pub(crate) label: String,
+    /// Folder the connection is filed under, e.g. `prod/web`, empty for none.
+    pub(crate) group: String,
+    /// Other groups the connection belongs to.
+    pub(crate) tags: Vec<String>,
     pub(crate) host: String,
     pub(crate) port: String,
     pub(crate) user: String,
     pub(crate) password: String,
     pub(crate) details: String,
+    pub(crate) sshfs_remote_path: String,
+    pub(crate) sshfs_mount_point: String,
+    pub(crate) sshfs_options: String,
+    /// Private key passed to ssh, if any.
+    pub(crate) identity_file: String,
+    /// Overrides the launch mode from the config.
+    pub(crate) launch_mode: Option<LaunchMode>,
+    /// Overrides the SSH backend from the config.
+    pub(crate) backend: Option<SshBackend>,
+    /// Program used by the connect action, ssh when not set.
+    pub(crate) protocol: Option<Protocol>,
+    /// Replaces the protocol's command when set, e.g. `ssh -t {user}@{host} tmux attach`.
+    pub(crate) command_template: String,
+    /// ssh `-o` options of this connection, on top of the defaults.
+    pub(crate) ssh_options: BTreeMap<String, String>,
+    /// Environment of the local process of this connection, on top of the defaults.
+    pub(crate) env: BTreeMap<String, String>,
+    /// Interactive sessions are recorded in asciicast format.
+    pub(crate) record: bool,
+    /// MAC address for Wake-on-LAN, empty when the host can't be woken.
+    pub(crate) wake_mac: String,
+    /// Where magic packets are sent, `address[:port]`.
+    pub(crate) wake_broadcast: String,
+    /// Connecting wakes the host first and waits until its port answers.
+    pub(crate) wake_on_connect: bool,
+    pub(crate) defaults: Defaults,
+    /// Which of the values above come from the defaults.
+    pub(crate) inherited: Inherited,
     pub(crate) status: Status,
 }
 
+/// Values of a connection that are not stored with it but taken from [`Defaults`].
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub(crate) struct Inherited {
+    pub(crate) user: bool,
+    pub(crate) port: bool,
+    pub(crate) identity_file: bool,
+}
+
 impl ConnectionItem {
-    pub(crate) fn to_list_item(&self, index: usize) -> ListItem {
+    /// Builds the connection from its stored form, filling in missing values from `defaults`.
+    pub(crate) fn from_stored(stored: StoredConnection, defaults: &Defaults) -> Self {
+        let (user, inherit_user) = inherit(stored.user, &defaults.user);
+        let (port, inherit_port) = inherit(stored.port, &defaults.port);
+        let (identity_file, inherit_identity) =
+            inherit(stored.identity_file, &defaults.identity_file);
+        ConnectionItem {
+            id: stored.id,
+            label: stored.label,
+            group: stored.group.unwrap_or_default(),
+            tags: stored.tags,
+            host: stored.host,
+            port: non_empty(port).unwrap_or_else(|| "22".to_string()),
+            user,
+            password: stored.password.unwrap_or_default(),
+            details: stored.details.unwrap_or_default(),
+            sshfs_remote_path: stored.sshfs_remote_path.unwrap_or_default(),
+            sshfs_mount_point: stored.sshfs_mount_point.unwrap_or_default(),
+            sshfs_options: stored.sshfs_options.unwrap_or_default(),
+            identity_file,
+            launch_mode: stored.launch_mode,
+            backend: stored.backend,
+            protocol: stored.protocol,
+            command_template: stored.command_template.unwrap_or_default(),
+            ssh_options: stored.ssh_options,
+            env: stored.env,
+            record: stored.record.unwrap_or(false),
+            wake_mac: stored.wake_mac.unwrap_or_default(),
+            wake_broadcast: stored.wake_broadcast.unwrap_or_default(),
+            wake_on_connect: stored.wake_on_connect.unwrap_or(false),
+            defaults: defaults.clone(),
+            inherited: Inherited {
+                user: inherit_user,
+                port: inherit_port,
+                identity_file: inherit_identity,
+            },
+            status: Status::Available,
+        }
+    }
+
+    /// How an interactive session to this connection is opened: its own launch mode, else the
+    /// one from the defaults, else the top level one. The native backend only exists inside this
+    /// process, so its sessions are always embedded.
+    pub(crate) fn launch_mode(&self, cfg: &Config) -> LaunchMode {
+        match self.session_backend(cfg) {
+            SshBackend::Native => LaunchMode::Embedded,
+            SshBackend::OpenSsh => self
+                .launch_mode
+                .or(self.defaults.launch_mode)
+                .unwrap_or(cfg.launch_mode),
+        }
+    }
+
+    /// Whether the connect action runs plain ssh rather than another protocol or a template.
+    pub(crate) fn is_plain_ssh(&self) -> bool {
+        self.command_template.trim().is_empty()
+            && self.protocol.unwrap_or_default() == Protocol::Ssh
+    }
+
+    /// ssh options in the order they are passed: the defaults, then this connection's own. Keys
+    /// are case insensitive like in ssh, so later values replace earlier ones.
+    pub(crate) fn merged_ssh_options(&self) -> Vec<(String, String)> {
+        let mut merged: Vec<(String, String)> = Vec::new();
+        for (key, value) in self.defaults.ssh_options.iter().chain(&self.ssh_options) {
+            match merged.iter_mut().find(|(k, _)| k.eq_ignore_ascii_case(key)) {
+                Some(existing) => existing.1 = value.clone(),
+                None => merged.push((key.clone(), value.clone())),
+            }
+        }
+        merged
+    }
+
+    /// The environment of the defaults overridden by this connection's own.
+    pub(crate) fn merged_env(&self) -> BTreeMap<String, String> {
+        let mut merged = self.defaults.env.clone();
+        merged.extend(self.env.clone());
+        merged
+    }
+
+    /// The backend of interactive sessions. Other protocols and templates run their own
+    /// programs, so only plain ssh can use the native client.
+    pub(crate) fn session_backend(&self, cfg: &Config) -> SshBackend {
+        if self.is_plain_ssh() {
+            self.backend(cfg)
+        } else {
+            SshBackend::OpenSsh
+        }
+    }
+
+    /// The backend used for this connection. Builds without the `native-ssh` feature always
+    /// use OpenSSH.
+    pub(crate) fn backend(&self, cfg: &Config) -> SshBackend {
+        match self.backend.unwrap_or(cfg.backend) {
+            SshBackend::Native if cfg!(feature = "native-ssh") => SshBackend::Native,
+            _ => SshBackend::OpenSsh,
+        }
+    }
+
+    pub(crate) fn to_list_item(&self, index: usize, marked: bool, mounted: bool) -> ListItem<'_> {
         let bg_color = match index % 2 {
             0 => NORMAL_ROW_COLOR,
             _ => ALT_ROW_COLOR,
         };
+        let mark = if marked { "*" } else { " " };
+        let mounted = if mounted { " [mounted]" } else { "" };
         let line = match self.status {
-            Status::Available => {
-                Line::styled(format!(" > {} {}", self.label, self.host), TEXT_COLOR)
-            }
+            Status::Available => Line::styled(
+                format!("{}> {} {}{}", mark, self.label, self.host, mounted),
+                TEXT_COLOR,
+            ),
             Status::NotAvailable => Line::styled(
-                format!(" X {} {}", self.label, self.host),
+                format!("{}X {} {}{}", mark, self.label, self.host, mounted),
                 (NOT_AVAILABLE_TEXT_COLOR, bg_color),
             ),
         };
@@ -47,15 +340,71 @@ impl ConnectionItem {
         ListItem::new(line).bg(bg_color)
     }
 
-    pub(crate) fn display(&self) -> String {
-        let info = format!(
-            "label: {}\n\
-             host: {}\n\
-             port: {}\n\
-             user: {}\n\
-             details: {}\n",
-            self.label, self.host, self.port, self.user, self.details
+    /// The details shown in the info pane, with the password masked unless `reveal_password`.
+    pub(crate) fn display(&self, reveal_password: bool) -> String {
+        let default = |inherited: bool| if inherited { " (default)" } else { "" };
+        let mut info = format!("label: {}\n", self.label);
+        if !self.group.is_empty() {
+            info += &format!("group: {}\n", self.group);
+        }
+        if !self.tags.is_empty() {
+            info += &format!("tags: {}\n", self.tags.join(" "));
+        }
+        info += &format!(
+            "host: {}\n\
+             port: {}{}\n\
+             user: {}{}\n",
+            self.host,
+            self.port,
+            default(self.inherited.port),
+            self.user,
+            default(self.inherited.user && !self.user.is_empty()),
         );
+        if !self.password.is_empty() {
+            let password = if reveal_password {
+                self.password.as_str()
+            } else {
+                PASSWORD_MASK
+            };
+            info += &format!("password: {}\n", password);
+        }
+        if !self.identity_file.is_empty() {
+            info += &format!(
+                "identity: {}{}\n",
+                self.identity_file,
+                default(self.inherited.identity_file)
+            );
+        }
+        if let Some(mode) = self.launch_mode.or(self.defaults.launch_mode) {
+            info += &format!(
+                "launch mode: {}{}\n",
+                mode.name(),
+                default(self.launch_mode.is_none())
+            );
+        }
+        for (key, value) in self.merged_ssh_options() {
+            let inherited = !self
+                .ssh_options
+                .keys()
+                .any(|k| k.eq_ignore_ascii_case(&key));
+            info += &format!("option: {}={}{}\n", key, value, default(inherited));
+        }
+        for (name, value) in self.merged_env() {
+            let inherited = !self.env.contains_key(&name);
+            info += &format!("env: {}={}{}\n", name, value, default(inherited));
+        }
+        if self.record {
+            info += "sessions are recorded\n";
+        }
+        if !self.wake_mac.is_empty() {
+            let broadcast = non_empty(self.wake_broadcast.clone())
+                .unwrap_or_else(|| wake::DEFAULT_BROADCAST.to_string());
+            info += &format!("wake: {} via {}\n", self.wake_mac, broadcast);
+            if self.wake_on_connect {
+                info += "woken before connecting\n";
+            }
+        }
+        info += &format!("details: {}\n", self.details);
         info
     }
 }
@@ -64,16 +413,74 @@ pub(crate) struct StatefulList {
     pub(crate) state: ListState,
     pub(crate) items: Vec<ConnectionItem>,
     pub(crate) last_selected: Option<usize>,
+    /// Indices of items marked for actions on several hosts.
+    pub(crate) marked: BTreeSet<usize>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 pub(crate) struct StoredConnection {
+    /// Identifies the entry in the file while its other values are edited. Entries stored
+    /// without one get one when the file is loaded.
+    #[serde(default)]
+    pub(crate) id: String,
+    // Templates in the config may leave out the label and host.
+    #[serde(default)]
     pub(crate) label: String,
+    #[serde(default)]
     pub(crate) host: String,
+    /// Folder the connection is filed under, e.g. `prod/web`. Kept from imported sessions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) group: Option<String>,
+    /// Other groups the connection belongs to, e.g. the further groups of an Ansible host.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) tags: Vec<String>,
     pub(crate) port: Option<String>,
     pub(crate) user: Option<String>,
     pub(crate) password: Option<String>,
     pub(crate) details: Option<String>,
+    /// Remote directory mounted by sshfs, `/` when not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) sshfs_remote_path: Option<String>,
+    /// Local mount point, `/tmp/<label>` when not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) sshfs_mount_point: Option<String>,
+    /// Comma separated sshfs `-o` options, e.g. `reconnect,idmap=user,ro`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) sshfs_options: Option<String>,
+    /// Private key passed to ssh, the default identity when not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) identity_file: Option<String>,
+    /// Overrides the launch mode from the config for this connection.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) launch_mode: Option<LaunchMode>,
+    /// Overrides the SSH backend from the config for this connection.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) backend: Option<SshBackend>,
+    /// Program used by the connect action, ssh when not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) protocol: Option<Protocol>,
+    /// Command run by the connect action instead of the protocol's, with `{user}`, `{host}`,
+    /// `{port}` and `{password}` placeholders.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) command_template: Option<String>,
+    /// ssh `-o` options, merged over the defaults from the config.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) ssh_options: BTreeMap<String, String>,
+    /// Environment of the local ssh process, merged over the defaults from the config.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) env: BTreeMap<String, String>,
+    /// Records interactive sessions to the `recordings_dir` from the config.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) record: Option<bool>,
+    /// MAC address magic packets are sent for, e.g. `aa:bb:cc:dd:ee:ff`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) wake_mac: Option<String>,
+    /// Broadcast address and port of the magic packets, `255.255.255.255:9` when not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) wake_broadcast: Option<String>,
+    /// Wakes the host and waits for it before connecting.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) wake_on_connect: Option<bool>,
 }
 
 impl Display for StoredConnection {
@@ -85,21 +492,49 @@ impl Display for StoredConnection {
             self.host,
             self.port.as_deref().unwrap_or("None"),
             self.user.as_deref().unwrap_or("None"),
-            self.password.as_deref().unwrap_or("None"),
+            match self.password.as_deref() {
+                Some(password) if !password.is_empty() => PASSWORD_MASK,
+                _ => "None",
+            },
             self.details.as_deref().unwrap_or("None"),
         )
     }
 }
 
+// Not derived, so the password stays masked in debug output too.
+impl fmt::Debug for StoredConnection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
 impl StoredConnection {
+    /// An empty connection with a new id.
     pub(crate) fn new() -> Self {
         Self {
+            id: new_id(),
             label: String::new(),
             host: String::new(),
-            port: Option::from(String::from("22")),
-            user: Option::from(String::new()),
+            group: None,
+            tags: Vec::new(),
+            port: None,
+            user: None,
             password: Option::from(String::new()),
             details: Option::from(String::new()),
+            sshfs_remote_path: None,
+            sshfs_mount_point: None,
+            sshfs_options: None,
+            identity_file: None,
+            launch_mode: None,
+            backend: None,
+            protocol: None,
+            command_template: None,
+            ssh_options: BTreeMap::new(),
+            env: BTreeMap::new(),
+            record: None,
+            wake_mac: None,
+            wake_broadcast: None,
+            wake_on_connect: None,
         }
     }
 }
@@ -110,29 +545,132 @@ pub(crate) enum Status {
     NotAvailable,
 }
 
-impl From<StoredConnection> for ConnectionItem {
-    fn from(stored: StoredConnection) -> Self {
-        ConnectionItem {
-            label: stored.label,
-            host: stored.host,
-            port: stored.port.unwrap_or_else(|| "22".to_string()),
-            user: stored.user.unwrap_or_else(String::new),
-            password: stored.password.unwrap_or_else(String::new),
-            details: stored.details.unwrap_or_else(String::new),
-            status: Status::Available,
-        }
-    }
-}
-
 impl From<ConnectionItem> for StoredConnection {
     fn from(connection: ConnectionItem) -> Self {
         StoredConnection {
+            id: connection.id,
             label: connection.label,
             host: connection.host,
-            port: Some(connection.port),
-            user: Some(connection.user),
+            group: non_empty(connection.group),
+            tags: connection.tags,
+            port: (!connection.inherited.port).then_some(connection.port),
+            user: (!connection.inherited.user).then_some(connection.user),
             password: Some(connection.password),
             details: Some(connection.details),
+            sshfs_remote_path: non_empty(connection.sshfs_remote_path),
+            sshfs_mount_point: non_empty(connection.sshfs_mount_point),
+            sshfs_options: non_empty(connection.sshfs_options),
+            identity_file: if connection.inherited.identity_file {
+                None
+            } else {
+                non_empty(connection.identity_file)
+            },
+            launch_mode: connection.launch_mode,
+            backend: connection.backend,
+            protocol: connection.protocol,
+            command_template: non_empty(connection.command_template),
+            ssh_options: connection.ssh_options,
+            env: connection.env,
+            record: connection.record.then_some(true),
+            wake_mac: non_empty(connection.wake_mac),
+            wake_broadcast: non_empty(connection.wake_broadcast),
+            wake_on_connect: connection.wake_on_connect.then_some(true),
         }
     }
 }
+
+/// The stored value when it is set, else the default, and whether the value is inherited. A
+/// value that isn't stored is inherited even without a default, so it stays unset when the
+/// connection is saved and a default added later applies to it.
+fn inherit(own: Option<String>, default: &Option<String>) -> (String, bool) {
+    match own.and_then(non_empty) {
+        Some(value) => (value, false),
+        None => (default.clone().unwrap_or_default(), true),
+    }
+}
+
+fn non_empty(value: String) -> Option<String> {
+    if value.is_empty() {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(stored: StoredConnection, defaults: &Defaults) -> StoredConnection {
+        StoredConnection::from(ConnectionItem::from_stored(stored, defaults))
+    }
+
+    #[test]
+    fn keeps_unset_values_unset() {
+        let stored = StoredConnection {
+            label: "web".to_string(),
+            host: "web.example.com".to_string(),
+            ..StoredConnection::new()
+        };
+        let item = ConnectionItem::from_stored(stored.clone(), &Defaults::default());
+        assert_eq!(item.port, "22");
+        assert!(item.inherited.port && item.inherited.user && item.inherited.identity_file);
+
+        let saved = round_trip(stored, &Defaults::default());
+        assert_eq!(saved.port, None);
+        assert_eq!(saved.user, None);
+        assert_eq!(saved.identity_file, None);
+
+        // Defaults set after the connection was saved still apply to it.
+        let defaults = Defaults {
+            user: Some("deploy".to_string()),
+            port: Some("2200".to_string()),
+            identity_file: Some("~/.ssh/work".to_string()),
+            ..Defaults::default()
+        };
+        let item = ConnectionItem::from_stored(saved.clone(), &defaults);
+        assert_eq!(item.port, "2200");
+        assert_eq!(item.user, "deploy");
+        assert_eq!(item.identity_file, "~/.ssh/work");
+        let saved = round_trip(saved, &defaults);
+        assert_eq!(
+            (saved.port, saved.user, saved.identity_file),
+            (None, None, None)
+        );
+    }
+
+    #[test]
+    fn round_trips_stored_values() {
+        let stored = StoredConnection {
+            id: "1".to_string(),
+            label: "web".to_string(),
+            host: "web.example.com".to_string(),
+            group: Some("prod/web".to_string()),
+            tags: vec!["debian".to_string()],
+            port: Some("22".to_string()),
+            user: Some("root".to_string()),
+            password: Some("secret".to_string()),
+            details: Some("notes".to_string()),
+            identity_file: Some("~/.ssh/web".to_string()),
+            protocol: Some(Protocol::Mosh),
+            command_template: Some("ssh {host}".to_string()),
+            ssh_options: BTreeMap::from([("Compression".to_string(), "yes".to_string())]),
+            env: BTreeMap::from([("TERM".to_string(), "xterm".to_string())]),
+            record: Some(true),
+            wake_mac: Some("aa:bb:cc:dd:ee:ff".to_string()),
+            wake_broadcast: Some("192.168.1.255".to_string()),
+            wake_on_connect: Some(true),
+            ..StoredConnection::new()
+        };
+        let defaults = Defaults {
+            user: Some("deploy".to_string()),
+            port: Some("2200".to_string()),
+            ..Defaults::default()
+        };
+        let saved = round_trip(stored.clone(), &defaults);
+        assert_eq!(
+            serde_json::to_value(&saved).unwrap(),
+            serde_json::to_value(&stored).unwrap()
+        );
+    }
+}
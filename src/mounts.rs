@@ -0,0 +1,152 @@
+use std::fs;
+use std::io;
+use std::process::Command;
+
+use ratatui::widgets::ListState;
+
+use crate::logging::Pending;
+use crate::model::model::ConnectionItem;
+use crate::remote::{OutputLine, RemoteCommand, RunStatus};
+use crate::{ssh, utils};
+
+/// An active sshfs mount as listed in `/proc/self/mounts`.
+#[derive(Clone)]
+pub(crate) struct Mount {
+    /// `user@host:/path` as passed to sshfs.
+    pub(crate) source: String,
+    pub(crate) mount_point: String,
+}
+
+impl Mount {
+    pub(crate) fn host(&self) -> &str {
+        let destination = self
+            .source
+            .rsplit_once(':')
+            .map_or(self.source.as_str(), |(destination, _)| destination);
+        destination
+            .rsplit_once('@')
+            .map_or(destination, |(_, host)| host)
+    }
+
+    /// Whether this is the mount of `item`: its mount point with its `user@host:/path`. Other
+    /// connections to the same host have their own mounts.
+    pub(crate) fn matches(&self, item: &ConnectionItem) -> bool {
+        self.mount_point == mount_point(item) && self.source == ssh::sshfs_source(item)
+    }
+}
+
+/// State of the mounts panel.
+pub(crate) struct MountsPanel {
+    pub(crate) state: ListState,
+    /// sshfs process started from the panel or the connection list.
+    pub(crate) job: Option<RemoteCommand>,
+    /// Outcome of the last mount or unmount.
+    pub(crate) message: Option<String>,
+    /// Audit record of the running sshfs process.
+    pub(crate) audit: Option<Pending>,
+}
+
+impl MountsPanel {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: ListState::default().with_selected(Some(0)),
+            job: None,
+            message: None,
+            audit: None,
+        }
+    }
+
+    /// Polls the running sshfs process and returns true once it has just finished.
+    pub(crate) fn poll(&mut self) -> bool {
+        let Some(job) = self.job.as_mut() else {
+            return false;
+        };
+        job.poll();
+        if job.is_running() {
+            return false;
+        }
+
+        let job = self.job.take().unwrap();
+        let message = match job.status {
+            RunStatus::Exited(0) => format!("Mounted {}", job.label),
+            _ => {
+                let error = job.lines.iter().rev().find_map(|line| match line {
+                    OutputLine::Stderr(text) => Some(text.clone()),
+                    OutputLine::Stdout(_) => None,
+                });
+                format!(
+                    "Mounting {} failed: {}",
+                    job.label,
+                    error.unwrap_or_else(|| job.status_text())
+                )
+            }
+        };
+        if let Some(audit) = self.audit.take() {
+            audit.finish(&message);
+        }
+        self.message = Some(message);
+        true
+    }
+}
+
+/// Where the connection gets mounted: its configured mount point or `/tmp/<label>`.
+pub(crate) fn mount_point(item: &ConnectionItem) -> String {
+    if item.sshfs_mount_point.is_empty() {
+        format!("/tmp/{}", utils::remove_whitespace(&item.label))
+    } else {
+        item.sshfs_mount_point.clone()
+    }
+}
+
+/// Reads the active sshfs mounts of this process' mount namespace.
+pub(crate) fn active_mounts() -> Vec<Mount> {
+    let Ok(content) = fs::read_to_string("/proc/self/mounts") else {
+        return Vec::new();
+    };
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(' ');
+            let source = fields.next()?;
+            let mount_point = fields.next()?;
+            let fs_type = fields.next()?;
+            (fs_type == "fuse.sshfs").then(|| Mount {
+                source: unescape(source),
+                mount_point: unescape(mount_point),
+            })
+        })
+        .collect()
+}
+
+/// Unmounts a FUSE filesystem, falling back to `fusermount3` where only FUSE 3 is installed.
+pub(crate) fn unmount(mount_point: &str) -> Result<(), String> {
+    for program in ["fusermount", "fusermount3"] {
+        match Command::new(program).arg("-u").arg(mount_point).output() {
+            Ok(output) if output.status.success() => return Ok(()),
+            Ok(output) => return Err(String::from_utf8_lossy(&output.stderr).trim().to_string()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+    Err("fusermount is not installed".to_string())
+}
+
+/// Decodes the octal escapes (`\040` for a space) used in `/proc/self/mounts`.
+fn unescape(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && i + 4 <= bytes.len() {
+            let digits = std::str::from_utf8(&bytes[i + 1..i + 4]).unwrap_or_default();
+            if let Ok(byte) = u8::from_str_radix(digits, 8) {
+                decoded.push(byte);
+                i += 4;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
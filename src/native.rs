@@ -0,0 +1,532 @@
+//! In-process SSH client built on libssh2, used instead of the `ssh`, `sshpass` and `scp`
+//! binaries for connections using the native backend.
+
+use std::fs;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use ssh2::{Channel, Session, Sftp};
+
+use crate::model::model::ConnectionItem;
+use crate::remote::{OutputLine, RunStatus};
+use crate::utils::expand_home;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const KEEPALIVE_SECS: u32 = 15;
+/// ssh options applied by [`connect`]. `StrictHostKeyChecking=no` is what the client does
+/// anyway; the other options are reported by [`options_warning`].
+const SUPPORTED_OPTIONS: [&str; 4] = [
+    "Compression",
+    "ConnectTimeout",
+    "ServerAliveInterval",
+    "IdentityFile",
+];
+/// Keys tried in order when no password is stored and the agent has none that work.
+const DEFAULT_KEYS: [&str; 3] = ["id_ed25519", "id_ecdsa", "id_rsa"];
+
+/// The merged ssh option `name` of `item`, if it sets one.
+fn option(item: &ConnectionItem, name: &str) -> Option<String> {
+    item.merged_ssh_options()
+        .into_iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value)
+}
+
+/// Names the ssh options of `item` the native backend can't apply, which it then ignores.
+pub(crate) fn options_warning(item: &ConnectionItem) -> Option<String> {
+    let unsupported: Vec<String> = item
+        .merged_ssh_options()
+        .into_iter()
+        .filter(|(key, value)| {
+            let unchecked = key.eq_ignore_ascii_case("StrictHostKeyChecking")
+                && value.eq_ignore_ascii_case("no");
+            !unchecked
+                && !SUPPORTED_OPTIONS
+                    .iter()
+                    .any(|name| name.eq_ignore_ascii_case(key))
+        })
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect();
+    (!unsupported.is_empty()).then(|| {
+        format!(
+            "The native backend ignores the ssh options {}",
+            unsupported.join(", ")
+        )
+    })
+}
+
+/// Sends the merged environment of `item` before a shell or command starts. Like `SendEnv`,
+/// servers only take the variables their `AcceptEnv` allows and refuse the others.
+fn set_env(channel: &mut Channel, item: &ConnectionItem) {
+    for (name, value) in item.merged_env() {
+        if name != "TERM" {
+            let _ = channel.setenv(&name, &value);
+        }
+    }
+}
+
+/// Opens an authenticated session to `item`. Host keys are not verified, matching the
+/// `StrictHostKeyChecking=no` used for the ssh binary.
+pub(crate) fn connect(item: &ConnectionItem) -> io::Result<Session> {
+    let port: u16 = item
+        .port
+        .parse()
+        .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "invalid port"))?;
+    let address = (item.host.as_str(), port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(ErrorKind::NotFound, "host not found"))?;
+    let timeout = option(item, "ConnectTimeout")
+        .and_then(|secs| secs.parse().ok())
+        .map_or(CONNECT_TIMEOUT, Duration::from_secs);
+    let tcp = TcpStream::connect_timeout(&address, timeout)?;
+
+    let mut session = Session::new()?;
+    session.set_tcp_stream(tcp);
+    session.set_compress(option(item, "Compression").is_some_and(|value| value == "yes"));
+    session.handshake()?;
+    let keepalive = option(item, "ServerAliveInterval")
+        .and_then(|secs| secs.parse().ok())
+        .unwrap_or(KEEPALIVE_SECS);
+    session.set_keepalive(false, keepalive);
+    authenticate(&session, item)?;
+    Ok(session)
+}
+
+fn authenticate(session: &Session, item: &ConnectionItem) -> io::Result<()> {
+    let user = if item.user.is_empty() {
+        std::env::var("USER").unwrap_or_default()
+    } else {
+        item.user.clone()
+    };
+
+    if !item.password.is_empty() {
+        session.userauth_password(&user, &item.password)?;
+        return Ok(());
+    }
+    // The connection's own key comes first, so the agent's keys can't use up the attempts the
+    // server allows before it is offered.
+    let identities = [
+        Some(item.identity_file.clone()),
+        option(item, "IdentityFile"),
+    ];
+    for identity in identities.into_iter().flatten() {
+        if identity.is_empty() {
+            continue;
+        }
+        let path = expand_home(&identity);
+        if session
+            .userauth_pubkey_file(&user, None, &path, None)
+            .is_ok()
+        {
+            return Ok(());
+        }
+    }
+    if session.userauth_agent(&user).is_ok() && session.authenticated() {
+        return Ok(());
+    }
+    if let Some(home) = std::env::var_os("HOME") {
+        for key in DEFAULT_KEYS {
+            let path = Path::new(&home).join(".ssh").join(key);
+            if path.exists()
+                && session
+                    .userauth_pubkey_file(&user, None, &path, None)
+                    .is_ok()
+            {
+                return Ok(());
+            }
+        }
+    }
+    Err(io::Error::new(
+        ErrorKind::PermissionDenied,
+        "authentication failed",
+    ))
+}
+
+/// Retries a libssh2 call that may return `WouldBlock` on a non-blocking session.
+fn retry<T>(mut call: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+    loop {
+        match call() {
+            Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(Duration::from_millis(1)),
+            result => return result,
+        }
+    }
+}
+
+fn write_all(channel: &mut Channel, mut data: &[u8]) -> io::Result<()> {
+    while !data.is_empty() {
+        let n = retry(|| channel.write(data))?;
+        data = &data[n..];
+    }
+    retry(|| channel.flush())
+}
+
+/// Runs `command` on `item`, reporting its output line by line. The command is abandoned once
+/// it runs longer than `timeout`.
+pub(crate) fn exec(
+    item: &ConnectionItem,
+    command: &str,
+    timeout: Option<Duration>,
+    mut on_line: impl FnMut(OutputLine),
+) -> RunStatus {
+    let started = Instant::now();
+    let session = match connect(item) {
+        Ok(session) => session,
+        Err(e) => return RunStatus::Failed(e.to_string()),
+    };
+    let mut channel = match session.channel_session().and_then(|mut channel| {
+        set_env(&mut channel, item);
+        channel.exec(command).map(|_| channel)
+    }) {
+        Ok(channel) => channel,
+        Err(e) => return RunStatus::Failed(e.to_string()),
+    };
+
+    // Both streams are read without blocking so a full stderr can't stall stdout.
+    session.set_blocking(false);
+    let mut stdout = LineBuffer::default();
+    let mut stderr = LineBuffer::default();
+    let mut buf = [0u8; 8192];
+    loop {
+        let mut idle = true;
+        match channel.read(&mut buf) {
+            Ok(n) if n > 0 => {
+                idle = false;
+                stdout.push(&buf[..n], |line| on_line(OutputLine::Stdout(line)));
+            }
+            Err(e) if e.kind() != ErrorKind::WouldBlock => return RunStatus::Failed(e.to_string()),
+            _ => {}
+        }
+        match channel.stderr().read(&mut buf) {
+            Ok(n) if n > 0 => {
+                idle = false;
+                stderr.push(&buf[..n], |line| on_line(OutputLine::Stderr(line)));
+            }
+            Err(e) if e.kind() != ErrorKind::WouldBlock => return RunStatus::Failed(e.to_string()),
+            _ => {}
+        }
+        if idle && channel.eof() {
+            break;
+        }
+        if timeout.is_some_and(|t| started.elapsed() > t) {
+            return RunStatus::TimedOut;
+        }
+        if idle {
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+    stdout.finish(|line| on_line(OutputLine::Stdout(line)));
+    stderr.finish(|line| on_line(OutputLine::Stderr(line)));
+
+    session.set_blocking(true);
+    let _ = channel.wait_close();
+    match channel.exit_status() {
+        Ok(code) => RunStatus::Exited(code),
+        Err(e) => RunStatus::Failed(e.to_string()),
+    }
+}
+
+/// Collects bytes and hands out complete lines.
+#[derive(Default)]
+struct LineBuffer(Vec<u8>);
+
+impl LineBuffer {
+    fn push(&mut self, bytes: &[u8], mut on_line: impl FnMut(String)) {
+        self.0.extend_from_slice(bytes);
+        while let Some(end) = self.0.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.0.drain(..=end).collect();
+            on_line(String::from_utf8_lossy(&line).trim_end().to_string());
+        }
+    }
+
+    fn finish(&mut self, on_line: impl FnOnce(String)) {
+        if !self.0.is_empty() {
+            on_line(String::from_utf8_lossy(&self.0).to_string());
+            self.0.clear();
+        }
+    }
+}
+
+enum ShellInput {
+    Data(Vec<u8>),
+    Resize(u16, u16),
+}
+
+/// An interactive shell with a remote pseudo-terminal. Input is handed to a worker thread that
+/// owns the session, since libssh2 sessions can't be read and written from different threads.
+pub(crate) struct Shell {
+    input: Sender<ShellInput>,
+}
+
+impl Shell {
+    /// Connects to `item` and starts a shell of `rows` x `cols`. `on_output` receives everything
+    /// the remote side prints and `on_exit` is called once the shell has closed.
+    pub(crate) fn open(
+        item: &ConnectionItem,
+        rows: u16,
+        cols: u16,
+        mut on_output: impl FnMut(&[u8]) + Send + 'static,
+        on_exit: impl FnOnce() + Send + 'static,
+    ) -> io::Result<Self> {
+        let session = connect(item)?;
+        let mut channel = session.channel_session()?;
+        let term = item
+            .merged_env()
+            .remove("TERM")
+            .unwrap_or_else(|| "xterm-256color".to_string());
+        channel.request_pty(&term, None, Some((cols as u32, rows as u32, 0, 0)))?;
+        set_env(&mut channel, item);
+        channel.shell()?;
+
+        let (input, receiver): (Sender<ShellInput>, Receiver<ShellInput>) = mpsc::channel();
+        thread::spawn(move || {
+            session.set_blocking(false);
+            let mut buf = [0u8; 8192];
+            loop {
+                let mut idle = true;
+                match receiver.try_recv() {
+                    Ok(ShellInput::Data(data)) => {
+                        idle = false;
+                        if write_all(&mut channel, &data).is_err() {
+                            break;
+                        }
+                    }
+                    Ok(ShellInput::Resize(rows, cols)) => {
+                        let _ = retry(|| {
+                            channel
+                                .request_pty_size(cols as u32, rows as u32, None, None)
+                                .map_err(io::Error::from)
+                        });
+                    }
+                    Err(TryRecvError::Empty) => {}
+                    Err(TryRecvError::Disconnected) => break,
+                }
+                match channel.read(&mut buf) {
+                    Ok(n) if n > 0 => {
+                        idle = false;
+                        on_output(&buf[..n]);
+                    }
+                    Err(e) if e.kind() != ErrorKind::WouldBlock => break,
+                    _ => {}
+                }
+                if channel.eof() {
+                    break;
+                }
+                if idle {
+                    thread::sleep(Duration::from_millis(5));
+                }
+            }
+            session.set_blocking(true);
+            let _ = channel.close();
+            on_exit();
+        });
+
+        Ok(Self { input })
+    }
+
+    pub(crate) fn write(&self, bytes: &[u8]) {
+        let _ = self.input.send(ShellInput::Data(bytes.to_vec()));
+    }
+
+    pub(crate) fn resize(&self, rows: u16, cols: u16) {
+        let _ = self.input.send(ShellInput::Resize(rows, cols));
+    }
+}
+
+/// Progress of an SFTP copy.
+pub(crate) enum CopyEvent {
+    /// A file has been copied completely.
+    Copied(String),
+    /// Percentage of all bytes copied so far.
+    Progress(u16),
+}
+
+/// Uploads `local` to `remote` over SFTP, descending into directories when `recursive` is set.
+pub(crate) fn upload(
+    item: &ConnectionItem,
+    local: &str,
+    remote: &str,
+    recursive: bool,
+    on_event: impl FnMut(CopyEvent),
+) -> io::Result<()> {
+    let session = connect(item)?;
+    let sftp = session.sftp()?;
+    let local = PathBuf::from(local);
+    let mut remote = remote_path(remote);
+    // Like scp, copying into an existing directory keeps the source name.
+    if sftp.stat(&remote).is_ok_and(|stat| stat.is_dir()) {
+        if let Some(name) = local.file_name() {
+            remote.push(name);
+        }
+    }
+
+    let mut files = Vec::new();
+    collect_local(&local, &remote, recursive, &mut files)?;
+    let total = files.iter().filter_map(|(_, _, size)| *size).sum();
+    let mut progress = Progress::new(total, on_event);
+    for (source, target, size) in files {
+        if size.is_none() {
+            // Fails harmlessly when the directory already exists.
+            let _ = sftp.mkdir(&target, 0o755);
+            continue;
+        }
+        let mut reader = fs::File::open(&source)?;
+        let mut writer = sftp.create(&target)?;
+        progress.copy(&mut reader, &mut writer)?;
+        progress.copied(source.display().to_string());
+    }
+    Ok(())
+}
+
+/// Downloads `remote` to `local` over SFTP, descending into directories when `recursive` is set.
+pub(crate) fn download(
+    item: &ConnectionItem,
+    remote: &str,
+    local: &str,
+    recursive: bool,
+    on_event: impl FnMut(CopyEvent),
+) -> io::Result<()> {
+    let session = connect(item)?;
+    let sftp = session.sftp()?;
+    let remote = remote_path(remote);
+    let mut local = PathBuf::from(local);
+    if local.is_dir() {
+        if let Some(name) = remote.file_name() {
+            local.push(name);
+        }
+    }
+
+    let mut files = Vec::new();
+    collect_remote(&sftp, &remote, &local, recursive, &mut files)?;
+    let total = files.iter().filter_map(|(_, _, size)| *size).sum();
+    let mut progress = Progress::new(total, on_event);
+    for (source, target, size) in files {
+        if size.is_none() {
+            fs::create_dir_all(&target)?;
+            continue;
+        }
+        let mut reader = sftp.open(&source)?;
+        let mut writer = fs::File::create(&target)?;
+        progress.copy(&mut reader, &mut writer)?;
+        progress.copied(source.display().to_string());
+    }
+    Ok(())
+}
+
+/// SFTP paths are relative to the home directory, so `~/` is dropped.
+fn remote_path(path: &str) -> PathBuf {
+    let path = path.trim();
+    let path = path.strip_prefix("~/").unwrap_or(path);
+    if path.is_empty() || path == "~" {
+        PathBuf::from(".")
+    } else {
+        PathBuf::from(path)
+    }
+}
+
+/// Files to copy as (source, target, size); directories come before their contents and have
+/// no size.
+type CopyList = Vec<(PathBuf, PathBuf, Option<u64>)>;
+
+fn collect_local(
+    source: &Path,
+    target: &Path,
+    recursive: bool,
+    files: &mut CopyList,
+) -> io::Result<()> {
+    let metadata = fs::metadata(source)?;
+    if !metadata.is_dir() {
+        files.push((
+            source.to_path_buf(),
+            target.to_path_buf(),
+            Some(metadata.len()),
+        ));
+        return Ok(());
+    }
+    if !recursive {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            format!("{} is a directory", source.display()),
+        ));
+    }
+    files.push((source.to_path_buf(), target.to_path_buf(), None));
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        collect_local(&entry.path(), &target.join(entry.file_name()), true, files)?;
+    }
+    Ok(())
+}
+
+fn collect_remote(
+    sftp: &Sftp,
+    source: &Path,
+    target: &Path,
+    recursive: bool,
+    files: &mut CopyList,
+) -> io::Result<()> {
+    let stat = sftp.stat(source)?;
+    if !stat.is_dir() {
+        files.push((
+            source.to_path_buf(),
+            target.to_path_buf(),
+            Some(stat.size.unwrap_or(0)),
+        ));
+        return Ok(());
+    }
+    if !recursive {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            format!("{} is a directory", source.display()),
+        ));
+    }
+    files.push((source.to_path_buf(), target.to_path_buf(), None));
+    for (path, _) in sftp.readdir(source)? {
+        if let Some(name) = path.file_name() {
+            collect_remote(sftp, &path, &target.join(name), true, files)?;
+        }
+    }
+    Ok(())
+}
+
+struct Progress<F: FnMut(CopyEvent)> {
+    total: u64,
+    done: u64,
+    percent: u16,
+    on_event: F,
+}
+
+impl<F: FnMut(CopyEvent)> Progress<F> {
+    fn new(total: u64, on_event: F) -> Self {
+        Self {
+            total,
+            done: 0,
+            percent: 0,
+            on_event,
+        }
+    }
+
+    fn copy(&mut self, reader: &mut impl Read, writer: &mut impl Write) -> io::Result<()> {
+        let mut buf = [0u8; 32 * 1024];
+        loop {
+            let n = reader.read(&mut buf)?;
+            if n == 0 {
+                return writer.flush();
+            }
+            writer.write_all(&buf[..n])?;
+            self.done += n as u64;
+            let percent = (self.done * 100).checked_div(self.total).unwrap_or(100) as u16;
+            if percent != self.percent {
+                self.percent = percent;
+                (self.on_event)(CopyEvent::Progress(percent.min(100)));
+            }
+        }
+    }
+
+    fn copied(&mut self, name: String) {
+        (self.on_event)(CopyEvent::Copied(name));
+    }
+}
@@ -0,0 +1,231 @@
+use std::io;
+use std::process::Command;
+
+use ratatui::widgets::ListState;
+
+use crate::launcher;
+use crate::model::model::{Config, ConnectionItem, LaunchMode, SshBackend, PASSWORD_MASK};
+use crate::mounts;
+use crate::protocol;
+use crate::record;
+use crate::ssh;
+use crate::tmux;
+
+/// The command lines the connect actions would run for one connection.
+pub(crate) struct Preview {
+    pub(crate) label: String,
+    /// Action name and the command line it runs, with the password masked.
+    pub(crate) entries: Vec<(String, String)>,
+    pub(crate) state: ListState,
+    pub(crate) message: Option<String>,
+}
+
+impl Preview {
+    pub(crate) fn new(item: &ConnectionItem, cfg: &Config) -> Self {
+        Self {
+            label: item.label.clone(),
+            entries: entries(item, cfg),
+            state: ListState::default().with_selected(Some(0)),
+            message: None,
+        }
+    }
+
+    pub(crate) fn next(&mut self) {
+        let i = self
+            .state
+            .selected()
+            .map_or(0, |i| (i + 1) % self.entries.len());
+        self.state.select(Some(i));
+    }
+
+    pub(crate) fn previous(&mut self) {
+        let count = self.entries.len();
+        let i = self.state.selected().map_or(0, |i| (i + count - 1) % count);
+        self.state.select(Some(i));
+    }
+
+    pub(crate) fn selected_line(&self) -> Option<&str> {
+        self.state
+            .selected()
+            .and_then(|i| self.entries.get(i))
+            .map(|(_, line)| line.as_str())
+    }
+}
+
+/// What `connect_ssh` and `connect_sshfs` would run for `item`.
+fn entries(item: &ConnectionItem, cfg: &Config) -> Vec<(String, String)> {
+    let item = &with_masked_password(item);
+    let mode = item.launch_mode(cfg);
+    vec![
+        (
+            format!("connect ({})", mode.name()),
+            connect_line(item, cfg),
+        ),
+        (
+            "sshfs".to_string(),
+            render(&command_words(&ssh::sshfs_command(
+                item,
+                &mounts::mount_point(item),
+            ))),
+        ),
+    ]
+}
+
+/// What the connect action runs for `item`, with the password masked.
+pub(crate) fn connect_line(item: &ConnectionItem, cfg: &Config) -> String {
+    let item = &with_masked_password(item);
+    let inline = || match protocol::command(item) {
+        Ok(command) => render(&command_words(&command)),
+        Err(e) => e.to_string(),
+    };
+    let words = match item.launch_mode(cfg) {
+        _ if item.session_backend(cfg) == SshBackend::Native => {
+            return format!(
+                "built-in SSH client to {} port {}",
+                ssh::destination(item),
+                item.port
+            )
+        }
+        LaunchMode::Inline | LaunchMode::Embedded if item.record => {
+            return format!(
+                "{}, recorded to {}",
+                inline(),
+                record::dir(cfg).join(&item.id).display()
+            )
+        }
+        LaunchMode::Inline | LaunchMode::Embedded => return inline(),
+        LaunchMode::External => launcher::command_line(item, cfg),
+        mode => tmux::command_line(item, mode, cfg),
+    };
+    match words {
+        Ok(words) => render(&words),
+        Err(e) => format!("{}, falls back to: {}", e, inline()),
+    }
+}
+
+/// `command`, built for `item`, as a shell command line with the password masked where it is
+/// passed: the `SSHPASS` variable and the words of the command template holding `{password}`.
+pub(crate) fn masked(command: &Command, item: &ConnectionItem) -> String {
+    let mut words = command_words(command);
+    if !item.password.is_empty() {
+        let template_words = protocol::password_words(item, &with_masked_password(item));
+        for word in &mut words {
+            if word.starts_with("SSHPASS=") {
+                *word = format!("SSHPASS={}", PASSWORD_MASK);
+            } else if let Some((_, masked)) = template_words.iter().find(|(w, _)| w == word) {
+                *word = masked.clone();
+            }
+        }
+    }
+    render(&words)
+}
+
+/// A copy of `item` with the mask as its password, so the commands built for it show the mask
+/// exactly where the password goes.
+fn with_masked_password(item: &ConnectionItem) -> ConnectionItem {
+    let mut item = item.clone();
+    if !item.password.is_empty() {
+        item.password = PASSWORD_MASK.to_string();
+    }
+    item
+}
+
+/// The environment assignments, program and arguments of `command`.
+fn command_words(command: &Command) -> Vec<String> {
+    let mut words: Vec<String> = command
+        .get_envs()
+        .filter_map(|(name, value)| {
+            value.map(|value| format!("{}={}", name.to_string_lossy(), value.to_string_lossy()))
+        })
+        .collect();
+    words.extend(ssh::command_line(command));
+    words
+}
+
+/// Quotes `words` like a shell command line.
+fn render(words: &[String]) -> String {
+    shell_words::join(words)
+}
+
+/// Prints the preview of every connection for `--dry-run`.
+pub(crate) fn print_all(items: &[ConnectionItem], cfg: &Config) -> io::Result<()> {
+    use std::io::Write;
+
+    let mut stdout = io::stdout();
+    for item in items {
+        writeln!(stdout, "{}", item.label)?;
+        for (action, line) in entries(item, cfg) {
+            writeln!(stdout, "  {}: {}", action, line)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::model::{Defaults, StoredConnection};
+
+    /// A connection whose password also occurs in its host, so masking it everywhere would show.
+    fn item(template: &str) -> ConnectionItem {
+        let stored = StoredConnection {
+            label: "web".to_string(),
+            host: "web.example.com".to_string(),
+            user: Some("deploy".to_string()),
+            password: Some("web".to_string()),
+            command_template: Some(template.to_string()),
+            ..StoredConnection::new()
+        };
+        ConnectionItem::from_stored(stored, &Defaults::default())
+    }
+
+    #[test]
+    fn masks_sshpass() {
+        let item = item("");
+        let line = masked(&protocol::command(&item).unwrap(), &item);
+        assert!(
+            line.starts_with(&format!("'SSHPASS={}' sshpass -e ssh", PASSWORD_MASK)),
+            "{}",
+            line
+        );
+        assert!(line.ends_with(" deploy@web.example.com"), "{}", line);
+    }
+
+    #[test]
+    fn masks_only_the_password_words_of_templates() {
+        let item = item("tool --host {host} --pass={password} {user}");
+        let line = masked(&protocol::command(&item).unwrap(), &item);
+        assert_eq!(
+            line,
+            format!(
+                "tool --host web.example.com '--pass={}' deploy",
+                PASSWORD_MASK
+            )
+        );
+    }
+
+    #[test]
+    fn leaves_commands_without_a_password_alone() {
+        let mut item = item("tool {host}");
+        item.password.clear();
+        let line = masked(&protocol::command(&item).unwrap(), &item);
+        assert_eq!(line, "tool web.example.com");
+    }
+
+    #[test]
+    fn previews_without_the_password() {
+        let mut item = item("tool --pass={password} {host}");
+        item.password = "s3cret".to_string();
+        let cfg = Config::default();
+        let lines: Vec<String> = entries(&item, &cfg)
+            .into_iter()
+            .map(|(_, line)| line)
+            .chain([connect_line(&item, &cfg)])
+            .collect();
+        for line in &lines {
+            assert!(!line.contains("s3cret"), "{}", line);
+        }
+        assert!(lines[0].contains(&format!("--pass={}", PASSWORD_MASK)));
+        assert!(lines[1].contains(&format!("'SSHPASS={}'", PASSWORD_MASK)));
+    }
+}
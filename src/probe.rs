@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read};
+use std::net::{IpAddr, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::model::model::{ConnectionItem, Protocol};
+
+/// How long connecting and reading the banner may take.
+const TIMEOUT: Duration = Duration::from_secs(3);
+/// Results older than this are refreshed when their connection is selected again.
+const REFRESH_AFTER: Duration = Duration::from_secs(300);
+/// A selection is probed once it rested this long, so scrolling through the list doesn't
+/// connect to every host passed on the way.
+const SETTLE: Duration = Duration::from_millis(800);
+/// Probes running at the same time, each on its own thread.
+const MAX_RUNNING: usize = 4;
+/// Servers may send other lines before their identification, up to this many.
+const MAX_BANNER_LINES: usize = 10;
+
+/// What a probe found out about a host.
+pub(crate) struct ProbeResult {
+    pub(crate) time: Instant,
+    pub(crate) addresses: Vec<IpAddr>,
+    /// Time the TCP handshake with the first address took.
+    pub(crate) latency: Option<Duration>,
+    /// The SSH identification string, e.g. `SSH-2.0-OpenSSH_9.6`.
+    pub(crate) banner: Option<String>,
+    pub(crate) error: Option<String>,
+}
+
+impl ProbeResult {
+    /// The lines shown in the info pane.
+    pub(crate) fn display(&self) -> String {
+        let mut info = format!("probed {}s ago\n", self.time.elapsed().as_secs());
+        if !self.addresses.is_empty() {
+            let addresses: Vec<String> = self.addresses.iter().map(IpAddr::to_string).collect();
+            info += &format!("  addresses: {}\n", addresses.join(", "));
+        }
+        if let Some(latency) = self.latency {
+            info += &format!("  latency: {:.1} ms\n", latency.as_secs_f64() * 1000.0);
+        }
+        if let Some(banner) = &self.banner {
+            info += &format!("  server: {}\n", banner);
+        }
+        if let Some(error) = &self.error {
+            info += &format!("  error: {}\n", error);
+        }
+        info
+    }
+}
+
+enum Probe {
+    Running(Receiver<ProbeResult>),
+    Done(ProbeResult),
+}
+
+/// Probe results by host and port, so connections to the same server share them and edits to
+/// the address probe again.
+#[derive(Default)]
+pub(crate) struct Probes {
+    probes: HashMap<String, Probe>,
+    /// Host and port of the selection and since when it is selected.
+    selected: Option<(String, Instant)>,
+}
+
+impl Probes {
+    /// Probes the selected `item` in the background once it has been selected for a moment,
+    /// unless a recent result or a probe is there already.
+    pub(crate) fn ensure(&mut self, item: &ConnectionItem) {
+        let key = key(item);
+        match &self.selected {
+            Some((selected, since)) if *selected == key => {
+                if since.elapsed() < SETTLE {
+                    return;
+                }
+            }
+            _ => {
+                self.selected = Some((key, Instant::now()));
+                return;
+            }
+        }
+        let stale = match self.probes.get(&key) {
+            None => true,
+            Some(Probe::Running(_)) => false,
+            Some(Probe::Done(result)) => result.time.elapsed() > REFRESH_AFTER,
+        };
+        if stale {
+            self.start(item);
+        }
+    }
+
+    /// Probes `item` again, dropping its result. Returns false when too many probes are
+    /// running already.
+    pub(crate) fn start(&mut self, item: &ConnectionItem) -> bool {
+        let running = self
+            .probes
+            .values()
+            .filter(|probe| matches!(probe, Probe::Running(_)))
+            .count();
+        if running >= MAX_RUNNING {
+            return false;
+        }
+        let (sender, receiver) = channel();
+        let probed = item.clone();
+        thread::spawn(move || {
+            let _ = sender.send(probe(&probed));
+        });
+        self.probes.insert(key(item), Probe::Running(receiver));
+        true
+    }
+
+    /// Collects the results of finished probes.
+    pub(crate) fn poll(&mut self) {
+        for probe in self.probes.values_mut() {
+            if let Probe::Running(receiver) = probe {
+                if let Ok(result) = receiver.try_recv() {
+                    *probe = Probe::Done(result);
+                }
+            }
+        }
+    }
+
+    /// The info pane lines for `item`, if it has been probed.
+    pub(crate) fn display(&self, item: &ConnectionItem) -> Option<String> {
+        match self.probes.get(&key(item))? {
+            Probe::Running(_) => Some("probing...\n".to_string()),
+            Probe::Done(result) => Some(result.display()),
+        }
+    }
+}
+
+fn key(item: &ConnectionItem) -> String {
+    format!("{}:{}", item.host, item.port)
+}
+
+fn probe(item: &ConnectionItem) -> ProbeResult {
+    let mut result = ProbeResult {
+        time: Instant::now(),
+        addresses: Vec::new(),
+        latency: None,
+        banner: None,
+        error: None,
+    };
+    let port = item.port.parse().unwrap_or(22);
+    let addresses: Vec<_> = match (item.host.as_str(), port).to_socket_addrs() {
+        Ok(addresses) => addresses.collect(),
+        Err(e) => {
+            result.error = Some(format!("can't resolve {}: {}", item.host, e));
+            return result;
+        }
+    };
+    for address in &addresses {
+        if !result.addresses.contains(&address.ip()) {
+            result.addresses.push(address.ip());
+        }
+    }
+    let Some(address) = addresses.first() else {
+        result.error = Some(format!("{} has no addresses", item.host));
+        return result;
+    };
+    let started = Instant::now();
+    let stream = match TcpStream::connect_timeout(address, TIMEOUT) {
+        Ok(stream) => stream,
+        Err(e) => {
+            result.error = Some(format!("can't connect to {}: {}", address, e));
+            return result;
+        }
+    };
+    result.latency = Some(started.elapsed());
+    // Telnet servers don't identify themselves.
+    if item.protocol.unwrap_or_default() != Protocol::Telnet {
+        match read_banner(stream) {
+            Ok(banner) => result.banner = Some(banner),
+            Err(e) => result.error = Some(e),
+        }
+    }
+    result.time = Instant::now();
+    result
+}
+
+/// Reads lines until the one starting with `SSH-`, see RFC 4253 section 4.2.
+fn read_banner(stream: TcpStream) -> Result<String, String> {
+    stream
+        .set_read_timeout(Some(TIMEOUT))
+        .map_err(|e| e.to_string())?;
+    // The identification is at most 255 characters, other lines aren't limited.
+    let mut reader = BufReader::new(stream.take(16 * 1024));
+    let mut line = Vec::new();
+    for _ in 0..MAX_BANNER_LINES {
+        line.clear();
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(e) => return Err(format!("no SSH identification: {}", e)),
+        }
+        let text = String::from_utf8_lossy(&line);
+        let text = text.trim_end();
+        if text.starts_with("SSH-") {
+            return Ok(text.to_string());
+        }
+    }
+    Err("no SSH identification".to_string())
+}
@@ -0,0 +1,183 @@
+use std::io;
+use std::process::Command;
+
+use crate::model::model::{Config, ConnectionItem, Protocol};
+use crate::record;
+use crate::ssh;
+
+/// Builds the interactive command the connect action runs for `item`: its command template
+/// when one is set, otherwise the command of its protocol.
+pub(crate) fn command(item: &ConnectionItem) -> io::Result<Command> {
+    if !item.command_template.trim().is_empty() {
+        let argv = expand(&item.command_template, item)?;
+        let mut command = Command::new(&argv[0]);
+        command.args(&argv[1..]).envs(item.merged_env());
+        return Ok(command);
+    }
+    Ok(match item.protocol.unwrap_or_default() {
+        Protocol::Ssh => ssh::ssh_command(item),
+        Protocol::Mosh => ssh::mosh_command(item),
+        Protocol::Telnet => telnet_command(item),
+    })
+}
+
+/// Like [`command`], for sessions started by tmux or a terminal emulator, which get a stored
+/// ssh password from the askpass helper reading the connection file. Recorded connections run
+/// through the recording helper.
+pub(crate) fn handoff_command_line(item: &ConnectionItem, cfg: &Config) -> io::Result<Vec<String>> {
+    let argv = unrecorded_handoff_command_line(item, &cfg.path_to_data_json)?;
+    if item.record {
+        record::wrap(item, cfg, argv)
+    } else {
+        Ok(argv)
+    }
+}
+
+fn unrecorded_handoff_command_line(item: &ConnectionItem, store: &str) -> io::Result<Vec<String>> {
+    let env: Vec<(String, String)> = item.merged_env().into_iter().collect();
+    if !item.command_template.trim().is_empty() {
+        return Ok(ssh::with_env(&env, expand(&item.command_template, item)?));
+    }
+    match item.protocol.unwrap_or_default() {
+        Protocol::Ssh => ssh::handoff_command_line(item, store),
+        Protocol::Mosh => ssh::mosh_handoff_command_line(item, store),
+        Protocol::Telnet => Ok(ssh::with_env(
+            &env,
+            ssh::command_line(&telnet_command(item)),
+        )),
+    }
+}
+
+fn telnet_command(item: &ConnectionItem) -> Command {
+    let mut command = Command::new("telnet");
+    command
+        .arg(&item.host)
+        .arg(&item.port)
+        .envs(item.merged_env());
+    command
+}
+
+/// The words of the command template that hold `{password}`, as expanded for `item` and for
+/// `masked`, the same connection with another password.
+pub(crate) fn password_words(
+    item: &ConnectionItem,
+    masked: &ConnectionItem,
+) -> Vec<(String, String)> {
+    let template = &item.command_template;
+    let (Ok(words), Ok(plain), Ok(hidden)) = (
+        shell_words::split(template),
+        expand(template, item),
+        expand(template, masked),
+    ) else {
+        return Vec::new();
+    };
+    words
+        .iter()
+        .zip(plain.into_iter().zip(hidden))
+        .filter(|(word, _)| word.contains("{password}"))
+        .map(|(_, pair)| pair)
+        .collect()
+}
+
+/// Splits the template like a shell would and fills in the placeholders of every word, so
+/// values are never interpreted by a shell.
+fn expand(template: &str, item: &ConnectionItem) -> io::Result<Vec<String>> {
+    let words =
+        shell_words::split(template).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    if words.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "command template is empty",
+        ));
+    }
+    Ok(words
+        .into_iter()
+        .map(|word| {
+            word.replace("{user}", &item.user)
+                .replace("{host}", &item.host)
+                .replace("{port}", &item.port)
+                .replace("{label}", &item.label)
+                .replace("{password}", &item.password)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::model::{Defaults, StoredConnection};
+
+    fn item(template: &str) -> ConnectionItem {
+        let stored = StoredConnection {
+            label: "web 1".to_string(),
+            host: "web.example.com".to_string(),
+            port: Some("2222".to_string()),
+            user: Some("deploy".to_string()),
+            password: Some("s3cret".to_string()),
+            command_template: Some(template.to_string()),
+            ..StoredConnection::new()
+        };
+        ConnectionItem::from_stored(stored, &Defaults::default())
+    }
+
+    #[test]
+    fn expands_placeholders_in_every_word() {
+        let item = item("");
+        assert_eq!(
+            expand(
+                "ssh -p {port} {user}@{host} 'echo {label}' {password}",
+                &item
+            )
+            .unwrap(),
+            [
+                "ssh",
+                "-p",
+                "2222",
+                "deploy@web.example.com",
+                "echo web 1",
+                "s3cret"
+            ]
+        );
+    }
+
+    #[test]
+    fn never_interprets_values() {
+        let mut item = item("");
+        item.host = "a; rm -rf ~".to_string();
+        assert_eq!(
+            expand("ping -c1 {host}", &item).unwrap(),
+            ["ping", "-c1", "a; rm -rf ~"]
+        );
+    }
+
+    #[test]
+    fn rejects_broken_templates() {
+        let item = item("");
+        assert!(expand("", &item).is_err());
+        assert!(expand("ssh 'open", &item).is_err());
+        assert!(command(&self::item("ssh 'open")).is_err());
+    }
+
+    #[test]
+    fn runs_the_template() {
+        let command = command(&item("ssh -t {user}@{host} tmux attach")).unwrap();
+        assert_eq!(command.get_program(), "ssh");
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(args, ["-t", "deploy@web.example.com", "tmux", "attach"]);
+    }
+
+    #[test]
+    fn pairs_the_password_words() {
+        let item = item("tool --host {host} --pass={password} {password}x");
+        let mut masked = item.clone();
+        masked.password = "***".to_string();
+        assert_eq!(
+            password_words(&item, &masked),
+            [
+                ("--pass=s3cret".to_string(), "--pass=***".to_string()),
+                ("s3cretx".to_string(), "***x".to_string()),
+            ]
+        );
+        assert!(password_words(&self::item("ssh {host}"), &masked).is_empty());
+    }
+}
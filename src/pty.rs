@@ -0,0 +1,50 @@
+use std::io;
+use std::process::Command;
+
+use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySize};
+
+/// A process running on the slave side of a pseudo-terminal.
+pub(crate) struct PtyProcess {
+    pub(crate) master: Box<dyn MasterPty + Send>,
+    pub(crate) child: Box<dyn Child + Send + Sync>,
+}
+
+/// Spawns `command` in a new pseudo-terminal of the given size, keeping its program, arguments,
+/// environment and working directory.
+pub(crate) fn spawn(command: &Command, rows: u16, cols: u16) -> io::Result<PtyProcess> {
+    let pair = native_pty_system()
+        .openpty(PtySize {
+            rows,
+            cols,
+            pixel_width: 0,
+            pixel_height: 0,
+        })
+        .map_err(io::Error::other)?;
+
+    let child = pair
+        .slave
+        .spawn_command(command_builder(command)?)
+        .map_err(io::Error::other)?;
+
+    Ok(PtyProcess {
+        master: pair.master,
+        child,
+    })
+}
+
+fn command_builder(command: &Command) -> io::Result<CommandBuilder> {
+    let mut builder = CommandBuilder::new(command.get_program());
+    builder.args(command.get_args());
+    for (key, value) in command.get_envs() {
+        match value {
+            Some(value) => builder.env(key, value),
+            None => builder.env_remove(key),
+        }
+    }
+    // portable-pty starts in the home directory unless told otherwise.
+    match command.get_current_dir() {
+        Some(dir) => builder.cwd(dir),
+        None => builder.cwd(std::env::current_dir()?),
+    }
+    Ok(builder)
+}
@@ -0,0 +1,241 @@
+//! Session recording in the asciicast v2 format of asciinema, one file per session in a
+//! directory per connection. Sessions handed off to tmux or a terminal emulator are recorded by
+//! the `--record` helper mode of this binary, which relays the session through a
+//! pseudo-terminal.
+
+use std::env;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use portable_pty::PtySize;
+use serde_json::json;
+
+use crate::logging;
+use crate::model::model::{Config, ConnectionItem};
+use crate::pty;
+
+/// First argument of the helper mode: `--record <file> <title> <program> [args...]`.
+const HELPER_FLAG: &str = "--record";
+
+/// Writes the output of a session to an asciicast file.
+pub(crate) struct Recorder {
+    file: BufWriter<File>,
+    started: Instant,
+    /// Bytes of a UTF-8 character split between two reads.
+    pending: Vec<u8>,
+}
+
+impl Recorder {
+    /// Creates the file at `path`, and its directory, and writes the header.
+    pub(crate) fn create(path: &Path, cols: u16, rows: u16, title: &str) -> io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = BufWriter::new(File::create(path)?);
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let header = json!({
+            "version": 2,
+            "width": cols,
+            "height": rows,
+            "timestamp": timestamp,
+            "title": title,
+            "env": { "TERM": env::var("TERM").unwrap_or_default() },
+        });
+        writeln!(file, "{}", header)?;
+        Ok(Self {
+            file,
+            started: Instant::now(),
+            pending: Vec::new(),
+        })
+    }
+
+    /// Records output of the session. Incomplete UTF-8 at the end waits for the next call.
+    pub(crate) fn output(&mut self, bytes: &[u8]) {
+        self.pending.extend_from_slice(bytes);
+        let valid = match std::str::from_utf8(&self.pending) {
+            Ok(_) => self.pending.len(),
+            // Only an incomplete character at the end is kept, invalid bytes are replaced.
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => self.pending.len(),
+        };
+        let rest = self.pending.split_off(valid);
+        let text = String::from_utf8_lossy(&self.pending).to_string();
+        self.pending = rest;
+        if !text.is_empty() {
+            self.event("o", &text);
+        }
+    }
+
+    pub(crate) fn resize(&mut self, cols: u16, rows: u16) {
+        self.event("r", &format!("{}x{}", cols, rows));
+    }
+
+    fn event(&mut self, kind: &str, data: &str) {
+        let time = self.started.elapsed().as_secs_f64();
+        if let Err(e) = writeln!(self.file, "{}", json!([time, kind, data])) {
+            logging::debug(&format!("Failed to write a recording: {}", e));
+        }
+    }
+
+    /// Writes out what is buffered, including an incomplete character.
+    pub(crate) fn flush(&mut self) {
+        if !self.pending.is_empty() {
+            let text = String::from_utf8_lossy(&self.pending).to_string();
+            self.pending.clear();
+            self.event("o", &text);
+        }
+        let _ = self.file.flush();
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+/// The directory recordings go to: `recordings_dir`, or `recordings` next to the connection
+/// file.
+pub(crate) fn dir(cfg: &Config) -> PathBuf {
+    if !cfg.recordings_dir.is_empty() {
+        return PathBuf::from(&cfg.recordings_dir);
+    }
+    Path::new(&cfg.path_to_data_json)
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join("recordings")
+}
+
+/// A new file for a recording of `item`, named after the current time.
+pub(crate) fn new_path(dir: &Path, item: &ConnectionItem) -> PathBuf {
+    let time = logging::timestamp(SystemTime::now()).replace(':', "");
+    dir.join(&item.id).join(format!("{}.cast", time))
+}
+
+/// File names of the recordings of `item`, newest first.
+pub(crate) fn list(dir: &Path, item: &ConnectionItem) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(dir.join(&item.id)) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|name| name.ends_with(".cast"))
+        .collect();
+    names.sort_unstable_by(|a, b| b.cmp(a));
+    names
+}
+
+/// `argv` wrapped in the helper mode, recording to a new file for `item`.
+pub(crate) fn wrap(
+    item: &ConnectionItem,
+    cfg: &Config,
+    argv: Vec<String>,
+) -> io::Result<Vec<String>> {
+    let exe = env::current_exe()?;
+    let path = new_path(&dir(cfg), item);
+    Ok([
+        exe.to_string_lossy().to_string(),
+        HELPER_FLAG.to_string(),
+        path.to_string_lossy().to_string(),
+        item.label.clone(),
+    ]
+    .into_iter()
+    .chain(argv)
+    .collect())
+}
+
+/// Whether this process was started as the recording helper.
+pub(crate) fn is_requested() -> bool {
+    env::args().nth(1).as_deref() == Some(HELPER_FLAG)
+}
+
+/// The helper mode: runs the command from the arguments and exits with its exit code.
+pub(crate) fn run() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().skip(2).collect();
+    let [path, title, program, args @ ..] = args.as_slice() else {
+        return Err(format!("usage: {} <file> <title> <program> [args...]", HELPER_FLAG).into());
+    };
+    let mut command = Command::new(program);
+    command.args(args);
+    let code = relay(&command, Path::new(path), title)?;
+    std::process::exit(code as i32);
+}
+
+/// Runs `command` in a pseudo-terminal connected to this terminal, recording its output to
+/// `path`, and returns its exit code.
+pub(crate) fn relay(command: &Command, path: &Path, title: &str) -> io::Result<u32> {
+    use crossterm::terminal;
+
+    let (cols, rows) = match terminal::size() {
+        Ok((cols, rows)) if cols > 0 && rows > 0 => (cols, rows),
+        _ => (80, 24),
+    };
+    let recorder = Arc::new(Mutex::new(Recorder::create(path, cols, rows, title)?));
+    let pty::PtyProcess { master, mut child } = pty::spawn(command, rows, cols)?;
+    let mut reader = master.try_clone_reader().map_err(io::Error::other)?;
+    let mut writer = master.take_writer().map_err(io::Error::other)?;
+
+    terminal::enable_raw_mode()?;
+    // Stays blocked on stdin after the session ends; the process exits soon after anyway.
+    thread::spawn(move || {
+        let mut buf = [0u8; 1024];
+        let mut stdin = io::stdin();
+        while let Ok(n) = stdin.read(&mut buf) {
+            if n == 0 || writer.write_all(&buf[..n]).is_err() {
+                break;
+            }
+            let _ = writer.flush();
+        }
+    });
+    // Follows size changes of this terminal, there is no portable resize notification.
+    {
+        let recorder = Arc::clone(&recorder);
+        thread::spawn(move || {
+            let mut size = (cols, rows);
+            loop {
+                thread::sleep(Duration::from_millis(250));
+                let Ok(current) = terminal::size() else {
+                    break;
+                };
+                if current != size {
+                    size = current;
+                    let resized = master.resize(PtySize {
+                        rows: size.1,
+                        cols: size.0,
+                        pixel_width: 0,
+                        pixel_height: 0,
+                    });
+                    if resized.is_err() {
+                        break;
+                    }
+                    recorder.lock().unwrap().resize(size.0, size.1);
+                }
+            }
+        });
+    }
+
+    let mut stdout = io::stdout();
+    let mut buf = [0u8; 8192];
+    // Reading fails with EIO once the process has exited.
+    while let Ok(n) = reader.read(&mut buf) {
+        if n == 0 {
+            break;
+        }
+        stdout.write_all(&buf[..n])?;
+        stdout.flush()?;
+        recorder.lock().unwrap().output(&buf[..n]);
+    }
+    recorder.lock().unwrap().flush();
+    let status = child.wait();
+    terminal::disable_raw_mode()?;
+    Ok(status?.exit_code())
+}
@@ -0,0 +1,218 @@
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::logging::Pending;
+use crate::model::model::{ConnectionItem, SshBackend};
+#[cfg(feature = "native-ssh")]
+use crate::native;
+use crate::ssh;
+
+/// A single line of output, tagged with the stream it came from.
+pub(crate) enum OutputLine {
+    Stdout(String),
+    Stderr(String),
+}
+
+pub(crate) enum RunStatus {
+    Running,
+    Exited(i32),
+    /// The process was terminated by a signal.
+    Interrupted,
+    /// The process was killed after running longer than its timeout.
+    TimedOut,
+    Failed(String),
+}
+
+enum RunEvent {
+    Line(OutputLine),
+    Finished(RunStatus, Duration),
+}
+
+/// A command running in the background whose output is collected for display in the TUI.
+pub(crate) struct RemoteCommand {
+    pub(crate) label: String,
+    pub(crate) command: String,
+    pub(crate) lines: Vec<OutputLine>,
+    pub(crate) status: RunStatus,
+    /// How long the process ran, once it has finished.
+    pub(crate) duration: Option<Duration>,
+    /// First visible line of the output pane.
+    pub(crate) scroll: usize,
+    /// Keep the pane scrolled to the newest output.
+    pub(crate) follow: bool,
+    /// Audit record of the run, finished with its status once it stops.
+    pub(crate) audit: Option<Pending>,
+    receiver: Receiver<RunEvent>,
+}
+
+impl RemoteCommand {
+    /// Runs `command` on `item` with the given backend.
+    pub(crate) fn run_on(
+        item: &ConnectionItem,
+        command: &str,
+        backend: SshBackend,
+        timeout: Option<Duration>,
+    ) -> Self {
+        match backend {
+            #[cfg(feature = "native-ssh")]
+            SshBackend::Native => Self::spawn_native(item, command, timeout),
+            _ => Self::spawn(
+                &item.label,
+                command,
+                ssh::ssh_exec_command(item, command),
+                timeout,
+            ),
+        }
+    }
+
+    /// Runs `command` through the in-process SSH client on a background thread.
+    #[cfg(feature = "native-ssh")]
+    fn spawn_native(item: &ConnectionItem, command: &str, timeout: Option<Duration>) -> Self {
+        let (sender, receiver) = channel();
+        let started = Instant::now();
+        let thread_item = item.clone();
+        let thread_command = command.to_string();
+        thread::spawn(move || {
+            if let Some(warning) = native::options_warning(&thread_item) {
+                let _ = sender.send(RunEvent::Line(OutputLine::Stderr(warning)));
+            }
+            let status = native::exec(&thread_item, &thread_command, timeout, |line| {
+                let _ = sender.send(RunEvent::Line(line));
+            });
+            let _ = sender.send(RunEvent::Finished(status, started.elapsed()));
+        });
+        Self::new(&item.label, command, RunStatus::Running, receiver)
+    }
+
+    /// Spawns `process` with stdin closed and starts streaming its stdout and stderr.
+    /// The process is killed if it is still running after `timeout`.
+    pub(crate) fn spawn(
+        label: &str,
+        command: &str,
+        mut process: Command,
+        timeout: Option<Duration>,
+    ) -> Self {
+        let (sender, receiver) = channel();
+        let started = Instant::now();
+        let status = match process
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+        {
+            Ok(mut child) => {
+                let stdout = child
+                    .stdout
+                    .take()
+                    .map(|out| forward_lines(out, sender.clone(), OutputLine::Stdout));
+                let stderr = child
+                    .stderr
+                    .take()
+                    .map(|err| forward_lines(err, sender.clone(), OutputLine::Stderr));
+                thread::spawn(move || {
+                    let status = loop {
+                        match child.try_wait() {
+                            Ok(Some(status)) => match status.code() {
+                                Some(code) => break RunStatus::Exited(code),
+                                None => break RunStatus::Interrupted,
+                            },
+                            Ok(None) if timeout.is_some_and(|t| started.elapsed() > t) => {
+                                let _ = child.kill();
+                                let _ = child.wait();
+                                break RunStatus::TimedOut;
+                            }
+                            Ok(None) => thread::sleep(Duration::from_millis(50)),
+                            Err(e) => break RunStatus::Failed(e.to_string()),
+                        }
+                    };
+                    // Drain both pipes before reporting the exit so no output is lost. A killed
+                    // process may leave children holding the pipes, so don't wait for those.
+                    if !matches!(status, RunStatus::TimedOut) {
+                        for handle in [stdout, stderr].into_iter().flatten() {
+                            let _ = handle.join();
+                        }
+                    }
+                    let _ = sender.send(RunEvent::Finished(status, started.elapsed()));
+                });
+                RunStatus::Running
+            }
+            Err(e) => RunStatus::Failed(format!("Failed to start: {}", e)),
+        };
+
+        Self::new(label, command, status, receiver)
+    }
+
+    fn new(label: &str, command: &str, status: RunStatus, receiver: Receiver<RunEvent>) -> Self {
+        Self {
+            label: label.to_string(),
+            command: command.to_string(),
+            lines: Vec::new(),
+            status,
+            duration: None,
+            scroll: 0,
+            follow: true,
+            audit: None,
+            receiver,
+        }
+    }
+
+    /// Collects output produced since the last call.
+    pub(crate) fn poll(&mut self) {
+        while let Ok(event) = self.receiver.try_recv() {
+            match event {
+                RunEvent::Line(line) => self.lines.push(line),
+                RunEvent::Finished(status, duration) => {
+                    self.status = status;
+                    self.duration = Some(duration);
+                }
+            }
+        }
+        if !self.is_running() {
+            if let Some(audit) = self.audit.take() {
+                audit.finish(&self.status_text());
+            }
+        }
+    }
+
+    pub(crate) fn is_running(&self) -> bool {
+        matches!(self.status, RunStatus::Running)
+    }
+
+    pub(crate) fn status_text(&self) -> String {
+        match &self.status {
+            RunStatus::Running => "running...".to_string(),
+            RunStatus::Exited(code) => format!("exit code {}", code),
+            RunStatus::Interrupted => "interrupted".to_string(),
+            RunStatus::TimedOut => "timed out".to_string(),
+            RunStatus::Failed(e) => e.clone(),
+        }
+    }
+
+    pub(crate) fn scroll_down(&mut self, lines: usize) {
+        self.scroll = (self.scroll + lines).min(self.lines.len().saturating_sub(1));
+        self.follow = false;
+    }
+
+    pub(crate) fn scroll_up(&mut self, lines: usize) {
+        self.scroll = self.scroll.saturating_sub(lines);
+        self.follow = false;
+    }
+}
+
+fn forward_lines<R: Read + Send + 'static>(
+    reader: R,
+    sender: Sender<RunEvent>,
+    wrap: fn(String) -> OutputLine,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        for line in BufReader::new(reader).lines() {
+            let Ok(line) = line else { break };
+            if sender.send(RunEvent::Line(wrap(line))).is_err() {
+                break;
+            }
+        }
+    })
+}
@@ -0,0 +1,296 @@
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use portable_pty::{Child, MasterPty, PtySize};
+
+use crate::model::model::{ConnectionItem, SshBackend};
+#[cfg(feature = "native-ssh")]
+use crate::native;
+use crate::protocol;
+use crate::pty;
+use crate::record::Recorder;
+
+/// Lines kept above the visible screen of an embedded session.
+const SCROLLBACK: usize = 1000;
+
+/// What the emulator is attached to.
+enum Backend {
+    /// The connect command, usually the ssh binary, running in a local pseudo-terminal.
+    Pty {
+        writer: Box<dyn Write + Send>,
+        master: Box<dyn MasterPty + Send>,
+        child: Box<dyn Child + Send + Sync>,
+    },
+    /// A shell channel of the in-process client. Dropping it closes the channel.
+    #[cfg(feature = "native-ssh")]
+    Native(native::Shell),
+}
+
+/// An ssh session rendered inside the TUI.
+pub(crate) struct Session {
+    pub(crate) label: String,
+    pub(crate) parser: Arc<Mutex<vt100::Parser>>,
+    exited: Arc<AtomicBool>,
+    backend: Backend,
+    size: (u16, u16),
+    /// Writes the session to an asciicast file, for recorded connections.
+    recorder: Option<Arc<Mutex<Recorder>>>,
+}
+
+impl Session {
+    /// Starts an interactive session to `item` in a terminal of `rows` x `cols`, recording it to
+    /// `recording` when given.
+    pub(crate) fn open(
+        item: &ConnectionItem,
+        rows: u16,
+        cols: u16,
+        backend: SshBackend,
+        recording: Option<&Path>,
+    ) -> io::Result<Self> {
+        let recorder = match recording {
+            Some(path) => Some(Arc::new(Mutex::new(Recorder::create(
+                path,
+                cols,
+                rows,
+                &item.label,
+            )?))),
+            None => None,
+        };
+        match backend {
+            #[cfg(feature = "native-ssh")]
+            SshBackend::Native => Self::open_native(item, rows, cols, recorder),
+            _ => Self::open_pty(item, rows, cols, recorder),
+        }
+    }
+
+    #[cfg(feature = "native-ssh")]
+    fn open_native(
+        item: &ConnectionItem,
+        rows: u16,
+        cols: u16,
+        recorder: Option<Arc<Mutex<Recorder>>>,
+    ) -> io::Result<Self> {
+        let parser = Arc::new(Mutex::new(vt100::Parser::new(rows, cols, SCROLLBACK)));
+        let exited = Arc::new(AtomicBool::new(false));
+        let shell = {
+            let parser = Arc::clone(&parser);
+            let exited = Arc::clone(&exited);
+            let recorder = recorder.clone();
+            native::Shell::open(
+                item,
+                rows,
+                cols,
+                move |bytes| {
+                    parser.lock().unwrap().process(bytes);
+                    if let Some(recorder) = &recorder {
+                        recorder.lock().unwrap().output(bytes);
+                    }
+                },
+                move || exited.store(true, Ordering::Relaxed),
+            )?
+        };
+
+        Ok(Self {
+            label: item.label.clone(),
+            parser,
+            exited,
+            backend: Backend::Native(shell),
+            size: (rows, cols),
+            recorder,
+        })
+    }
+
+    fn open_pty(
+        item: &ConnectionItem,
+        rows: u16,
+        cols: u16,
+        recorder: Option<Arc<Mutex<Recorder>>>,
+    ) -> io::Result<Self> {
+        let mut command = protocol::command(item)?;
+        // The emulator understands xterm sequences, unless the connection asks for another TERM.
+        if !item.merged_env().contains_key("TERM") {
+            command.env("TERM", "xterm-256color");
+        }
+        let pty::PtyProcess { master, child } = pty::spawn(&command, rows, cols)?;
+        let mut reader = master.try_clone_reader().map_err(io::Error::other)?;
+        let writer = master.take_writer().map_err(io::Error::other)?;
+
+        let parser = Arc::new(Mutex::new(vt100::Parser::new(rows, cols, SCROLLBACK)));
+        let exited = Arc::new(AtomicBool::new(false));
+        {
+            let parser = Arc::clone(&parser);
+            let exited = Arc::clone(&exited);
+            let recorder = recorder.clone();
+            thread::spawn(move || {
+                let mut buf = [0u8; 8192];
+                // Reading fails with EIO once the process has exited.
+                while let Ok(n) = reader.read(&mut buf) {
+                    if n == 0 {
+                        break;
+                    }
+                    parser.lock().unwrap().process(&buf[..n]);
+                    if let Some(recorder) = &recorder {
+                        recorder.lock().unwrap().output(&buf[..n]);
+                    }
+                }
+                if let Some(recorder) = &recorder {
+                    recorder.lock().unwrap().flush();
+                }
+                exited.store(true, Ordering::Relaxed);
+            });
+        }
+
+        Ok(Self {
+            label: item.label.clone(),
+            parser,
+            exited,
+            backend: Backend::Pty {
+                writer,
+                master,
+                child,
+            },
+            size: (rows, cols),
+            recorder,
+        })
+    }
+
+    pub(crate) fn has_exited(&self) -> bool {
+        self.exited.load(Ordering::Relaxed)
+    }
+
+    /// Resizes the pseudo-terminal and the emulator when the pane size changes.
+    pub(crate) fn resize(&mut self, rows: u16, cols: u16) {
+        if self.size == (rows, cols) || rows == 0 || cols == 0 {
+            return;
+        }
+        self.size = (rows, cols);
+        self.parser.lock().unwrap().set_size(rows, cols);
+        if let Some(recorder) = &self.recorder {
+            recorder.lock().unwrap().resize(cols, rows);
+        }
+        match &mut self.backend {
+            Backend::Pty { master, .. } => {
+                let _ = master.resize(PtySize {
+                    rows,
+                    cols,
+                    pixel_width: 0,
+                    pixel_height: 0,
+                });
+            }
+            #[cfg(feature = "native-ssh")]
+            Backend::Native(shell) => shell.resize(rows, cols),
+        }
+    }
+
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        match &mut self.backend {
+            Backend::Pty { writer, .. } => {
+                let _ = writer.write_all(bytes);
+                let _ = writer.flush();
+            }
+            #[cfg(feature = "native-ssh")]
+            Backend::Native(shell) => shell.write(bytes),
+        }
+    }
+
+    /// Sends pasted text, wrapped in bracketed paste markers when the remote program asked for
+    /// them.
+    pub(crate) fn paste(&mut self, text: &str) {
+        let bracketed = self.parser.lock().unwrap().screen().bracketed_paste();
+        if bracketed {
+            self.write(b"\x1b[200~");
+            self.write(text.as_bytes());
+            self.write(b"\x1b[201~");
+        } else {
+            self.write(text.as_bytes());
+        }
+    }
+
+    /// Forwards a key press to the session, encoded the way xterm sends it.
+    pub(crate) fn send_key(&mut self, key: KeyEvent) {
+        let application_cursor = self.parser.lock().unwrap().screen().application_cursor();
+        if let Some(bytes) = key_to_bytes(key, application_cursor) {
+            self.write(&bytes);
+        }
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        match &mut self.backend {
+            Backend::Pty { child, .. } => {
+                if !self.exited.load(Ordering::Relaxed) {
+                    let _ = child.kill();
+                }
+                let _ = child.wait();
+            }
+            #[cfg(feature = "native-ssh")]
+            Backend::Native(_) => {}
+        }
+    }
+}
+
+fn key_to_bytes(key: KeyEvent, application_cursor: bool) -> Option<Vec<u8>> {
+    let cursor = |c: u8| {
+        if application_cursor {
+            vec![0x1b, b'O', c]
+        } else {
+            vec![0x1b, b'[', c]
+        }
+    };
+    let mut bytes = match key.code {
+        KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            let c = c.to_ascii_lowercase();
+            match c {
+                'a'..='z' => vec![c as u8 - b'a' + 1],
+                ' ' | '@' | '2' => vec![0],
+                '[' | '3' => vec![0x1b],
+                '\\' | '4' => vec![0x1c],
+                ']' | '5' => vec![0x1d],
+                '^' | '6' => vec![0x1e],
+                '_' | '7' => vec![0x1f],
+                _ => return None,
+            }
+        }
+        KeyCode::Char(c) => c.to_string().into_bytes(),
+        KeyCode::Enter => vec![b'\r'],
+        KeyCode::Backspace => vec![0x7f],
+        KeyCode::Tab => vec![b'\t'],
+        KeyCode::BackTab => b"\x1b[Z".to_vec(),
+        KeyCode::Esc => vec![0x1b],
+        KeyCode::Up => cursor(b'A'),
+        KeyCode::Down => cursor(b'B'),
+        KeyCode::Right => cursor(b'C'),
+        KeyCode::Left => cursor(b'D'),
+        KeyCode::Home => cursor(b'H'),
+        KeyCode::End => cursor(b'F'),
+        KeyCode::Insert => b"\x1b[2~".to_vec(),
+        KeyCode::Delete => b"\x1b[3~".to_vec(),
+        KeyCode::PageUp => b"\x1b[5~".to_vec(),
+        KeyCode::PageDown => b"\x1b[6~".to_vec(),
+        KeyCode::F(n) => match n {
+            1 => b"\x1bOP".to_vec(),
+            2 => b"\x1bOQ".to_vec(),
+            3 => b"\x1bOR".to_vec(),
+            4 => b"\x1bOS".to_vec(),
+            5 => b"\x1b[15~".to_vec(),
+            6 => b"\x1b[17~".to_vec(),
+            7 => b"\x1b[18~".to_vec(),
+            8 => b"\x1b[19~".to_vec(),
+            9 => b"\x1b[20~".to_vec(),
+            10 => b"\x1b[21~".to_vec(),
+            11 => b"\x1b[23~".to_vec(),
+            12 => b"\x1b[24~".to_vec(),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    if key.modifiers.contains(KeyModifiers::ALT) {
+        bytes.insert(0, 0x1b);
+    }
+    Some(bytes)
+}
@@ -0,0 +1,206 @@
+use std::io;
+use std::process::Command;
+
+use crate::askpass;
+use crate::model::model::ConnectionItem;
+
+/// Options passed to every ssh invocation made by the manager unless the connection or the
+/// defaults set them.
+const SSH_OPTIONS: [&str; 3] = [
+    "ServerAliveInterval=15",
+    "ServerAliveCountMax=3",
+    "StrictHostKeyChecking=no",
+];
+
+/// The program and arguments of `command`, for handing it to another launcher.
+pub(crate) fn command_line(command: &Command) -> Vec<String> {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|arg| arg.to_string_lossy().to_string())
+        .collect()
+}
+
+/// `user@host`, or just `host` when no user is stored.
+pub(crate) fn destination(item: &ConnectionItem) -> String {
+    if item.user.is_empty() {
+        item.host.clone()
+    } else {
+        format!("{}@{}", item.user, item.host)
+    }
+}
+
+/// `program` with the connection's environment, wrapped in `sshpass` when the connection has a
+/// stored password. The password is passed in the environment, which unlike the arguments isn't
+/// visible to other users.
+fn with_password(item: &ConnectionItem, program: &str) -> Command {
+    let mut command = if item.password.is_empty() {
+        Command::new(program)
+    } else {
+        let mut command = Command::new("sshpass");
+        command
+            .env("SSHPASS", &item.password)
+            .arg("-e")
+            .arg(program);
+        command
+    };
+    command.envs(item.merged_env());
+    command
+}
+
+/// Adds the connection's `-o` options and the common ones it doesn't override. ssh uses the
+/// first value it gets for an option, so the built-in ones go last. Commands running behind the
+/// TUI must not prompt on the terminal, so without a stored password they are limited to
+/// non-interactive authentication.
+fn add_options(command: &mut Command, item: &ConnectionItem, interactive: bool) {
+    if !interactive && item.password.is_empty() {
+        command.arg("-o").arg("BatchMode=yes");
+    }
+    if !item.identity_file.is_empty() {
+        command
+            .arg("-o")
+            .arg(format!("IdentityFile={}", item.identity_file));
+    }
+    let options = item.merged_ssh_options();
+    for (key, value) in &options {
+        command.arg("-o").arg(format!("{}={}", key, value));
+    }
+    for option in SSH_OPTIONS {
+        let key = option.split('=').next().unwrap_or_default();
+        if !options.iter().any(|(k, _)| k.eq_ignore_ascii_case(key)) {
+            command.arg("-o").arg(option);
+        }
+    }
+}
+
+/// `ssh` with the connection's port and the common options. The destination is left for the
+/// caller since ssh stops parsing options after it.
+fn ssh_base(item: &ConnectionItem, interactive: bool) -> Command {
+    let mut command = with_password(item, "ssh");
+    add_options(&mut command, item, interactive);
+    command.arg("-p").arg(&item.port);
+    command
+}
+
+/// Builds an interactive `ssh` command for the connection.
+pub(crate) fn ssh_command(item: &ConnectionItem) -> Command {
+    let mut command = ssh_base(item, true);
+    command.arg(destination(item));
+    command
+}
+
+/// The program and arguments of an interactive ssh session that is started by another program,
+/// e.g. tmux or a terminal emulator, and so can't inherit `SSHPASS`. A stored password is
+/// answered by the [`askpass`] helper instead, reading it from `store`.
+pub(crate) fn handoff_command_line(item: &ConnectionItem, store: &str) -> io::Result<Vec<String>> {
+    let mut command = Command::new("ssh");
+    add_options(&mut command, item, true);
+    command.arg("-p").arg(&item.port).arg(destination(item));
+    handoff(item, store, &command)
+}
+
+/// Builds an interactive `mosh` command, using ssh with the connection's port and the common
+/// options to start the server.
+pub(crate) fn mosh_command(item: &ConnectionItem) -> Command {
+    let mut command = with_password(item, "mosh");
+    command.args(mosh_args(item));
+    command
+}
+
+/// Like [`handoff_command_line`], for `mosh`.
+pub(crate) fn mosh_handoff_command_line(
+    item: &ConnectionItem,
+    store: &str,
+) -> io::Result<Vec<String>> {
+    let mut command = Command::new("mosh");
+    command.args(mosh_args(item));
+    handoff(item, store, &command)
+}
+
+fn mosh_args(item: &ConnectionItem) -> [String; 2] {
+    let mut ssh = Command::new("ssh");
+    add_options(&mut ssh, item, true);
+    ssh.arg("-p").arg(&item.port);
+    [
+        format!("--ssh={}", shell_words::join(command_line(&ssh))),
+        destination(item),
+    ]
+}
+
+/// `command` run through `env` with the connection's environment and, when it has a password,
+/// the [`askpass`] one.
+fn handoff(item: &ConnectionItem, store: &str, command: &Command) -> io::Result<Vec<String>> {
+    let mut env: Vec<(String, String)> = item.merged_env().into_iter().collect();
+    if !item.password.is_empty() {
+        env.extend(askpass::env(item, store)?);
+    }
+    Ok(with_env(&env, command_line(command)))
+}
+
+/// `argv` prefixed with `env NAME=value ...` when there is anything to set.
+pub(crate) fn with_env(env: &[(String, String)], argv: Vec<String>) -> Vec<String> {
+    if env.is_empty() {
+        return argv;
+    }
+    std::iter::once("env".to_string())
+        .chain(
+            env.iter()
+                .map(|(name, value)| format!("{}={}", name, value)),
+        )
+        .chain(argv)
+        .collect()
+}
+
+/// Builds a non-interactive `ssh` command that runs `remote_command` on the connection.
+pub(crate) fn ssh_exec_command(item: &ConnectionItem, remote_command: &str) -> Command {
+    let mut command = ssh_base(item, false);
+    command.arg("-T").arg(destination(item)).arg(remote_command);
+    command
+}
+
+/// `user@host:path` for use as an scp source or target.
+pub(crate) fn remote_path(item: &ConnectionItem, path: &str) -> String {
+    format!("{}:{}", destination(item), path)
+}
+
+/// Builds an `scp` command copying `source` to `target`, either of which may be a
+/// [`remote_path`].
+pub(crate) fn scp_command(
+    item: &ConnectionItem,
+    source: &str,
+    target: &str,
+    recursive: bool,
+) -> Command {
+    let mut command = with_password(item, "scp");
+    add_options(&mut command, item, false);
+    command.arg("-P").arg(&item.port);
+    if recursive {
+        command.arg("-r");
+    }
+    command.arg(source).arg(target);
+    command
+}
+
+/// Builds an `sshfs` command mounting the connection's remote path at `mount_point`.
+pub(crate) fn sshfs_command(item: &ConnectionItem, mount_point: &str) -> Command {
+    let mut command = with_password(item, "sshfs");
+    add_options(&mut command, item, false);
+    if !item.sshfs_options.is_empty() {
+        command.arg("-o").arg(&item.sshfs_options);
+    }
+    command
+        .arg("-p")
+        .arg(&item.port)
+        .arg(sshfs_source(item))
+        .arg(mount_point);
+    command
+}
+
+/// The `user@host:/path` sshfs mounts for `item`, which the mount table lists as its source.
+pub(crate) fn sshfs_source(item: &ConnectionItem) -> String {
+    let remote = if item.sshfs_remote_path.is_empty() {
+        "/"
+    } else {
+        item.sshfs_remote_path.as_str()
+    };
+    remote_path(item, remote)
+}
@@ -0,0 +1,121 @@
+use std::time::{Duration, Instant};
+
+use ratatui::style::Color;
+use ratatui::widgets::ListState;
+
+use crate::logging;
+use crate::ui::style::{NOT_AVAILABLE_TEXT_COLOR, SELECTED_STYLE_FG, TEXT_COLOR};
+
+/// How long a message stays in the status bar. Errors stay longer so they aren't missed.
+const SHOW_FOR: Duration = Duration::from_secs(5);
+const SHOW_ERRORS_FOR: Duration = Duration::from_secs(15);
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+
+    pub(crate) fn color(self) -> Color {
+        match self {
+            Severity::Info => TEXT_COLOR,
+            Severity::Warning => SELECTED_STYLE_FG,
+            Severity::Error => NOT_AVAILABLE_TEXT_COLOR,
+        }
+    }
+}
+
+pub(crate) struct Message {
+    pub(crate) severity: Severity,
+    pub(crate) text: String,
+    pub(crate) time: Instant,
+}
+
+impl Message {
+    /// How long ago the message was added, e.g. `42s` or `3m`.
+    pub(crate) fn age(&self) -> String {
+        let secs = self.time.elapsed().as_secs();
+        match secs {
+            0..=59 => format!("{}s", secs),
+            60..=3599 => format!("{}m", secs / 60),
+            _ => format!("{}h", secs / 3600),
+        }
+    }
+}
+
+/// Messages of this run of the manager. The latest one shows in the status bar for a while,
+/// all of them in the message log.
+#[derive(Default)]
+pub(crate) struct StatusLog {
+    pub(crate) messages: Vec<Message>,
+    /// Selected message while the log view is open.
+    pub(crate) view: Option<ListState>,
+}
+
+impl StatusLog {
+    pub(crate) fn info(&mut self, text: impl Into<String>) {
+        self.push(Severity::Info, text.into());
+    }
+
+    pub(crate) fn warning(&mut self, text: impl Into<String>) {
+        self.push(Severity::Warning, text.into());
+    }
+
+    pub(crate) fn error(&mut self, text: impl Into<String>) {
+        self.push(Severity::Error, text.into());
+    }
+
+    fn push(&mut self, severity: Severity, text: String) {
+        logging::debug(&format!("{}: {}", severity.name(), text));
+        self.messages.push(Message {
+            severity,
+            text,
+            time: Instant::now(),
+        });
+    }
+
+    /// The message to show in the status bar, if the latest one is recent enough.
+    pub(crate) fn current(&self) -> Option<&Message> {
+        self.messages.last().filter(|message| {
+            let show_for = if message.severity == Severity::Error {
+                SHOW_ERRORS_FOR
+            } else {
+                SHOW_FOR
+            };
+            message.time.elapsed() < show_for
+        })
+    }
+
+    /// Opens the log view on the latest message.
+    pub(crate) fn open_view(&mut self) {
+        let last = self.messages.len().checked_sub(1);
+        self.view = Some(ListState::default().with_selected(last));
+    }
+
+    pub(crate) fn scroll_down(&mut self, lines: usize) {
+        let last = self.messages.len().saturating_sub(1);
+        if let Some(view) = self.view.as_mut() {
+            let i = view
+                .selected()
+                .map_or(0, |i| i.saturating_add(lines).min(last));
+            view.select(Some(i));
+        }
+    }
+
+    pub(crate) fn scroll_up(&mut self, lines: usize) {
+        if let Some(view) = self.view.as_mut() {
+            let i = view.selected().map_or(0, |i| i.saturating_sub(lines));
+            view.select(Some(i));
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use std::collections::BTreeMap;
+
+use ratatui::widgets::ListState;
+
+use crate::model::model::StoredConnection;
+use crate::utils::new_id;
+
+/// The list of named templates from the config, to start a new connection from one.
+pub(crate) struct TemplatePicker {
+    pub(crate) names: Vec<String>,
+    pub(crate) state: ListState,
+}
+
+impl TemplatePicker {
+    pub(crate) fn new(templates: &BTreeMap<String, StoredConnection>) -> Self {
+        let names: Vec<String> = templates.keys().cloned().collect();
+        let selected = (!names.is_empty()).then_some(0);
+        Self {
+            names,
+            state: ListState::default().with_selected(selected),
+        }
+    }
+
+    pub(crate) fn next(&mut self) {
+        if let Some(i) = self.state.selected() {
+            self.state.select(Some((i + 1) % self.names.len()));
+        }
+    }
+
+    pub(crate) fn previous(&mut self) {
+        let count = self.names.len();
+        if let Some(i) = self.state.selected() {
+            self.state.select(Some((i + count - 1) % count));
+        }
+    }
+
+    pub(crate) fn selected(&self) -> Option<&str> {
+        self.state
+            .selected()
+            .and_then(|i| self.names.get(i))
+            .map(String::as_str)
+    }
+}
+
+/// A new connection with the values of `template`.
+pub(crate) fn seed(template: &StoredConnection) -> StoredConnection {
+    StoredConnection {
+        id: new_id(),
+        ..template.clone()
+    }
+}
@@ -1,20 +1,45 @@
+use std::collections::BTreeSet;
 use std::process::exit;
-use std::process::Command;
-use std::{fs, io::stdout};
+use std::time::{Duration, Instant};
+use std::{fs, io, io::stdout};
 
 use crate::app::App;
-use crate::model::model::{ConnectionItem, StatefulList, Status, StoredConnection};
-use crate::ui::style::{APP_HEADER_BG, NORMAL_ROW_COLOR, SELECTED_STYLE_FG, TEXT_COLOR};
-use crate::utils;
+use crate::broadcast::Broadcast;
+use crate::export::ExportForm;
+use crate::import::ImportPreview;
+use crate::input::LineEditor;
+use crate::keys::{KeySetup, Step};
+use crate::logging::{self, Action, Pending};
+use crate::model::model::{
+    Config, ConnectionItem, Defaults, LaunchMode, Protocol, SshBackend, StatefulList, Status,
+    StoredConnection,
+};
+use crate::mounts::{Mount, MountsPanel};
+use crate::preview::Preview;
+use crate::probe::Probes;
+use crate::remote::{OutputLine, RemoteCommand, RunStatus};
+use crate::session::Session;
+use crate::status::{Severity, StatusLog};
+use crate::templates::TemplatePicker;
+use crate::transfer::{Direction, Transfer, TransferField, TransferForm};
+use crate::ui::style::{
+    APP_HEADER_BG, NORMAL_ROW_COLOR, NOT_AVAILABLE_TEXT_COLOR, SELECTED_STYLE_FG, TEXT_COLOR,
+};
+use crate::wake::{self, Waking};
+use crate::{launcher, mounts, preview, protocol, record, ssh, tmux};
 use color_eyre::config::HookBuilder;
 use crossterm::cursor::{EnableBlinking, Hide, SetCursorStyle, Show};
 use crossterm::{
+    event::{DisableBracketedPaste, EnableBracketedPaste},
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
 };
 use execute::Execute;
 use ratatui::{prelude::*, widgets::*};
 
+/// How long the recordings of the selected connection are shown before they are listed again.
+const RECORDINGS_REFRESH: Duration = Duration::from_secs(10);
+
 pub(crate) fn init_error_hooks() -> color_eyre::Result<()> {
     let (panic, error) = HookBuilder::default().into_hooks();
     let panic = panic.into_panic_hook();
@@ -33,6 +58,7 @@ pub(crate) fn init_error_hooks() -> color_eyre::Result<()> {
 pub(crate) fn init_terminal() -> color_eyre::Result<Terminal<impl Backend>> {
     enable_raw_mode()?;
     stdout().execute(EnterAlternateScreen)?;
+    stdout().execute(EnableBracketedPaste)?;
     let backend = CrosstermBackend::new(stdout());
     let terminal = Terminal::new(backend)?;
     Ok(terminal)
@@ -40,6 +66,7 @@ pub(crate) fn init_terminal() -> color_eyre::Result<Terminal<impl Backend>> {
 
 pub(crate) fn restore_terminal() -> color_eyre::Result<()> {
     disable_raw_mode()?;
+    stdout().execute(DisableBracketedPaste)?;
     stdout().execute(LeaveAlternateScreen)?;
     Ok(())
 }
@@ -55,109 +82,490 @@ fn disable_cursor() {
 }
 
 impl App {
-    pub(crate) fn new(connections: Vec<StoredConnection>) -> Self {
+    pub(crate) fn new(connections: Vec<StoredConnection>, cfg: &Config) -> Self {
         Self {
-            items: StatefulList::with_items(connections),
+            items: StatefulList::with_items(connections, &cfg.defaults),
             new_item_popup: false,
             is_edit_mode: false,
             new_connection: StoredConnection::new(),
+            ssh_options_input: String::new(),
+            env_input: String::new(),
+            tags_input: String::new(),
+            popup_error: None,
+            defaults: cfg.defaults.clone(),
             input_mode: InputMode::Label,
+            command_prompt: None,
+            remote_command: None,
+            broadcast: None,
+            transfer_form: None,
+            transfer: None,
+            mounts: mounts::active_mounts(),
+            mounts_panel: None,
+            preview: None,
+            template_picker: None,
+            waking: None,
+            key_setup: None,
+            import_prompt: None,
+            import_preview: None,
+            export_form: None,
+            probes: Probes::default(),
+            recordings_dir: record::dir(cfg),
+            recordings: None,
+            status: StatusLog::default(),
+            sessions: Vec::new(),
+            active_session: None,
+            prefix_pending: false,
+            editor: LineEditor::default(),
+            reveal_secret: false,
+            revealed_password: None,
+            clipboard_clear_at: None,
         }
     }
 
-    /// Changes the status of the selected list item
-    pub(crate) fn connect_ssh(&mut self) {
+    pub(crate) fn selected_label(&self) -> &str {
+        self.items
+            .state
+            .selected()
+            .map(|i| self.items.items[i].label.as_str())
+            .unwrap_or_default()
+    }
+
+    /// Runs `command` on the selected connection without leaving the TUI.
+    pub(crate) fn run_remote_command(&mut self, command: &str, cfg: &Config) {
         if let Some(i) = self.items.state.selected() {
-            match self.items.items[i].status {
-                Status::Available => {
-                    restore_terminal().unwrap();
-                    enable_cursor();
-                    let output = if !self.items.items[i].password.is_empty() {
-                        let ssh_command = format!(
-                            "sshpass -p {} ssh -o StrictHostKeyChecking=no {}@{} -p {}",
-                            self.items.items[i].password,
-                            self.items.items[i].user,
-                            self.items.items[i].host,
-                            self.items.items[i].port
-                        );
-                        Command::new("sh")
-                            .arg("-c")
-                            .arg(ssh_command)
-                            .execute_output()
-                            .unwrap()
-                    } else {
-                        Command::new("ssh")
-                            .arg("-o ServerAliveInterval=15")
-                            .arg("-o ServerAliveCountMax=3")
-                            .arg("-o StrictHostKeyChecking=no")
-                            .arg(format!(
-                                "{}@{}",
-                                self.items.items[i].user, self.items.items[i].host
-                            ))
-                            .arg(format!("-p {}", self.items.items[i].port))
-                            .execute_output()
-                            .unwrap()
-                    };
+            let item = &self.items.items[i];
+            let mut run = RemoteCommand::run_on(item, command, item.backend(cfg), None);
+            run.audit = Some(Pending::start(
+                Action::Run,
+                item.into(),
+                command.to_string(),
+            ));
+            self.remote_command = Some(run);
+        }
+    }
 
-                    if let Some(exit_code) = output.status.code() {
-                        println!("{}", output.status);
-                        if exit_code != 0 {
-                            println!("stderr: {}", String::from_utf8_lossy(&output.stderr));
-                        }
-                    } else {
-                        eprintln!("Interrupted!");
+    /// Starts copying files between this machine and the selected connection.
+    pub(crate) fn start_transfer(&mut self, form: &TransferForm, cfg: &Config) {
+        if let Some(i) = self.items.state.selected() {
+            let item = &self.items.items[i];
+            let mut transfer = Transfer::start(item, form, item.backend(cfg));
+            transfer.audit = Some(Pending::start(
+                Action::Transfer,
+                item.into(),
+                transfer.description.clone(),
+            ));
+            self.transfer = Some(transfer);
+        }
+    }
+
+    /// Runs `command` on every marked connection in parallel.
+    pub(crate) fn start_broadcast(&mut self, command: &str, cfg: &Config) {
+        let items = self.items.marked_items();
+        if !items.is_empty() {
+            self.broadcast = Some(Broadcast::new(command, items, cfg));
+        }
+    }
+
+    /// Connects to the selected connection, waking it first when it has `wake_on_connect` set.
+    pub(crate) fn connect_ssh(&mut self, cfg: &Config) {
+        if let Some(i) = self.items.state.selected() {
+            let item = &self.items.items[i];
+            if item.wake_on_connect && !item.wake_mac.is_empty() {
+                self.start_waking(i, cfg);
+                return;
+            }
+            self.connect(i, cfg);
+        }
+    }
+
+    /// Opens a session to the connection at `i` the way its launch mode says.
+    fn connect(&mut self, i: usize, cfg: &Config) {
+        match self.items.items[i].status {
+            Status::Available => {
+                let backend = self.items.items[i].session_backend(cfg);
+                let mode = self.items.items[i].launch_mode(cfg);
+                // Fall back to running ssh in this terminal when the mode is unavailable,
+                // e.g. tmux modes outside of tmux or a failed native connection.
+                let launched = match mode {
+                    LaunchMode::Inline => Ok(false),
+                    LaunchMode::Embedded => self.open_session(i, backend).map(|_| true),
+                    LaunchMode::TmuxWindow | LaunchMode::TmuxPane | LaunchMode::TmuxSession => {
+                        tmux::open(&self.items.items[i], mode, cfg).map(|_| true)
+                    }
+                    LaunchMode::External => launcher::open(&self.items.items[i], cfg).map(|_| true),
+                };
+                let item = &self.items.items[i];
+                let target = logging::Target::from(item);
+                if let Ok(true) = launched {
+                    let outcome = format!("opened ({})", mode.name());
+                    logging::audit(
+                        Action::Connect,
+                        &target,
+                        &preview::connect_line(item, cfg),
+                        &outcome,
+                    );
+                    return;
+                }
+                restore_terminal().unwrap();
+                enable_cursor();
+                // The TUI is gone now, so the reason goes to the terminal.
+                if let Err(e) = &launched {
+                    logging::debug(&format!("{} failed for {}: {}", mode.name(), item.label, e));
+                    eprintln!(
+                        "Can't open {} ({}), connecting in this terminal",
+                        mode.name(),
+                        e
+                    );
+                }
+                let mut command = match protocol::command(item) {
+                    Ok(command) => command,
+                    Err(e) => {
+                        let outcome = format!("failed: {}", e);
+                        logging::audit(Action::Connect, &target, "", &outcome);
+                        eprintln!("Failed to connect: {}", e);
+                        exit(1);
+                    }
+                };
+                let audit =
+                    Pending::start(Action::Connect, target, preview::masked(&command, item));
+                if item.record {
+                    let path = record::new_path(&self.recordings_dir, item);
+                    let outcome = match record::relay(&command, &path, &item.label) {
+                        Ok(code) => format!("exit status: {}", code),
+                        Err(e) => format!("failed: {}", e),
                     };
+                    println!("{}", outcome);
+                    println!("Recorded to {}", path.display());
+                    audit.finish(&outcome);
                     exit(0);
                 }
-                Status::NotAvailable => {}
+                let output = match command.execute_output() {
+                    Ok(output) => {
+                        audit.finish(&output.status.to_string());
+                        output
+                    }
+                    Err(e) => {
+                        audit.finish(&format!("failed: {}", e));
+                        eprintln!("Failed to connect: {}", e);
+                        exit(1);
+                    }
+                };
+
+                if let Some(exit_code) = output.status.code() {
+                    println!("{}", output.status);
+                    if exit_code != 0 {
+                        println!("stderr: {}", String::from_utf8_lossy(&output.stderr));
+                    }
+                } else {
+                    eprintln!("Interrupted!");
+                };
+                exit(0);
             }
+            Status::NotAvailable => {}
         }
         disable_cursor();
     }
 
+    /// Sends a magic packet to the selected connection.
+    pub(crate) fn wake_selected(&mut self) {
+        let Some(i) = self.items.state.selected() else {
+            return;
+        };
+        let item = &self.items.items[i];
+        if item.wake_mac.is_empty() {
+            self.status
+                .warning(format!("{} has no Wake-on-LAN MAC address", item.label));
+            return;
+        }
+        let target = logging::Target::from(item);
+        let command = wake::describe(item);
+        match wake::send(item) {
+            Ok(()) => {
+                logging::audit(Action::Wake, &target, &command, "sent");
+                self.status
+                    .info(format!("Sent a wake packet to {}", item.label));
+            }
+            Err(e) => {
+                logging::audit(Action::Wake, &target, &command, &format!("failed: {}", e));
+                self.status
+                    .error(format!("Failed to wake {}: {}", item.label, e));
+            }
+        }
+    }
+
+    /// Wakes the connection at `i` in the background and connects once its port answers,
+    /// see [`App::poll_waking`]. Replaces a wait for another connection.
+    fn start_waking(&mut self, i: usize, cfg: &Config) {
+        self.cancel_waking();
+        let item = &self.items.items[i];
+        let mut waking = Waking::start(item, Duration::from_secs(cfg.wake_timeout_secs));
+        waking.audit = Some(Pending::start(
+            Action::Wake,
+            item.into(),
+            wake::describe(item),
+        ));
+        self.waking = Some(waking);
+    }
+
+    /// Connects to the woken connection once it is up, or reports that it didn't come up.
+    pub(crate) fn poll_waking(&mut self, cfg: &Config) {
+        let Some(result) = self.waking.as_ref().and_then(Waking::poll) else {
+            return;
+        };
+        let Some(mut waking) = self.waking.take() else {
+            return;
+        };
+        let audit = waking.audit.take();
+        match result {
+            Ok(()) => {
+                if let Some(audit) = audit {
+                    audit.finish("reachable");
+                }
+                // The list may have changed while waiting.
+                match self
+                    .items
+                    .items
+                    .iter()
+                    .position(|item| item.id == waking.id)
+                {
+                    Some(i) => self.connect(i, cfg),
+                    None => self
+                        .status
+                        .warning(format!("{} is up but no longer in the list", waking.label)),
+                }
+            }
+            Err(e) => {
+                if let Some(audit) = audit {
+                    audit.finish(&format!("failed: {}", e));
+                }
+                self.status.error(e);
+            }
+        }
+    }
+
+    /// Stops waiting for a connection to wake up.
+    pub(crate) fn cancel_waking(&mut self) {
+        if let Some(mut waking) = self.waking.take() {
+            if let Some(audit) = waking.audit.take() {
+                audit.finish("cancelled");
+            }
+            self.status
+                .info(format!("Stopped waiting for {}", waking.label));
+        }
+    }
+
+    /// Probes the selected connection once the cursor rests on it, unless it has a recent
+    /// result.
+    pub(crate) fn probe_selected(&mut self) {
+        self.probes.poll();
+        if let Some(i) = self.items.state.selected() {
+            let item = &self.items.items[i];
+            if let Status::Available = item.status {
+                self.probes.ensure(item);
+            }
+        }
+    }
+
+    /// Opens the marked connections in synchronized tmux panes.
+    pub(crate) fn open_cluster(&mut self, cfg: &Config) {
+        let items = self.items.marked_items();
+        if items.is_empty() {
+            self.status
+                .warning("Mark connections with Space to open them as a cluster");
+            return;
+        }
+        match tmux::cluster(&items, cfg) {
+            Ok(None) => self
+                .status
+                .info(format!("Opened {} connections in tmux", items.len())),
+            Ok(Some(session)) => self.status.info(format!(
+                "Opened {} connections in tmux, attach with tmux attach -t {}",
+                items.len(),
+                session
+            )),
+            Err(e) => self
+                .status
+                .error(format!("Failed to open the tmux cluster: {}", e)),
+        }
+    }
+
+    /// Opens an embedded terminal tab with an ssh session to the item at `index` and shows it.
+    fn open_session(&mut self, index: usize, backend: SshBackend) -> io::Result<()> {
+        let (cols, rows) = crossterm::terminal::size()?;
+        let item = &self.items.items[index];
+        let recording = item
+            .record
+            .then(|| record::new_path(&self.recordings_dir, item));
+        // The tab bar and the hint line take one row each.
+        let session = Session::open(
+            item,
+            rows.saturating_sub(2),
+            cols,
+            backend,
+            recording.as_deref(),
+        )?;
+        #[cfg(feature = "native-ssh")]
+        if backend == SshBackend::Native {
+            if let Some(warning) = crate::native::options_warning(item) {
+                self.status.warning(warning);
+            }
+        }
+        self.sessions.push(session);
+        self.active_session = Some(self.sessions.len() - 1);
+        Ok(())
+    }
+
+    /// Closes the session at `index`, keeping the active tab pointing at the same session.
+    pub(crate) fn close_session(&mut self, index: usize) {
+        self.sessions.remove(index);
+        // Its recording, if it had one, is complete now.
+        self.recordings = None;
+        self.active_session = match self.active_session {
+            Some(active) if active == index => None,
+            Some(active) if active > index => Some(active - 1),
+            active => active,
+        };
+    }
+
+    /// Lists the recordings of the selected connection when the selection changed, a session
+    /// closed or, for recordings made in tmux or a terminal emulator, a while has passed.
+    pub(crate) fn list_recordings(&mut self) {
+        let Some(item) = self.items.state.selected().map(|i| &self.items.items[i]) else {
+            self.recordings = None;
+            return;
+        };
+        let current = self
+            .recordings
+            .as_ref()
+            .is_some_and(|(id, listed, _)| *id == item.id && listed.elapsed() < RECORDINGS_REFRESH);
+        if !current {
+            let names = record::list(&self.recordings_dir, item);
+            self.recordings = Some((item.id.clone(), Instant::now(), names));
+        }
+    }
+
+    /// Drops sessions whose ssh process has exited.
+    pub(crate) fn reap_sessions(&mut self) {
+        for i in (0..self.sessions.len()).rev() {
+            if self.sessions[i].has_exited() {
+                self.close_session(i);
+            }
+        }
+    }
+
+    /// Mounts the selected connection with sshfs in the background and shows the mounts panel.
     pub(crate) fn connect_sshfs(&mut self) {
         if let Some(i) = self.items.state.selected() {
             match self.items.items[i].status {
                 Status::Available => {
-                    restore_terminal().unwrap();
-
-                    let mount_path = format!(
-                        "/tmp/{}",
-                        utils::remove_whitespace(self.items.items[i].label.as_str())
-                    );
-                    fs::create_dir_all(&mount_path)
-                        .expect(&format!("Can't create temp directory {}", mount_path));
-
-                    let output = Command::new("sshfs")
-                        // .arg("-o reconnect")
-                        // .arg("-o ServerAliveInterval=15")
-                        // .arg("-o ServerAliveCountMax=3")
-                        .arg(format!(
-                            "{}@{}:/",
-                            self.items.items[i].user, self.items.items[i].host
-                        ))
-                        .arg(mount_path)
-                        .arg(format!("-p {}", self.items.items[i].port))
-                        .execute_output()
-                        .unwrap();
-
-                    if let Some(exit_code) = output.status.code() {
-                        if exit_code == 0 {
-                            println!("Ok.");
-                        } else {
-                            eprintln!("Failed.");
-                            println!("stderr: {}", String::from_utf8_lossy(&output.stderr));
-                        }
-                    } else {
-                        eprintln!("Interrupted!");
-                    };
-                    exit(0);
+                    let item = self.items.items[i].clone();
+                    self.mount(&item);
                 }
                 Status::NotAvailable => {}
             }
         }
     }
 
+    fn mount(&mut self, item: &ConnectionItem) {
+        let mount_point = mounts::mount_point(item);
+        let already_mounted = self.mounts.iter().any(|m| m.mount_point == mount_point);
+        let panel = self.mounts_panel.get_or_insert_with(MountsPanel::new);
+        if already_mounted {
+            panel.message = Some(format!(
+                "{} is already mounted at {}",
+                item.label, mount_point
+            ));
+            return;
+        }
+        if let Err(e) = fs::create_dir_all(&mount_point) {
+            panel.message = Some(format!("Can't create directory {}: {}", mount_point, e));
+            return;
+        }
+
+        let process = ssh::sshfs_command(item, &mount_point);
+        panel.audit = Some(Pending::start(
+            Action::Mount,
+            item.into(),
+            preview::masked(&process, item),
+        ));
+        panel.job = Some(RemoteCommand::spawn(
+            &item.label,
+            "sshfs",
+            process,
+            Some(Duration::from_secs(60)),
+        ));
+        panel.message = Some(format!("Mounting {} at {}...", item.label, mount_point));
+    }
+
+    /// Shows the command lines the connect actions would run for the selected connection.
+    pub(crate) fn open_preview(&mut self, cfg: &Config) {
+        if let Some(i) = self.items.state.selected() {
+            self.preview = Some(Preview::new(&self.items.items[i], cfg));
+        }
+    }
+
+    pub(crate) fn open_mounts_panel(&mut self) {
+        self.refresh_mounts();
+        self.mounts_panel = Some(MountsPanel::new());
+    }
+
+    pub(crate) fn refresh_mounts(&mut self) {
+        self.mounts = mounts::active_mounts();
+    }
+
+    /// Unmounts the mount selected in the panel and, for `remount`, mounts its connection again.
+    pub(crate) fn unmount_selected(&mut self, remount: bool) {
+        let Some(panel) = self.mounts_panel.as_mut() else {
+            return;
+        };
+        let Some(mount) = panel
+            .state
+            .selected()
+            .and_then(|i| self.mounts.get(i))
+            .cloned()
+        else {
+            return;
+        };
+        let item = self
+            .items
+            .items
+            .iter()
+            .find(|item| mount.matches(item))
+            .cloned();
+        let target = item.as_ref().map_or_else(
+            || logging::Target {
+                host: mount.host().to_string(),
+                ..Default::default()
+            },
+            logging::Target::from,
+        );
+        let command = format!("fusermount -u {}", mount.mount_point);
+        if let Err(e) = mounts::unmount(&mount.mount_point) {
+            logging::audit(
+                Action::Unmount,
+                &target,
+                &command,
+                &format!("failed: {}", e),
+            );
+            panel.message = Some(format!("Unmounting {} failed: {}", mount.mount_point, e));
+            return;
+        }
+        logging::audit(Action::Unmount, &target, &command, "ok");
+        panel.message = Some(format!("Unmounted {}", mount.mount_point));
+        self.refresh_mounts();
+
+        if remount {
+            match item {
+                Some(item) => self.mount(&item),
+                None => {
+                    if let Some(panel) = self.mounts_panel.as_mut() {
+                        panel.message = Some(format!("No connection for {}", mount.source));
+                    }
+                }
+            }
+        }
+    }
+
     pub(crate) fn go_top(&mut self) {
         self.items.state.select(Some(0));
     }
@@ -173,18 +581,21 @@ impl Widget for &mut App {
         let vertical = Layout::vertical([
             Constraint::Length(2),
             Constraint::Min(0),
-            Constraint::Length(2),
+            Constraint::Length(4),
         ]);
         let [header_area, rest_area, footer_area] = vertical.areas(area);
+        let [status_area, footer_area] =
+            Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(footer_area);
 
         // Create two chunks with equal vertical screen space. One for the list and the other for
         // the info block.
         let vertical = Layout::horizontal([Constraint::Percentage(70), Constraint::Percentage(30)]);
         let [upper_item_list_area, lower_item_list_area] = vertical.areas(rest_area);
 
-        render_title(header_area, buf);
+        render_title(header_area, buf, &self.sessions);
         self.render_app(upper_item_list_area, buf);
         self.render_info(lower_item_list_area, buf);
+        render_status(status_area, buf, &self.status, self.waking.as_ref());
         render_footer(footer_area, buf);
     }
 }
@@ -216,7 +627,10 @@ impl App {
             .items
             .iter()
             .enumerate()
-            .map(|(i, connection_item)| connection_item.to_list_item(i))
+            .map(|(i, connection_item)| {
+                let mounted = self.mounts.iter().any(|m| m.matches(connection_item));
+                connection_item.to_list_item(i, self.items.marked.contains(&i), mounted)
+            })
             .collect();
 
         // Create a List from all list items and highlight the currently selected one
@@ -241,7 +655,37 @@ impl App {
         // We get the info depending on the item's state.
         let info = if let Some(i) = self.items.state.selected() {
             match self.items.items[i].status {
-                Status::Available => self.items.items[i].display(),
+                Status::Available => {
+                    let item = &self.items.items[i];
+                    let mut info = item.display(self.revealed_password == Some(i));
+                    if let Some(probe) = self.probes.display(item) {
+                        info += &format!("\n{}", probe);
+                    }
+                    let recordings = match &self.recordings {
+                        Some((id, _, names)) if *id == item.id => names.as_slice(),
+                        _ => &[],
+                    };
+                    if !recordings.is_empty() {
+                        info += &format!(
+                            "\nrecordings in {}:\n",
+                            self.recordings_dir.join(&item.id).display()
+                        );
+                        for name in recordings.iter().take(10) {
+                            info += &format!("  {}\n", name);
+                        }
+                        if recordings.len() > 10 {
+                            info += &format!("  and {} more\n", recordings.len() - 10);
+                        }
+                    }
+                    if let Some(at) = self.clipboard_clear_at {
+                        let left = at.saturating_duration_since(Instant::now());
+                        info += &format!(
+                            "\npassword on the clipboard, cleared in {}s",
+                            left.as_secs() + 1
+                        );
+                    }
+                    info
+                }
                 Status::NotAvailable => {
                     "NotAvailable - ".to_string() + self.items.items[i].host.as_str()
                 }
@@ -280,16 +724,40 @@ impl App {
     }
 }
 
-fn render_title(area: Rect, buf: &mut Buffer) {
-    Paragraph::new("SSH Manager")
-        .bold()
-        .centered()
-        .render(area, buf);
+fn render_title(area: Rect, buf: &mut Buffer, sessions: &[Session]) {
+    let title = if sessions.is_empty() {
+        "SSH Manager".to_string()
+    } else {
+        let tabs: Vec<String> = sessions
+            .iter()
+            .enumerate()
+            .map(|(i, session)| format!("{} {}", i + 1, session.label))
+            .collect();
+        format!("SSH Manager | sessions: {} (Ctrl-a 1-9)", tabs.join(", "))
+    };
+    Paragraph::new(title).bold().centered().render(area, buf);
+}
+
+/// The latest message while it is recent.
+fn render_status(area: Rect, buf: &mut Buffer, status: &StatusLog, waking: Option<&Waking>) {
+    if let Some(waking) = waking {
+        Paragraph::new(waking.status_text())
+            .fg(Severity::Info.color())
+            .centered()
+            .render(area, buf);
+    } else if let Some(message) = status.current() {
+        Paragraph::new(format!("{}: {}", message.severity.name(), message.text))
+            .fg(message.severity.color())
+            .centered()
+            .render(area, buf);
+    }
 }
 
 fn render_footer(area: Rect, buf: &mut Buffer) {
     Paragraph::new(
-        "\nUse ↓↑ to move, ← to unselect, → to change status, g/G to go top/bottom. f to sshfs.",
+        "Use ↓↑ to move, ← to unselect, → to change status, g/G to go top/bottom. f to sshfs, r to run a command.\n\
+         Space to mark, a to mark all, b to broadcast a command to marked hosts, t to transfer files, m for mounts, c to open marked hosts in tmux, p to preview commands.\n\
+         v to reveal the password, Y to copy it to the clipboard, C to clone, T to start from a template, L for the message log, w to wake the host, i to probe it again, K to set up a key, I to import, X to export.",
     )
     .centered()
     .render(area, buf);
@@ -297,15 +765,17 @@ fn render_footer(area: Rect, buf: &mut Buffer) {
 
 // https://github.com/TheAwiteb/ratatui-textarea/blob/main/examples/single_line.rs
 pub(crate) fn add_new_connection_ui(f: &mut Frame, app: &App) {
-    let area = f.size();
+    let area = f.area();
 
     let vertical = Layout::vertical([Constraint::Percentage(10), Constraint::Percentage(80)]);
-    let [instructions, content] = vertical.areas(area);
+    let [instructions, _] = vertical.areas(area);
 
-    let text = if app.new_item_popup {
-        "Press Enter to save, Esc to cancel"
+    let text = if let Some(error) = app.popup_error.as_ref().filter(|_| app.new_item_popup) {
+        Line::from(error.as_str()).red()
+    } else if app.new_item_popup {
+        Line::from("Press Enter to save, Esc to cancel, Tab/Shift-Tab to move between fields")
     } else {
-        "Press n to add new connection"
+        Line::from("Press n to add new connection")
     };
     let paragraph = Paragraph::new(text).centered().wrap(Wrap { trim: true });
     f.render_widget(paragraph, instructions);
@@ -316,74 +786,691 @@ pub(crate) fn add_new_connection_ui(f: &mut Frame, app: &App) {
             .borders(Borders::ALL);
         let area = centered_rect(60, 80, area);
         f.render_widget(Clear, area); // clear background for popup
+        let inner_area = block.inner(area);
+        f.render_widget(block, area);
+        let area = inner_area;
+
+        // Only as many fields as fit are shown, scrolled so the active one stays visible.
+        let visible = (area.height / 3).max(1) as usize;
+        let active = InputMode::ALL
+            .iter()
+            .position(|&mode| mode == app.input_mode)
+            .unwrap_or(0);
+        let offset = (active + 1).saturating_sub(visible);
+        let input_layout = Layout::vertical(vec![Constraint::Length(3); visible]).split(area);
+
+        for (i, &mode) in InputMode::ALL.iter().skip(offset).take(visible).enumerate() {
+            let value = app.popup_field(mode);
+            let active = mode == app.input_mode;
+            // Errors of untouched fields only show up after trying to save.
+            let error = app
+                .field_error(mode)
+                .filter(|_| app.popup_error.is_some() || !value.is_empty());
+            let mut block = Block::default().title(mode.title()).borders(Borders::ALL);
+            if let Some(error) = &error {
+                block = block
+                    .title_bottom(Line::from(error.as_str()).red())
+                    .border_style(Style::default().fg(Color::Red));
+            }
+            let inner = block.inner(input_layout[i]);
+
+            // Long values scroll sideways to keep the cursor visible.
+            let cursor = app.editor.cursor(value) as u16;
+            let scroll = if active {
+                cursor.saturating_sub(inner.width.saturating_sub(1))
+            } else {
+                0
+            };
+            if mode.is_secret() && !value.is_empty() {
+                let hint = if active && app.reveal_secret {
+                    "Ctrl-r to hide"
+                } else {
+                    "Ctrl-r to reveal"
+                };
+                block = block.title(Line::from(hint).right_aligned().dark_gray());
+            }
+            let text = match app.popup_default(mode) {
+                Some(default) if value.is_empty() => {
+                    Line::from(format!("{} (default)", default)).dark_gray()
+                }
+                // One mask character per character keeps the cursor in place.
+                _ if mode.is_secret() && !(active && app.reveal_secret) => {
+                    Line::from("•".repeat(value.chars().count()))
+                }
+                _ => Line::from(value),
+            };
+            let field = Paragraph::new(text)
+                .block(block)
+                .scroll((0, scroll))
+                .style(if active {
+                    Style::default().fg(Color::Yellow)
+                } else {
+                    Style::default()
+                });
+            f.render_widget(field, input_layout[i]);
+            if active && !mode.is_choice() {
+                f.set_cursor_position((inner.x + cursor - scroll, inner.y));
+            }
+        }
+    }
+}
+
+pub(crate) fn render_command_prompt(f: &mut Frame, target: &str, prompt: &str) {
+    let area = centered_rect(60, 20, f.area());
+    let block = Block::default()
+        .title(format!("Run command on {}", target))
+        .title_bottom("Enter to run, Esc to cancel")
+        .borders(Borders::ALL);
+    let paragraph = Paragraph::new(format!("$ {}", prompt))
+        .block(block)
+        .style(Style::default().fg(Color::Yellow));
+    f.render_widget(Clear, area);
+    f.render_widget(paragraph, area);
+}
+
+pub(crate) fn render_command_output(f: &mut Frame, output: &mut RemoteCommand, hint: &str) {
+    let area = centered_rect(90, 90, f.area());
+    let block = Block::default()
+        .title(format!("{}: {}", output.label, output.command))
+        .title_bottom(format!("{} | {}", output.status_text(), hint))
+        .borders(Borders::ALL);
+
+    let height = block.inner(area).height as usize;
+    let max_scroll = output.lines.len().saturating_sub(height);
+    if output.follow || output.scroll > max_scroll {
+        output.scroll = max_scroll;
+    }
+
+    let lines: Vec<Line> = output
+        .lines
+        .iter()
+        .skip(output.scroll)
+        .take(height)
+        .map(|line| match line {
+            OutputLine::Stdout(text) => Line::raw(text.as_str()),
+            OutputLine::Stderr(text) => Line::styled(text.as_str(), NOT_AVAILABLE_TEXT_COLOR),
+        })
+        .collect();
+
+    f.render_widget(Clear, area);
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
 
-        let input_layout = Layout::vertical([
-            Constraint::Length(3), // Increased height for better visibility
-            Constraint::Length(3),
-            Constraint::Length(3),
-            Constraint::Length(3),
-            Constraint::Length(3),
-        ])
-        .split(area);
-
-        // Render input fields
-        let label_field = Paragraph::new(app.new_connection.label.to_string())
-            .block(Block::default().title("Label").borders(Borders::ALL))
-            .style(match app.input_mode {
-                InputMode::Label => Style::default().fg(Color::Yellow),
-                _ => Style::default(),
-            });
-        f.render_widget(label_field, input_layout[0]);
-
-        let host_field = Paragraph::new(app.new_connection.host.as_str())
-            .block(Block::default().title("Host").borders(Borders::ALL))
-            .style(match app.input_mode {
-                InputMode::Host => Style::default().fg(Color::Yellow),
-                _ => Style::default(),
-            });
-        f.render_widget(host_field, input_layout[1]);
-
-        let port_field = Paragraph::new(
-            app.new_connection
-                .port
-                .as_ref()
-                .unwrap_or(&"".to_string())
-                .to_string(),
+pub(crate) fn render_broadcast(f: &mut Frame, broadcast: &mut Broadcast) {
+    if let Some(run) = broadcast.open_run() {
+        render_command_output(f, run, "j/k/PgUp/PgDn to scroll, Esc to go back");
+        return;
+    }
+
+    let area = centered_rect(90, 90, f.area());
+    let block = Block::default()
+        .title(format!("Broadcast: {}", broadcast.command))
+        .title_bottom(format!(
+            "{}/{} finished | Enter to show output, Esc to close",
+            broadcast.finished(),
+            broadcast.hosts.len()
+        ))
+        .borders(Borders::ALL);
+
+    let rows: Vec<Row> = broadcast
+        .hosts
+        .iter()
+        .map(|host| {
+            let (status, exit_code, duration) = match &host.run {
+                None => ("pending".to_string(), String::new(), String::new()),
+                Some(run) => {
+                    let exit_code = match run.status {
+                        RunStatus::Exited(code) => code.to_string(),
+                        _ => String::new(),
+                    };
+                    let duration = run
+                        .duration
+                        .map(|d| format!("{:.1}s", d.as_secs_f32()))
+                        .unwrap_or_default();
+                    (run.status_text(), exit_code, duration)
+                }
+            };
+            let style = match host.run.as_ref().map(|run| &run.status) {
+                Some(RunStatus::Exited(0)) | Some(RunStatus::Running) | None => {
+                    Style::default().fg(TEXT_COLOR)
+                }
+                _ => Style::default().fg(NOT_AVAILABLE_TEXT_COLOR),
+            };
+            Row::new(vec![
+                host.item.label.clone(),
+                host.item.host.clone(),
+                status,
+                exit_code,
+                duration,
+            ])
+            .style(style)
+        })
+        .collect();
+
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Percentage(25),
+            Constraint::Percentage(25),
+            Constraint::Percentage(25),
+            Constraint::Percentage(10),
+            Constraint::Percentage(15),
+        ],
+    )
+    .header(Row::new(vec!["Label", "Host", "Status", "Exit", "Duration"]).bold())
+    .block(block)
+    .row_highlight_style(
+        Style::default()
+            .add_modifier(Modifier::REVERSED)
+            .fg(SELECTED_STYLE_FG),
+    );
+
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(table, area, &mut broadcast.state);
+}
+
+pub(crate) fn render_transfer_form(f: &mut Frame, label: &str, form: &TransferForm) {
+    let area = centered_rect(60, 60, f.area());
+    let block = Block::default()
+        .title(format!("Transfer files with {}", label))
+        .title_bottom("Tab to switch fields, Space/←→ to toggle, Enter to start, Esc to cancel")
+        .borders(Borders::ALL);
+    let inner_area = block.inner(area);
+    f.render_widget(Clear, area);
+    f.render_widget(block, area);
+
+    let layout = Layout::vertical([Constraint::Length(3); 4]).split(inner_area);
+    let direction = match form.direction {
+        Direction::Upload => "Upload (local -> remote)",
+        Direction::Download => "Download (remote -> local)",
+    };
+    let recursive = if form.recursive { "[x]" } else { "[ ]" };
+    let fields = [
+        (TransferField::Direction, "Direction", direction),
+        (TransferField::Local, "Local path", form.local.as_str()),
+        (TransferField::Remote, "Remote path", form.remote.as_str()),
+        (TransferField::Recursive, "Recursive", recursive),
+    ];
+    for (i, (field, title, value)) in fields.into_iter().enumerate() {
+        let style = if form.field == field {
+            Style::default().fg(Color::Yellow)
+        } else {
+            Style::default()
+        };
+        let paragraph = Paragraph::new(value)
+            .block(Block::default().title(title).borders(Borders::ALL))
+            .style(style);
+        f.render_widget(paragraph, layout[i]);
+    }
+}
+
+pub(crate) fn render_transfer(f: &mut Frame, transfer: &Transfer) {
+    let area = centered_rect(80, 60, f.area());
+    let status = match transfer.duration {
+        Some(duration) => format!(
+            "{} in {:.1}s",
+            transfer.status_text(),
+            duration.as_secs_f32()
+        ),
+        None => transfer.status_text(),
+    };
+    let block = Block::default()
+        .title(transfer.description.as_str())
+        .title_bottom(format!("{} | Esc to close", status))
+        .borders(Borders::ALL);
+    let inner_area = block.inner(area);
+    f.render_widget(Clear, area);
+    f.render_widget(block, area);
+
+    let [gauge_area, current_area, log_area] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Length(1),
+        Constraint::Min(0),
+    ])
+    .areas(inner_area);
+
+    let progress = transfer.progress.unwrap_or(0);
+    let gauge_color = match transfer.status {
+        RunStatus::Running | RunStatus::Exited(0) => SELECTED_STYLE_FG,
+        _ => NOT_AVAILABLE_TEXT_COLOR,
+    };
+    f.render_widget(
+        Gauge::default()
+            .gauge_style(Style::default().fg(gauge_color))
+            .percent(progress),
+        gauge_area,
+    );
+    f.render_widget(Paragraph::new(transfer.current.as_str()), current_area);
+
+    let height = log_area.height as usize;
+    let lines: Vec<Line> = transfer
+        .log
+        .iter()
+        .skip(transfer.log.len().saturating_sub(height))
+        .map(|line| Line::raw(line.as_str()))
+        .collect();
+    f.render_widget(Paragraph::new(lines), log_area);
+}
+
+pub(crate) fn render_import_prompt(f: &mut Frame, prompt: &str) {
+    let area = centered_rect(60, 20, f.area());
+    let block = Block::default()
+        .title(
+            "Import a PuTTY .reg, .remmina, Termius .csv, .mxtsessions or Ansible inventory file \
+             or directory",
         )
-        .block(Block::default().title("Port").borders(Borders::ALL))
-        .style(match app.input_mode {
-            InputMode::Port => Style::default().fg(Color::Yellow),
-            _ => Style::default(),
-        });
-        f.render_widget(port_field, input_layout[2]);
-
-        let user_field = Paragraph::new(
-            app.new_connection
-                .user
-                .as_ref()
-                .unwrap_or(&"".to_string())
-                .to_string(),
+        .title_bottom("Enter to read it, Esc to cancel")
+        .borders(Borders::ALL);
+    let paragraph = Paragraph::new(prompt)
+        .block(block)
+        .style(Style::default().fg(Color::Yellow));
+    f.render_widget(Clear, area);
+    f.render_widget(paragraph, area);
+}
+
+pub(crate) fn render_import_preview(f: &mut Frame, preview: &mut ImportPreview) {
+    let area = centered_rect(90, 80, f.area());
+    let selected = preview
+        .entries
+        .iter()
+        .filter(|entry| entry.selected)
+        .count();
+    let block = Block::default()
+        .title(format!("Import from {}", preview.source))
+        .title_bottom(format!(
+            "Space to toggle, a to toggle all, Enter to add {} connections, Esc to cancel",
+            selected
+        ))
+        .borders(Borders::ALL);
+    let inner_area = block.inner(area);
+    f.render_widget(Clear, area);
+    f.render_widget(block, area);
+
+    let skipped_height = preview.skipped.len().min(5) as u16;
+    let [list_area, skipped_area] = Layout::vertical([
+        Constraint::Min(0),
+        Constraint::Length(skipped_height + u16::from(skipped_height > 0)),
+    ])
+    .areas(inner_area);
+
+    let list_items: Vec<ListItem> = preview
+        .entries
+        .iter()
+        .map(|entry| {
+            let connection = &entry.connection;
+            let mark = if entry.selected { "[x]" } else { "[ ]" };
+            let mut destination = connection.host.clone();
+            if let Some(user) = &connection.user {
+                destination = format!("{}@{}", user, destination);
+            }
+            if let Some(port) = &connection.port {
+                destination = format!("{}:{}", destination, port);
+            }
+            let mut line = format!("{} {}  {}", mark, connection.label, destination);
+            if let Some(group) = &connection.group {
+                line += &format!("  [{}]", group);
+            }
+            if let Some(label) = &entry.duplicate_of {
+                line += &format!("  duplicate of {}", label);
+            }
+            if let Some(label) = &entry.renamed_from {
+                line += &format!("  renamed from {}", label);
+            }
+            let color = if entry.duplicate_of.is_some() {
+                NOT_AVAILABLE_TEXT_COLOR
+            } else {
+                TEXT_COLOR
+            };
+            ListItem::new(line).fg(color)
+        })
+        .collect();
+    let list = List::new(list_items).highlight_style(
+        Style::default()
+            .add_modifier(Modifier::REVERSED)
+            .fg(SELECTED_STYLE_FG),
+    );
+    f.render_stateful_widget(list, list_area, &mut preview.state);
+
+    if skipped_height > 0 {
+        let mut lines = vec![Line::raw(format!("Skipped {}:", preview.skipped.len()))];
+        lines.extend(
+            preview
+                .skipped
+                .iter()
+                .take(skipped_height as usize)
+                .map(|reason| Line::raw(format!("  {}", reason))),
+        );
+        f.render_widget(Paragraph::new(lines), skipped_area);
+    }
+}
+
+pub(crate) fn render_export_form(f: &mut Frame, form: &mut ExportForm) {
+    let area = centered_rect(60, 70, f.area());
+    let block = Block::default()
+        .title(format!(
+            "Export {} connections as CSV or a Markdown table",
+            form.connections.len()
+        ))
+        .title_bottom("Tab to switch fields, Space/←→ to toggle, Enter to write, Esc to cancel")
+        .borders(Borders::ALL);
+    let inner_area = block.inner(area);
+    f.render_widget(Clear, area);
+    f.render_widget(block, area);
+
+    let [path_area, list_area] =
+        Layout::vertical([Constraint::Length(3), Constraint::Min(0)]).areas(inner_area);
+    let path_style = if form.focus == 0 {
+        Style::default().fg(Color::Yellow)
+    } else {
+        Style::default()
+    };
+    let path = Paragraph::new(form.path.as_str())
+        .block(
+            Block::default()
+                .title("File (.csv or .md)")
+                .borders(Borders::ALL),
         )
-        .block(Block::default().title("User").borders(Borders::ALL))
-        .style(match app.input_mode {
-            InputMode::User => Style::default().fg(Color::Yellow),
-            _ => Style::default(),
-        });
-        f.render_widget(user_field, input_layout[3]);
-
-        let password_field = Paragraph::new(
-            app.new_connection
-                .password
-                .as_ref()
-                .unwrap_or(&"".to_string())
-                .to_string(),
+        .style(path_style);
+    f.render_widget(path, path_area);
+
+    let mark = |selected: bool| if selected { "[x]" } else { "[ ]" };
+    let mut list_items = vec![ListItem::new(format!(
+        "{} Redact passwords and env values",
+        mark(form.redact)
+    ))];
+    list_items.extend(
+        form.columns
+            .iter()
+            .map(|(column, selected)| ListItem::new(format!("{} {}", mark(*selected), column))),
+    );
+    let list = List::new(list_items)
+        .block(Block::default().title("Columns").borders(Borders::ALL))
+        .highlight_style(
+            Style::default()
+                .add_modifier(Modifier::REVERSED)
+                .fg(SELECTED_STYLE_FG),
+        );
+    f.render_stateful_widget(list, list_area, &mut form.state);
+}
+
+pub(crate) fn render_key_setup(f: &mut Frame, setup: &KeySetup) {
+    let area = centered_rect(80, 60, f.area());
+    let hint = match setup.step {
+        _ if setup.is_running() => "Esc to close",
+        Step::Generate => "Type to change the key file, Enter to generate it, Esc to close",
+        Step::Switch => "y to clear the password and use the key, n to keep it, Esc to close",
+        Step::Done => "Enter or Esc to close",
+        _ => "Enter to run the next step, Esc to close",
+    };
+    let block = Block::default()
+        .title(format!("SSH key for {}", setup.item.label))
+        .title_bottom(hint)
+        .borders(Borders::ALL);
+    let inner_area = block.inner(area);
+    f.render_widget(Clear, area);
+    f.render_widget(block, area);
+
+    let [path_area, steps_area, log_area] = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Length(Step::ALL.len() as u16 + 1),
+        Constraint::Min(0),
+    ])
+    .areas(inner_area);
+
+    let path_style = if setup.step == Step::Generate && !setup.is_running() {
+        Style::default().fg(Color::Yellow)
+    } else {
+        Style::default()
+    };
+    f.render_widget(
+        Paragraph::new(setup.key_path.as_str())
+            .block(Block::default().title("Key file").borders(Borders::ALL))
+            .style(path_style),
+        path_area,
+    );
+
+    let steps: Vec<Line> = Step::ALL
+        .iter()
+        .map(|&step| {
+            let mark = if step < setup.step {
+                "[x]"
+            } else if step == setup.step && setup.is_running() {
+                "[.]"
+            } else if step == setup.step {
+                "[>]"
+            } else {
+                "[ ]"
+            };
+            Line::raw(format!("{} {}", mark, step.title()))
+        })
+        .collect();
+    f.render_widget(Paragraph::new(steps), steps_area);
+
+    let height = log_area.height as usize;
+    let lines: Vec<Line> = setup
+        .log
+        .iter()
+        .skip(setup.log.len().saturating_sub(height))
+        .map(|line| Line::raw(line.as_str()))
+        .collect();
+    f.render_widget(Paragraph::new(lines).wrap(Wrap { trim: false }), log_area);
+}
+
+pub(crate) fn render_mounts_panel(
+    f: &mut Frame,
+    panel: &mut MountsPanel,
+    mounts: &[Mount],
+    items: &[ConnectionItem],
+) {
+    let area = centered_rect(80, 60, f.area());
+    let block = Block::default()
+        .title("sshfs mounts")
+        .title_bottom("u to unmount, r to remount, Esc to close")
+        .borders(Borders::ALL);
+    let inner_area = block.inner(area);
+    f.render_widget(Clear, area);
+    f.render_widget(block, area);
+
+    let [list_area, message_area] =
+        Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(inner_area);
+
+    let list_items: Vec<ListItem> = if mounts.is_empty() {
+        vec![ListItem::new("No active sshfs mounts")]
+    } else {
+        mounts
+            .iter()
+            .map(|mount| {
+                let label = items
+                    .iter()
+                    .find(|item| mount.matches(item))
+                    .map_or("-", |item| item.label.as_str());
+                ListItem::new(format!(
+                    "{}  {} -> {}",
+                    label, mount.source, mount.mount_point
+                ))
+            })
+            .collect()
+    };
+    let list = List::new(list_items).highlight_style(
+        Style::default()
+            .add_modifier(Modifier::REVERSED)
+            .fg(SELECTED_STYLE_FG),
+    );
+    f.render_stateful_widget(list, list_area, &mut panel.state);
+
+    if let Some(message) = &panel.message {
+        f.render_widget(Paragraph::new(message.as_str()), message_area);
+    }
+}
+
+pub(crate) fn render_preview(f: &mut Frame, preview: &mut Preview) {
+    let area = centered_rect(80, 60, f.area());
+    let block = Block::default()
+        .title(format!("Commands for {}", preview.label))
+        .title_bottom("y to copy, Esc to close")
+        .borders(Borders::ALL);
+    let inner_area = block.inner(area);
+    f.render_widget(Clear, area);
+    f.render_widget(block, area);
+
+    let [list_area, message_area] =
+        Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(inner_area);
+
+    // Command lines are long, so each entry wraps over as many rows as it needs.
+    let width = list_area.width.max(1) as usize;
+    let list_items: Vec<ListItem> = preview
+        .entries
+        .iter()
+        .map(|(action, line)| {
+            let mut lines = vec![Line::from(action.as_str()).bold()];
+            let chars: Vec<char> = line.chars().collect();
+            lines.extend(
+                chars
+                    .chunks(width)
+                    .map(|chunk| Line::from(chunk.iter().collect::<String>())),
+            );
+            lines.push(Line::default());
+            ListItem::new(lines)
+        })
+        .collect();
+    let list = List::new(list_items).highlight_style(Style::default().fg(SELECTED_STYLE_FG));
+    f.render_stateful_widget(list, list_area, &mut preview.state);
+
+    if let Some(message) = &preview.message {
+        f.render_widget(Paragraph::new(message.as_str()), message_area);
+    }
+}
+
+pub(crate) fn render_template_picker(f: &mut Frame, picker: &mut TemplatePicker) {
+    let area = centered_rect(40, 40, f.area());
+    let block = Block::default()
+        .title("New connection from template")
+        .title_bottom("Enter to use, Esc to close")
+        .borders(Borders::ALL);
+    f.render_widget(Clear, area);
+    if picker.names.is_empty() {
+        let hint = Paragraph::new("No templates in the config, add them as [templates.<name>].")
+            .block(block)
+            .wrap(Wrap { trim: true });
+        f.render_widget(hint, area);
+        return;
+    }
+    let list = List::new(picker.names.iter().map(String::as_str))
+        .block(block)
+        .highlight_style(Style::default().fg(SELECTED_STYLE_FG));
+    f.render_stateful_widget(list, area, &mut picker.state);
+}
+
+pub(crate) fn render_message_log(f: &mut Frame, status: &mut StatusLog) {
+    let area = centered_rect(80, 70, f.area());
+    let block = Block::default()
+        .title("Messages")
+        .title_bottom("j/k/PgUp/PgDn to scroll, g/G for oldest/latest, Esc to close")
+        .borders(Borders::ALL);
+    f.render_widget(Clear, area);
+    if status.messages.is_empty() {
+        f.render_widget(Paragraph::new("No messages yet.").block(block), area);
+        return;
+    }
+    let items: Vec<ListItem> = status
+        .messages
+        .iter()
+        .map(|message| {
+            ListItem::new(Line::from(vec![
+                Span::raw(format!("{:>4} ago ", message.age())).dark_gray(),
+                Span::raw(format!("{:<8}", message.severity.name())).fg(message.severity.color()),
+                Span::raw(message.text.as_str()),
+            ]))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    if let Some(view) = status.view.as_mut() {
+        f.render_stateful_widget(list, area, view);
+    }
+}
+
+pub(crate) fn render_sessions(f: &mut Frame, sessions: &mut [Session], active: usize) {
+    let [tabs_area, pane_area, hint_area] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(0),
+        Constraint::Length(1),
+    ])
+    .areas(f.area());
+
+    let titles: Vec<String> = sessions
+        .iter()
+        .enumerate()
+        .map(|(i, session)| format!("{} {}", i + 1, session.label))
+        .collect();
+    f.render_widget(
+        Tabs::new(titles)
+            .select(active)
+            .style(Style::default().fg(TEXT_COLOR).bg(APP_HEADER_BG))
+            .highlight_style(
+                Style::default()
+                    .add_modifier(Modifier::REVERSED)
+                    .fg(SELECTED_STYLE_FG),
+            ),
+        tabs_area,
+    );
+    f.render_widget(
+        Paragraph::new(
+            "Ctrl-a then: l list, n/p next/previous, 1-9 tab, x close, Ctrl-a send Ctrl-a",
         )
-        .block(Block::default().title("Password").borders(Borders::ALL))
-        .style(match app.input_mode {
-            InputMode::Password => Style::default().fg(Color::Yellow),
-            _ => Style::default(),
-        });
-        f.render_widget(password_field, input_layout[4]);
+        .fg(TEXT_COLOR)
+        .bg(APP_HEADER_BG),
+        hint_area,
+    );
+
+    let session = &mut sessions[active];
+    session.resize(pane_area.height, pane_area.width);
+    let parser = session.parser.lock().unwrap();
+    let screen = parser.screen();
+    let buf = f.buffer_mut();
+    for row in 0..pane_area.height {
+        for col in 0..pane_area.width {
+            let Some(cell) = screen.cell(row, col) else {
+                continue;
+            };
+            if cell.is_wide_continuation() {
+                continue;
+            }
+            let mut style = Style::default()
+                .fg(vt100_color(cell.fgcolor()))
+                .bg(vt100_color(cell.bgcolor()));
+            if cell.bold() {
+                style = style.add_modifier(Modifier::BOLD);
+            }
+            if cell.italic() {
+                style = style.add_modifier(Modifier::ITALIC);
+            }
+            if cell.underline() {
+                style = style.add_modifier(Modifier::UNDERLINED);
+            }
+            if cell.inverse() {
+                style = style.add_modifier(Modifier::REVERSED);
+            }
+            let contents = cell.contents();
+            let symbol = if contents.is_empty() { " " } else { &contents };
+            if let Some(target) = buf.cell_mut((pane_area.x + col, pane_area.y + row)) {
+                target.set_symbol(symbol).set_style(style);
+            }
+        }
+    }
+
+    if !screen.hide_cursor() {
+        let (row, col) = screen.cursor_position();
+        f.set_cursor_position((pane_area.x + col, pane_area.y + row));
+    }
+}
+
+fn vt100_color(color: vt100::Color) -> Color {
+    match color {
+        vt100::Color::Default => Color::Reset,
+        vt100::Color::Idx(i) => Color::Indexed(i),
+        vt100::Color::Rgb(r, g, b) => Color::Rgb(r, g, b),
     }
 }
 
@@ -405,31 +1492,41 @@ pub(crate) fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
 }
 
 impl StatefulList {
-    pub(crate) fn with_items(items: Vec<StoredConnection>) -> StatefulList {
+    pub(crate) fn with_items(items: Vec<StoredConnection>, defaults: &Defaults) -> StatefulList {
         let a = items
             .into_iter()
-            .map(|item| ConnectionItem {
-                label: item.label,
-                host: item.host,
-                port: item.port.unwrap_or("22".parse().unwrap()), // TODO maybe configure default port and default user
-                user: item.user.unwrap_or("root".parse().unwrap()),
-                password: if let Some(password) = item.password {
-                    password
-                } else {
-                    "".parse().unwrap()
-                },
-                details: "".parse().unwrap(),
-                status: Status::Available,
-            })
+            .map(|item| ConnectionItem::from_stored(item, defaults))
             .collect::<Vec<ConnectionItem>>();
 
         StatefulList {
             state: ListState::default(),
             items: a,
             last_selected: None,
+            marked: BTreeSet::new(),
         }
     }
 
+    pub(crate) fn toggle_mark(&mut self) {
+        if let Some(i) = self.state.selected() {
+            if !self.marked.remove(&i) {
+                self.marked.insert(i);
+            }
+        }
+    }
+
+    /// Marks every item, or clears the marks if everything is already marked.
+    pub(crate) fn toggle_mark_all(&mut self) {
+        if self.marked.len() == self.items.len() {
+            self.marked.clear();
+        } else {
+            self.marked = (0..self.items.len()).collect();
+        }
+    }
+
+    pub(crate) fn marked_items(&self) -> Vec<ConnectionItem> {
+        self.marked.iter().map(|&i| self.items[i].clone()).collect()
+    }
+
     pub(crate) fn next(&mut self) {
         let i = match self.state.selected() {
             Some(i) => {
@@ -466,11 +1563,207 @@ impl StatefulList {
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq)]
 pub(crate) enum InputMode {
     Label,
+    Group,
+    Tags,
     Host,
     Port,
     User,
     Password,
+    IdentityFile,
+    SshfsRemotePath,
+    SshfsMountPoint,
+    SshfsOptions,
+    LaunchMode,
+    Backend,
+    Protocol,
+    CommandTemplate,
+    SshOptions,
+    Env,
+    Record,
+    WakeMac,
+    WakeBroadcast,
+    WakeOnConnect,
+}
+
+impl InputMode {
+    /// Fields of the connection popup in the order Tab cycles through them.
+    pub(crate) const ALL: [InputMode; 21] = [
+        InputMode::Label,
+        InputMode::Group,
+        InputMode::Tags,
+        InputMode::Host,
+        InputMode::Port,
+        InputMode::User,
+        InputMode::Password,
+        InputMode::IdentityFile,
+        InputMode::SshfsRemotePath,
+        InputMode::SshfsMountPoint,
+        InputMode::SshfsOptions,
+        InputMode::LaunchMode,
+        InputMode::Backend,
+        InputMode::Protocol,
+        InputMode::CommandTemplate,
+        InputMode::SshOptions,
+        InputMode::Env,
+        InputMode::Record,
+        InputMode::WakeMac,
+        InputMode::WakeBroadcast,
+        InputMode::WakeOnConnect,
+    ];
+
+    pub(crate) fn next(self) -> Self {
+        let i = Self::ALL.iter().position(|&mode| mode == self).unwrap_or(0);
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+
+    pub(crate) fn previous(self) -> Self {
+        let i = Self::ALL.iter().position(|&mode| mode == self).unwrap_or(0);
+        Self::ALL[(i + Self::ALL.len() - 1) % Self::ALL.len()]
+    }
+
+    pub(crate) fn title(self) -> &'static str {
+        match self {
+            InputMode::Label => "Label",
+            InputMode::Group => "Group (e.g. prod/web)",
+            InputMode::Tags => "Tags (e.g. web debian)",
+            InputMode::Host => "Host",
+            InputMode::Port => "Port",
+            InputMode::User => "User",
+            InputMode::Password => "Password",
+            InputMode::IdentityFile => "Identity file (e.g. ~/.ssh/id_ed25519)",
+            InputMode::SshfsRemotePath => "sshfs remote path (default /)",
+            InputMode::SshfsMountPoint => "sshfs mount point (default /tmp/<label>)",
+            InputMode::SshfsOptions => "sshfs options (e.g. reconnect,idmap=user,ro)",
+            InputMode::LaunchMode => "Launch mode (Space to change, Backspace to use the config)",
+            InputMode::Backend => "SSH backend (Space to change, Backspace to use the config)",
+            InputMode::Protocol => "Protocol (Space to change, default ssh)",
+            InputMode::CommandTemplate => {
+                "Command template, replaces the protocol ({password} shows in ps)"
+            }
+            InputMode::SshOptions => "ssh options (e.g. Compression=yes ForwardX11=yes)",
+            InputMode::Env => "Environment (e.g. TERM=xterm-256color LC_ALL=C.UTF-8)",
+            InputMode::Record => "Record sessions (Space to change)",
+            InputMode::WakeMac => "Wake-on-LAN MAC address (e.g. aa:bb:cc:dd:ee:ff)",
+            InputMode::WakeBroadcast => "Wake-on-LAN broadcast address[:port]",
+            InputMode::WakeOnConnect => "Wake before connecting (Space to change)",
+        }
+    }
+
+    /// Fields that pick from a fixed set of values instead of taking text.
+    pub(crate) fn is_choice(self) -> bool {
+        matches!(
+            self,
+            InputMode::LaunchMode
+                | InputMode::Backend
+                | InputMode::Protocol
+                | InputMode::Record
+                | InputMode::WakeOnConnect
+        )
+    }
+
+    /// Fields rendered masked until revealed.
+    pub(crate) fn is_secret(self) -> bool {
+        self == InputMode::Password
+    }
+}
+
+impl StoredConnection {
+    /// The text of a popup field.
+    pub(crate) fn field(&self, mode: InputMode) -> &str {
+        let value = match mode {
+            InputMode::LaunchMode => return self.launch_mode.map_or("", LaunchMode::name),
+            InputMode::Backend => return self.backend.map_or("", SshBackend::name),
+            InputMode::Protocol => return self.protocol.map_or("", Protocol::name),
+            InputMode::Record => return if self.record == Some(true) { "on" } else { "" },
+            InputMode::WakeOnConnect => {
+                return if self.wake_on_connect == Some(true) {
+                    "on"
+                } else {
+                    ""
+                }
+            }
+            // Edited as text by the app, see `App::popup_field`.
+            InputMode::SshOptions | InputMode::Env | InputMode::Tags => return "",
+            InputMode::Label => return &self.label,
+            InputMode::Host => return &self.host,
+            InputMode::Group => &self.group,
+            InputMode::Port => &self.port,
+            InputMode::User => &self.user,
+            InputMode::Password => &self.password,
+            InputMode::IdentityFile => &self.identity_file,
+            InputMode::SshfsRemotePath => &self.sshfs_remote_path,
+            InputMode::SshfsMountPoint => &self.sshfs_mount_point,
+            InputMode::SshfsOptions => &self.sshfs_options,
+            InputMode::CommandTemplate => &self.command_template,
+            InputMode::WakeMac => &self.wake_mac,
+            InputMode::WakeBroadcast => &self.wake_broadcast,
+        };
+        value.as_deref().unwrap_or_default()
+    }
+
+    /// The text of a popup field for editing, creating optional fields on first input. Fields
+    /// without free text (the choice fields) have none.
+    pub(crate) fn field_mut(&mut self, mode: InputMode) -> Option<&mut String> {
+        let value = match mode {
+            InputMode::LaunchMode
+            | InputMode::Backend
+            | InputMode::Protocol
+            | InputMode::Record
+            | InputMode::WakeOnConnect
+            | InputMode::SshOptions
+            | InputMode::Env
+            | InputMode::Tags => return None,
+            InputMode::Label => return Some(&mut self.label),
+            InputMode::Host => return Some(&mut self.host),
+            InputMode::Group => &mut self.group,
+            InputMode::Port => &mut self.port,
+            InputMode::User => &mut self.user,
+            InputMode::Password => &mut self.password,
+            InputMode::IdentityFile => &mut self.identity_file,
+            InputMode::SshfsRemotePath => &mut self.sshfs_remote_path,
+            InputMode::SshfsMountPoint => &mut self.sshfs_mount_point,
+            InputMode::SshfsOptions => &mut self.sshfs_options,
+            InputMode::CommandTemplate => &mut self.command_template,
+            InputMode::WakeMac => &mut self.wake_mac,
+            InputMode::WakeBroadcast => &mut self.wake_broadcast,
+        };
+        Some(value.get_or_insert_with(String::new))
+    }
+
+    /// Steps a choice field through "use the config" and every value.
+    pub(crate) fn cycle_choice(&mut self, mode: InputMode) {
+        match mode {
+            InputMode::LaunchMode => self.launch_mode = cycle(self.launch_mode, &LaunchMode::ALL),
+            InputMode::Backend => self.backend = cycle(self.backend, &SshBackend::ALL),
+            InputMode::Protocol => self.protocol = cycle(self.protocol, &Protocol::ALL),
+            InputMode::Record => self.record = cycle(self.record, &[true]),
+            InputMode::WakeOnConnect => self.wake_on_connect = cycle(self.wake_on_connect, &[true]),
+            _ => {}
+        }
+    }
+
+    /// Resets a choice field to the value from the config.
+    pub(crate) fn clear_choice(&mut self, mode: InputMode) {
+        match mode {
+            InputMode::LaunchMode => self.launch_mode = None,
+            InputMode::Backend => self.backend = None,
+            InputMode::Protocol => self.protocol = None,
+            InputMode::Record => self.record = None,
+            InputMode::WakeOnConnect => self.wake_on_connect = None,
+            _ => {}
+        }
+    }
+}
+
+fn cycle<T: Copy + PartialEq>(current: Option<T>, all: &[T]) -> Option<T> {
+    match current {
+        None => all.first().copied(),
+        Some(value) => {
+            let i = all.iter().position(|&v| v == value).unwrap_or(0);
+            all.get(i + 1).copied()
+        }
+    }
 }
@@ -0,0 +1,138 @@
+use std::env;
+use std::io;
+use std::process::{Command, Output};
+
+use crate::model::model::{Config, ConnectionItem, LaunchMode};
+use crate::protocol;
+
+/// Whether the manager itself runs inside a tmux client.
+pub(crate) fn inside_tmux() -> bool {
+    env::var_os("TMUX").is_some()
+}
+
+/// Opens an ssh session to `item` in a new tmux window, pane or session, keeping the manager
+/// running.
+pub(crate) fn open(item: &ConnectionItem, mode: LaunchMode, cfg: &Config) -> io::Result<()> {
+    let argv = command_line(item, mode, cfg)?;
+    if mode == LaunchMode::TmuxSession {
+        let target = format!("={}", target_name(&item.label));
+        let exists = tmux(&["has-session", "-t", &target], &[]).is_ok();
+        if !exists {
+            tmux(&[], &argv[1..])?;
+        }
+        // Outside tmux the session is left detached for `tmux attach`.
+        if inside_tmux() {
+            tmux(&["switch-client", "-t", &target], &[])?;
+        }
+    } else {
+        tmux(&[], &argv[1..])?;
+    }
+    Ok(())
+}
+
+/// The tmux command that [`open`] runs to create the window, pane or session.
+pub(crate) fn command_line(
+    item: &ConnectionItem,
+    mode: LaunchMode,
+    cfg: &Config,
+) -> io::Result<Vec<String>> {
+    let name = target_name(&item.label);
+    let args = match mode {
+        LaunchMode::TmuxWindow if inside_tmux() => vec!["new-window", "-n", &name, "--"],
+        LaunchMode::TmuxPane if inside_tmux() => vec!["split-window", "--"],
+        LaunchMode::TmuxSession => vec!["new-session", "-d", "-s", &name, "--"],
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("{} needs to run inside tmux", mode.name()),
+            ))
+        }
+    };
+    let mut argv: Vec<String> = std::iter::once("tmux")
+        .chain(args)
+        .map(String::from)
+        .collect();
+    argv.extend(protocol::handoff_command_line(item, cfg)?);
+    Ok(argv)
+}
+
+/// Opens all `items` as panes of one new tmux window with synchronized input, so typing goes
+/// to every host at once. Outside tmux the window gets a detached session of its own, named
+/// `cluster` or `cluster-2` and so on while earlier clusters still run, which is returned.
+pub(crate) fn cluster(items: &[ConnectionItem], cfg: &Config) -> io::Result<Option<String>> {
+    let Some((first, rest)) = items.split_first() else {
+        return Ok(None);
+    };
+    let session = (!inside_tmux()).then(|| {
+        (1..)
+            .map(|n| match n {
+                1 => "cluster".to_string(),
+                n => format!("cluster-{}", n),
+            })
+            .find(|name| tmux(&["has-session", "-t", &format!("={}", name)], &[]).is_err())
+            .unwrap_or_default()
+    });
+    let ssh = protocol::handoff_command_line(first, cfg)?;
+    let output = match &session {
+        None => tmux(
+            &[
+                "new-window",
+                "-P",
+                "-F",
+                "#{window_id}",
+                "-n",
+                "cluster",
+                "--",
+            ],
+            &ssh,
+        )?,
+        Some(session) => tmux(
+            &[
+                "new-session",
+                "-d",
+                "-P",
+                "-F",
+                "#{window_id}",
+                "-s",
+                session,
+                "--",
+            ],
+            &ssh,
+        )?,
+    };
+    let window = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+    for item in rest {
+        let ssh = protocol::handoff_command_line(item, cfg)?;
+        tmux(&["split-window", "-t", &window, "--"], &ssh)?;
+        // Re-tile after every split so panes don't become too small to split again.
+        tmux(&["select-layout", "-t", &window, "tiled"], &[])?;
+    }
+    tmux(
+        &[
+            "set-window-option",
+            "-t",
+            &window,
+            "synchronize-panes",
+            "on",
+        ],
+        &[],
+    )?;
+    Ok(session)
+}
+
+/// tmux doesn't allow `.` and `:` in session and window names.
+fn target_name(label: &str) -> String {
+    label.replace(['.', ':'], "_")
+}
+
+fn tmux(args: &[&str], command: &[String]) -> io::Result<Output> {
+    let output = Command::new("tmux").args(args).args(command).output()?;
+    if output.status.success() {
+        Ok(output)
+    } else {
+        Err(io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ))
+    }
+}
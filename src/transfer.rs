@@ -0,0 +1,311 @@
+use std::io::Read;
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::logging::Pending;
+use crate::model::model::{ConnectionItem, SshBackend};
+#[cfg(feature = "native-ssh")]
+use crate::native;
+use crate::pty;
+use crate::remote::RunStatus;
+use crate::ssh;
+
+#[derive(Copy, Clone, PartialEq)]
+pub(crate) enum Direction {
+    Upload,
+    Download,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub(crate) enum TransferField {
+    Direction,
+    Local,
+    Remote,
+    Recursive,
+}
+
+impl TransferField {
+    pub(crate) fn next(self) -> Self {
+        match self {
+            TransferField::Direction => TransferField::Local,
+            TransferField::Local => TransferField::Remote,
+            TransferField::Remote => TransferField::Recursive,
+            TransferField::Recursive => TransferField::Direction,
+        }
+    }
+
+    pub(crate) fn previous(self) -> Self {
+        match self {
+            TransferField::Direction => TransferField::Recursive,
+            TransferField::Local => TransferField::Direction,
+            TransferField::Remote => TransferField::Local,
+            TransferField::Recursive => TransferField::Remote,
+        }
+    }
+}
+
+/// State of the upload/download dialog.
+pub(crate) struct TransferForm {
+    pub(crate) direction: Direction,
+    pub(crate) local: String,
+    pub(crate) remote: String,
+    pub(crate) recursive: bool,
+    pub(crate) field: TransferField,
+}
+
+impl TransferForm {
+    pub(crate) fn new() -> Self {
+        Self {
+            direction: Direction::Upload,
+            local: String::new(),
+            remote: String::new(),
+            recursive: false,
+            field: TransferField::Local,
+        }
+    }
+
+    /// Flips the value of the focused toggle field.
+    pub(crate) fn toggle(&mut self) {
+        match self.field {
+            TransferField::Direction => {
+                self.direction = match self.direction {
+                    Direction::Upload => Direction::Download,
+                    Direction::Download => Direction::Upload,
+                }
+            }
+            TransferField::Recursive => self.recursive = !self.recursive,
+            _ => {}
+        }
+    }
+
+    /// The focused text field, if any.
+    pub(crate) fn input_mut(&mut self) -> Option<&mut String> {
+        match self.field {
+            TransferField::Local => Some(&mut self.local),
+            TransferField::Remote => Some(&mut self.remote),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn is_complete(&self) -> bool {
+        !self.local.trim().is_empty() && !self.remote.trim().is_empty()
+    }
+}
+
+enum TransferEvent {
+    Output(String),
+    /// A finished line of output, reported by the native backend.
+    #[cfg_attr(not(feature = "native-ssh"), allow(dead_code))]
+    Line(String),
+    #[cfg_attr(not(feature = "native-ssh"), allow(dead_code))]
+    Progress(u16),
+    Finished(RunStatus),
+}
+
+/// An scp copy running in a pseudo-terminal so its progress meter can be followed, or an SFTP
+/// copy with the native backend.
+pub(crate) struct Transfer {
+    pub(crate) description: String,
+    /// Completed output lines.
+    pub(crate) log: Vec<String>,
+    /// The line scp is currently redrawing, usually its progress meter.
+    pub(crate) current: String,
+    /// Percentage of the current file, parsed from the progress meter.
+    pub(crate) progress: Option<u16>,
+    pub(crate) status: RunStatus,
+    pub(crate) duration: Option<Duration>,
+    carriage_return: bool,
+    started: Instant,
+    receiver: Receiver<TransferEvent>,
+    /// Audit record completed with the outcome once the transfer ends.
+    pub(crate) audit: Option<Pending>,
+}
+
+impl Transfer {
+    pub(crate) fn start(item: &ConnectionItem, form: &TransferForm, backend: SshBackend) -> Self {
+        match backend {
+            #[cfg(feature = "native-ssh")]
+            SshBackend::Native => Self::start_native(item, form),
+            _ => Self::start_scp(item, form),
+        }
+    }
+
+    fn start_scp(item: &ConnectionItem, form: &TransferForm) -> Self {
+        let remote = ssh::remote_path(item, form.remote.trim());
+        let local = form.local.trim().to_string();
+        let (source, target) = match form.direction {
+            Direction::Upload => (local, remote),
+            Direction::Download => (remote, local),
+        };
+        let description = format!("{} -> {}", source, target);
+        let command = ssh::scp_command(item, &source, &target, form.recursive);
+
+        let (sender, receiver) = channel();
+        let status = match pty::spawn(&command, 24, 200) {
+            Ok(process) => {
+                let pty::PtyProcess { master, mut child } = process;
+                match master.try_clone_reader() {
+                    Ok(mut reader) => {
+                        let output_sender = sender.clone();
+                        let output = thread::spawn(move || {
+                            let mut buf = [0u8; 4096];
+                            // Reading fails with EIO once the process has exited.
+                            while let Ok(n) = reader.read(&mut buf) {
+                                if n == 0 {
+                                    break;
+                                }
+                                let chunk = String::from_utf8_lossy(&buf[..n]).to_string();
+                                if output_sender.send(TransferEvent::Output(chunk)).is_err() {
+                                    break;
+                                }
+                            }
+                        });
+                        thread::spawn(move || {
+                            let status = match child.wait() {
+                                Ok(status) if status.success() => RunStatus::Exited(0),
+                                Ok(status) => RunStatus::Exited(status.exit_code() as i32),
+                                Err(e) => RunStatus::Failed(e.to_string()),
+                            };
+                            // The master side must stay open until the output is drained.
+                            let _ = output.join();
+                            drop(master);
+                            let _ = sender.send(TransferEvent::Finished(status));
+                        });
+                        RunStatus::Running
+                    }
+                    Err(e) => RunStatus::Failed(e.to_string()),
+                }
+            }
+            Err(e) => RunStatus::Failed(format!("Failed to start: {}", e)),
+        };
+
+        Self::new(description, status, receiver)
+    }
+
+    #[cfg(feature = "native-ssh")]
+    fn start_native(item: &ConnectionItem, form: &TransferForm) -> Self {
+        let local = form.local.trim().to_string();
+        let remote = form.remote.trim().to_string();
+        let description = match form.direction {
+            Direction::Upload => format!("{} -> {}:{}", local, item.host, remote),
+            Direction::Download => format!("{}:{} -> {}", item.host, remote, local),
+        };
+
+        let (sender, receiver) = channel();
+        let item = item.clone();
+        let direction = form.direction;
+        let recursive = form.recursive;
+        thread::spawn(move || {
+            if let Some(warning) = native::options_warning(&item) {
+                let _ = sender.send(TransferEvent::Line(warning));
+            }
+            let on_event = |event| {
+                let event = match event {
+                    native::CopyEvent::Copied(path) => TransferEvent::Line(path),
+                    native::CopyEvent::Progress(percent) => TransferEvent::Progress(percent),
+                };
+                let _ = sender.send(event);
+            };
+            let result = match direction {
+                Direction::Upload => native::upload(&item, &local, &remote, recursive, on_event),
+                Direction::Download => {
+                    native::download(&item, &remote, &local, recursive, on_event)
+                }
+            };
+            let status = match result {
+                Ok(()) => RunStatus::Exited(0),
+                Err(e) => RunStatus::Failed(e.to_string()),
+            };
+            let _ = sender.send(TransferEvent::Finished(status));
+        });
+
+        Self::new(description, RunStatus::Running, receiver)
+    }
+
+    fn new(description: String, status: RunStatus, receiver: Receiver<TransferEvent>) -> Self {
+        Self {
+            description,
+            log: Vec::new(),
+            current: String::new(),
+            progress: None,
+            status,
+            duration: None,
+            carriage_return: false,
+            started: Instant::now(),
+            receiver,
+            audit: None,
+        }
+    }
+
+    pub(crate) fn poll(&mut self) {
+        while let Ok(event) = self.receiver.try_recv() {
+            match event {
+                TransferEvent::Output(chunk) => self.push_output(&chunk),
+                TransferEvent::Line(line) => self.log.push(line),
+                TransferEvent::Progress(percent) => self.progress = Some(percent),
+                TransferEvent::Finished(status) => {
+                    if matches!(status, RunStatus::Exited(0)) {
+                        self.progress = Some(100);
+                    }
+                    self.status = status;
+                    self.duration = Some(self.started.elapsed());
+                }
+            }
+        }
+        if !matches!(self.status, RunStatus::Running) {
+            if let Some(audit) = self.audit.take() {
+                audit.finish(&self.status_text());
+            }
+        }
+    }
+
+    pub(crate) fn status_text(&self) -> String {
+        match &self.status {
+            RunStatus::Running => "transferring...".to_string(),
+            RunStatus::Exited(0) => "done".to_string(),
+            RunStatus::Exited(code) => format!("failed with exit code {}", code),
+            RunStatus::Interrupted => "interrupted".to_string(),
+            RunStatus::TimedOut => "timed out".to_string(),
+            RunStatus::Failed(e) => e.clone(),
+        }
+    }
+
+    /// scp redraws its progress meter with carriage returns, so only newlines finish a line and
+    /// a lone carriage return starts the current line over.
+    fn push_output(&mut self, chunk: &str) {
+        for c in chunk.chars() {
+            match c {
+                '\n' => {
+                    let line = std::mem::take(&mut self.current);
+                    if let Some(progress) = parse_progress(&line) {
+                        self.progress = Some(progress);
+                    }
+                    if !line.trim().is_empty() {
+                        self.log.push(line.trim_end().to_string());
+                    }
+                    self.carriage_return = false;
+                }
+                '\r' => self.carriage_return = true,
+                _ => {
+                    if self.carriage_return {
+                        self.current.clear();
+                        self.carriage_return = false;
+                    }
+                    self.current.push(c);
+                }
+            }
+        }
+        if let Some(progress) = parse_progress(&self.current) {
+            self.progress = Some(progress);
+        }
+    }
+}
+
+/// Finds the `NN%` field of an scp progress line.
+fn parse_progress(line: &str) -> Option<u16> {
+    line.split_whitespace()
+        .filter_map(|field| field.strip_suffix('%'))
+        .find_map(|percent| percent.parse::<u16>().ok())
+        .map(|percent| percent.min(100))
+}
@@ -1,9 +1,63 @@
 use crate::model::model::StoredConnection;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs::{File, OpenOptions};
 use std::io::Write;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::{fs, io};
 
+/// Formats `NAME=value` pairs as one line, quoting values with spaces like a shell would.
+pub(crate) fn format_assignments(pairs: &BTreeMap<String, String>) -> String {
+    shell_words::join(
+        pairs
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value)),
+    )
+}
+
+/// Parses a line written by [`format_assignments`]. Words without a `=` are rejected.
+pub(crate) fn parse_assignments(line: &str) -> Result<BTreeMap<String, String>, String> {
+    let words = shell_words::split(line).map_err(|e| e.to_string())?;
+    words
+        .into_iter()
+        .map(|word| match word.split_once('=') {
+            Some((name, value)) if !name.trim().is_empty() => {
+                Ok((name.trim().to_string(), value.to_string()))
+            }
+            _ => Err(format!("expected NAME=value, got {}", word)),
+        })
+        .collect()
+}
+
+/// Whether `host` is an IP address, optionally in brackets, or a syntactically valid host name.
+pub(crate) fn is_valid_host(host: &str) -> bool {
+    let unbracketed = host
+        .strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .unwrap_or(host);
+    if unbracketed.parse::<IpAddr>().is_ok() {
+        return true;
+    }
+    let name = host.strip_suffix('.').unwrap_or(host);
+    let labels: Vec<&str> = name.split('.').collect();
+    !name.is_empty()
+        && name.len() <= 253
+        // Only digits and dots is a mistyped address rather than a name.
+        && !labels.iter().all(|label| label.chars().all(|c| c.is_ascii_digit()))
+        && labels.iter().all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+}
+
 pub(crate) fn remove_whitespace(s: &str) -> String {
     s.split_whitespace().collect::<String>()
 }
@@ -26,12 +80,8 @@ pub(crate) fn append_json_to_file<T: Serialize + Clone + for<'de> Deserialize<'d
 ) -> std::io::Result<()> {
     // Read the existing file and parse it as a JSON array
     let mut existing_data: Vec<T> = match std::fs::read_to_string(file_path) {
-        Ok(file_content) => {
-            match serde_json::from_str(&file_content) {
-                Ok(data) => data,
-                Err(_) => Vec::new(), // If the file is empty or not in JSON array format, create an empty vector
-            }
-        }
+        // If the file is empty or not in JSON array format, create an empty vector
+        Ok(file_content) => serde_json::from_str(&file_content).unwrap_or_default(),
         Err(_) => Vec::new(), // If the file doesn't exist, create an empty vector
     };
 
@@ -52,17 +102,35 @@ pub(crate) fn append_json_to_file<T: Serialize + Clone + for<'de> Deserialize<'d
     Ok(())
 }
 
-pub(crate) fn edit_connection_and_save(
-    data: &StoredConnection,
-    file_path: &str,
-    host: &str,
-) -> io::Result<()> {
+/// A new connection id, unique within this machine: the time in nanoseconds and a counter for
+/// ids made within the same tick.
+pub(crate) fn new_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_nanos());
+    format!("{:x}-{:x}", nanos, COUNTER.fetch_add(1, Ordering::Relaxed))
+}
+
+/// Gives every connection without an id a new one. Returns whether any was missing, i.e. the
+/// file needs to be written to keep them.
+pub(crate) fn assign_missing_ids(connections: &mut [StoredConnection]) -> bool {
+    let mut assigned = false;
+    for connection in connections.iter_mut().filter(|c| c.id.is_empty()) {
+        connection.id = new_id();
+        assigned = true;
+    }
+    assigned
+}
+
+/// Replaces the stored connection with the id of `data`, or appends `data` if there is none.
+pub(crate) fn edit_connection_and_save(data: &StoredConnection, file_path: &str) -> io::Result<()> {
     let mut existing_data: Vec<StoredConnection> = match fs::read_to_string(file_path) {
         Ok(file_content) => serde_json::from_str(&file_content).unwrap_or_else(|_| Vec::new()),
         Err(_) => Vec::new(),
     };
 
-    if let Some(item) = existing_data.iter_mut().find(|item| item.host == host) {
+    if let Some(item) = existing_data.iter_mut().find(|item| item.id == data.id) {
         *item = data.clone();
     } else {
         existing_data.push(data.clone());
@@ -77,3 +145,94 @@ pub(crate) fn edit_connection_and_save(
     file.write_all(json_data.as_bytes())?;
     Ok(())
 }
+
+/// Appends `connections` to the stored ones in one write.
+pub(crate) fn append_connections_and_save(
+    connections: &[StoredConnection],
+    file_path: &str,
+) -> io::Result<()> {
+    let mut existing_data: Vec<StoredConnection> = match fs::read_to_string(file_path) {
+        Ok(file_content) => serde_json::from_str(&file_content).unwrap_or_default(),
+        Err(_) => Vec::new(),
+    };
+    existing_data.extend_from_slice(connections);
+    write_json_to_file(&existing_data, file_path)
+}
+
+/// Removes the stored connection with `id`.
+pub(crate) fn remove_connection_and_save(id: &str, file_path: &str) -> io::Result<()> {
+    let mut existing_data: Vec<StoredConnection> =
+        serde_json::from_str(&fs::read_to_string(file_path)?)?;
+    existing_data.retain(|item| item.id != id);
+    write_json_to_file(&existing_data, file_path)
+}
+
+/// `path` with a leading `~/` replaced by the home directory, as ssh does for `IdentityFile`.
+pub(crate) fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix("~/") {
+        Some(rest) => Path::new(&std::env::var_os("HOME").unwrap_or_default()).join(rest),
+        None => PathBuf::from(path),
+    }
+}
+
+/// Puts `text` on the clipboard of the terminal emulator with an OSC 52 escape sequence, which
+/// also works over ssh.
+pub(crate) fn copy_to_clipboard(text: &str) -> io::Result<()> {
+    use base64::Engine;
+
+    let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", encoded)?;
+    stdout.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_hosts() {
+        for host in [
+            "example.com",
+            "example.com.",
+            "web-1",
+            "my_host",
+            "1password.example",
+            "10.example.com",
+            "192.168.1.1",
+            "::1",
+            "2001:db8::1",
+            "[::1]",
+            "[fe80::1]",
+            "[192.168.1.1]",
+        ] {
+            assert!(is_valid_host(host), "{}", host);
+        }
+        assert!(is_valid_host(&format!("{}.com", "a".repeat(63))));
+    }
+
+    #[test]
+    fn rejects_hosts() {
+        for host in [
+            "",
+            ".",
+            "-web",
+            "web-",
+            "a..b",
+            "host name",
+            "ex@mple.com",
+            "1234",
+            "999.1.1.1",
+            "192.168.1",
+            "[example.com]",
+            "[::1",
+            "::1]",
+            "[::1]:22",
+            "fe80::zz",
+        ] {
+            assert!(!is_valid_host(host), "{}", host);
+        }
+        assert!(!is_valid_host(&format!("{}.com", "a".repeat(64))));
+        assert!(!is_valid_host(&vec!["a".repeat(63); 5].join(".")));
+    }
+}
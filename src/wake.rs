@@ -0,0 +1,217 @@
+use std::io;
+use std::net::{TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::logging::Pending;
+use crate::model::model::ConnectionItem;
+
+/// Where magic packets go when the connection doesn't set a broadcast address.
+pub(crate) const DEFAULT_BROADCAST: &str = "255.255.255.255:9";
+
+/// How long one reachability probe waits for the TCP handshake.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Parses a MAC address written as six hex pairs, separated by `:` or `-` or not at all.
+pub(crate) fn parse_mac(mac: &str) -> Result<[u8; 6], String> {
+    let digits: Vec<u8> = mac.bytes().filter(|b| !matches!(b, b':' | b'-')).collect();
+    let invalid = || format!("not a MAC address: {}", mac);
+    if digits.len() != 12 || !digits.iter().all(u8::is_ascii_hexdigit) {
+        return Err(invalid());
+    }
+    let mut bytes = [0u8; 6];
+    for (byte, pair) in bytes.iter_mut().zip(digits.chunks_exact(2)) {
+        let pair = std::str::from_utf8(pair).map_err(|_| invalid())?;
+        *byte = u8::from_str_radix(pair, 16).map_err(|_| invalid())?;
+    }
+    Ok(bytes)
+}
+
+/// `address` with the Wake-on-LAN port 9 added when it has none. Magic packets are broadcast,
+/// which IPv6 has no notion of, so IPv6 addresses are refused.
+fn broadcast_address(address: &str) -> Result<String, String> {
+    let address = if address.trim().is_empty() {
+        DEFAULT_BROADCAST
+    } else {
+        address.trim()
+    };
+    if address.starts_with('[') || address.matches(':').count() > 1 {
+        return Err(format!(
+            "{} is an IPv6 address, Wake-on-LAN broadcasts need IPv4",
+            address
+        ));
+    }
+    match address.rsplit_once(':') {
+        None => Ok(format!("{}:9", address)),
+        Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {
+            Ok(address.to_string())
+        }
+        Some(_) => Err(format!("expected address[:port], got {}", address)),
+    }
+}
+
+/// Checks a broadcast address as entered in the popup, `host[:port]`.
+pub(crate) fn check_broadcast(address: &str) -> Result<(), String> {
+    broadcast_address(address).map(|_| ())
+}
+
+/// Six `0xff` bytes followed by the MAC address 16 times.
+fn magic_packet(mac: [u8; 6]) -> Vec<u8> {
+    let mut packet = vec![0xff; 6];
+    for _ in 0..16 {
+        packet.extend_from_slice(&mac);
+    }
+    packet
+}
+
+/// Sends a magic packet for the connection's MAC address to its broadcast address.
+pub(crate) fn send(item: &ConnectionItem) -> io::Result<()> {
+    let mac =
+        parse_mac(&item.wake_mac).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let address = broadcast_address(&item.wake_broadcast)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    socket.set_broadcast(true)?;
+    socket.send_to(&magic_packet(mac), address)?;
+    Ok(())
+}
+
+/// The packet sent for the connection, for the audit log.
+pub(crate) fn describe(item: &ConnectionItem) -> String {
+    format!(
+        "magic packet for {} to {}",
+        item.wake_mac,
+        broadcast_address(&item.wake_broadcast).unwrap_or_else(|_| item.wake_broadcast.clone())
+    )
+}
+
+/// Whether the connection's port accepts TCP connections.
+pub(crate) fn is_reachable(item: &ConnectionItem) -> bool {
+    let Ok(addresses) = (item.host.as_str(), item.port.parse().unwrap_or(22)).to_socket_addrs()
+    else {
+        return false;
+    };
+    addresses
+        .into_iter()
+        .any(|address| TcpStream::connect_timeout(&address, PROBE_TIMEOUT).is_ok())
+}
+
+/// Wakes a connection in the background: sends the magic packet unless the host is already up,
+/// then probes its port until it answers.
+pub(crate) struct Waking {
+    pub(crate) id: String,
+    pub(crate) label: String,
+    started: Instant,
+    timeout: Duration,
+    receiver: Receiver<Result<(), String>>,
+    pub(crate) audit: Option<Pending>,
+}
+
+impl Waking {
+    pub(crate) fn start(item: &ConnectionItem, timeout: Duration) -> Self {
+        let (sender, receiver) = channel();
+        let woken = item.clone();
+        thread::spawn(move || {
+            let _ = sender.send(wake_and_wait(&woken, timeout));
+        });
+        Self {
+            id: item.id.clone(),
+            label: item.label.clone(),
+            started: Instant::now(),
+            timeout,
+            receiver,
+            audit: None,
+        }
+    }
+
+    /// The outcome, once the host answered or the time ran out.
+    pub(crate) fn poll(&self) -> Option<Result<(), String>> {
+        self.receiver.try_recv().ok()
+    }
+
+    pub(crate) fn status_text(&self) -> String {
+        format!(
+            "Waking {}, waiting {}s of {}s for it to come up (Esc to cancel)",
+            self.label,
+            self.started.elapsed().as_secs(),
+            self.timeout.as_secs()
+        )
+    }
+}
+
+fn wake_and_wait(item: &ConnectionItem, timeout: Duration) -> Result<(), String> {
+    if is_reachable(item) {
+        return Ok(());
+    }
+    send(item).map_err(|e| format!("Failed to send the wake packet: {}", e))?;
+    let started = Instant::now();
+    while started.elapsed() < timeout {
+        let attempt = Instant::now();
+        if is_reachable(item) {
+            return Ok(());
+        }
+        // Probes of unreachable hosts can fail at once, don't spin.
+        thread::sleep(Duration::from_secs(1).saturating_sub(attempt.elapsed()));
+    }
+    Err(format!(
+        "{} didn't come up within {}s",
+        item.label,
+        timeout.as_secs()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_macs() {
+        let mac = [0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff];
+        assert_eq!(parse_mac("aa:bb:cc:dd:ee:ff"), Ok(mac));
+        assert_eq!(parse_mac("AA-BB-CC-DD-EE-FF"), Ok(mac));
+        assert_eq!(parse_mac("aabbccddeeff"), Ok(mac));
+        assert!(parse_mac("aa:bb:cc:dd:ee").is_err());
+        assert!(parse_mac("gg:bb:cc:dd:ee:ff").is_err());
+        assert!(parse_mac("+a:bb:cc:dd:ee:ff").is_err());
+        // Multi-byte characters are rejected, not sliced through.
+        assert!(parse_mac("aa:bb:cc:dd:eé:f").is_err());
+        assert!(parse_mac("aa:bb:cc:dd:ee:fé").is_err());
+    }
+
+    #[test]
+    fn adds_the_default_port() {
+        assert_eq!(broadcast_address(""), Ok(DEFAULT_BROADCAST.to_string()));
+        assert_eq!(
+            broadcast_address(" 192.168.1.255 "),
+            Ok("192.168.1.255:9".to_string())
+        );
+        assert_eq!(
+            broadcast_address("192.168.1.255:7"),
+            Ok("192.168.1.255:7".to_string())
+        );
+        assert_eq!(
+            broadcast_address("lan.example"),
+            Ok("lan.example:9".to_string())
+        );
+    }
+
+    #[test]
+    fn refuses_bad_addresses() {
+        for address in [
+            "192.168.1.255:",
+            ":9",
+            "192.168.1.255:port",
+            "192.168.1.255:70000",
+        ] {
+            assert!(broadcast_address(address).is_err(), "{}", address);
+        }
+        for address in ["ff02::1", "[ff02::1]:9", "[ff02::1]", "fe80::1:9"] {
+            assert!(
+                broadcast_address(address).is_err_and(|e| e.contains("IPv4")),
+                "{}",
+                address
+            );
+        }
+    }
+}